@@ -1,9 +1,22 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+
 use super::error::Result;
 use super::future::BoxFuture;
 use super::keys::{wrap::WrapKeyMethod, PassKey};
 use super::options::IntoOptions;
-use super::store::{Backend, ManageBackend, QueryBackend, Scan, Session, Store};
-use super::types::{Entry, EntryKind, EntryOperation, EntryTag, TagFilter};
+use super::store::{Backend, ChangeStream, ManageBackend, QueryBackend, Scan, Session, Store};
+use super::types::{
+    AuditRecord, Entry, EntryCacheStats, EntryHistory, EntryKind, EntryOperation, EntryTag,
+    ProfileQuota, ProfileUsage, SoftDelete, TagFilter,
+};
+
+#[cfg(feature = "memory")]
+use super::memory::MemoryStore;
 
 #[cfg(feature = "postgres")]
 use super::postgres::PostgresStore;
@@ -14,12 +27,243 @@ use super::sqlite::SqliteStore;
 /// A generic `Store` implementation for any supported backend
 pub type AnyStore = Store<AnyBackend>;
 
+static BACKENDS: Lazy<RwLock<HashMap<String, Arc<dyn BackendFactory>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Create, open, or remove stores for a URI scheme registered with `register_backend`
+pub trait BackendFactory: Send + Sync {
+    /// Open an existing store given its URI
+    fn open_backend<'a>(
+        &'a self,
+        uri: &'a str,
+        method: Option<WrapKeyMethod>,
+        pass_key: PassKey<'a>,
+        profile: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<AnyBackend>>;
+
+    /// Provision a new store given its URI
+    fn provision_backend<'a>(
+        &'a self,
+        uri: &'a str,
+        method: WrapKeyMethod,
+        pass_key: PassKey<'a>,
+        profile: Option<&'a str>,
+        recreate: bool,
+    ) -> BoxFuture<'a, Result<AnyBackend>>;
+
+    /// Remove an existing store given its URI
+    fn remove_backend<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<bool>>;
+}
+
+/// Register a backend implementation for a custom URI scheme
+///
+/// The schemes of the enabled built-in backends are reserved and may not be registered.
+pub fn register_backend(scheme: &str, factory: impl BackendFactory + 'static) -> Result<()> {
+    if is_builtin_scheme(scheme) {
+        return Err(err_msg!(
+            Duplicate,
+            "Cannot register a backend for built-in scheme: {}",
+            scheme
+        ));
+    }
+    let mut backends = BACKENDS
+        .write()
+        .map_err(|_| err_msg!(Unexpected, "Error acquiring backend registry"))?;
+    if backends.contains_key(scheme) {
+        return Err(err_msg!(
+            Duplicate,
+            "A backend is already registered for scheme: {}",
+            scheme
+        ));
+    }
+    backends.insert(scheme.to_string(), Arc::new(factory));
+    Ok(())
+}
+
+/// Remove a backend implementation registered for a custom URI scheme
+pub fn unregister_backend(scheme: &str) -> Result<bool> {
+    Ok(BACKENDS
+        .write()
+        .map_err(|_| err_msg!(Unexpected, "Error acquiring backend registry"))?
+        .remove(scheme)
+        .is_some())
+}
+
+fn is_builtin_scheme(scheme: &str) -> bool {
+    match scheme {
+        #[cfg(feature = "memory")]
+        "memory" => true,
+        #[cfg(feature = "postgres")]
+        "postgres" => true,
+        #[cfg(feature = "sqlite")]
+        "sqlite" => true,
+        _ => false,
+    }
+}
+
+fn registered_backend(scheme: &str) -> Result<Arc<dyn BackendFactory>> {
+    BACKENDS
+        .read()
+        .map_err(|_| err_msg!(Unexpected, "Error acquiring backend registry"))?
+        .get(scheme)
+        .cloned()
+        .ok_or_else(|| err_msg!(Unsupported, "Invalid backend: {}", scheme))
+}
+
+impl AnyBackend {
+    /// Wrap a backend implementation for use with `AnyStore`
+    pub fn custom<B>(backend: B) -> Self
+    where
+        B: Backend + Debug + 'static,
+        B::Session: Debug + 'static,
+    {
+        Self::Custom(Box::new(CustomBackend(backend)))
+    }
+}
+
+/// A backend implementation which may be contained by `AnyBackend`
+pub trait DynBackend: Backend<Session = AnyQueryBackend> + Debug {}
+
+impl<B> DynBackend for B where B: Backend<Session = AnyQueryBackend> + Debug {}
+
+/// A session implementation which may be contained by `AnyQueryBackend`
+pub trait DynQueryBackend: QueryBackend + Debug {
+    /// Close the current store session
+    fn close_boxed(self: Box<Self>, commit: bool) -> BoxFuture<'static, Result<()>>;
+}
+
+impl<Q> DynQueryBackend for Q
+where
+    Q: QueryBackend + Debug + 'static,
+{
+    fn close_boxed(self: Box<Self>, commit: bool) -> BoxFuture<'static, Result<()>> {
+        (*self).close(commit)
+    }
+}
+
+#[derive(Debug)]
+struct CustomBackend<B>(B);
+
+impl<B> Backend for CustomBackend<B>
+where
+    B: Backend + Debug,
+    B::Session: Debug + 'static,
+{
+    type Session = AnyQueryBackend;
+
+    fn create_profile(&self, name: Option<String>) -> BoxFuture<'_, Result<String>> {
+        self.0.create_profile(name)
+    }
+
+    fn get_profile_name(&self) -> &str {
+        self.0.get_profile_name()
+    }
+
+    fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool>> {
+        self.0.remove_profile(name)
+    }
+
+    fn set_profile_quota(
+        &self,
+        profile: Option<String>,
+        quota: ProfileQuota,
+    ) -> BoxFuture<'_, Result<()>> {
+        self.0.set_profile_quota(profile, quota)
+    }
+
+    fn profile_usage(&self, profile: Option<String>) -> BoxFuture<'_, Result<ProfileUsage>> {
+        self.0.profile_usage(profile)
+    }
+
+    fn scan(
+        &self,
+        profile: Option<String>,
+        kind: EntryKind,
+        category: String,
+        tag_filter: Option<TagFilter>,
+        offset: Option<i64>,
+        limit: Option<i64>,
+    ) -> BoxFuture<'_, Result<Scan<'static, Entry>>> {
+        self.0
+            .scan(profile, kind, category, tag_filter, offset, limit)
+    }
+
+    fn session(&self, profile: Option<String>, transaction: bool) -> Result<Self::Session> {
+        let session = self.0.session(profile, transaction)?;
+        Ok(AnyQueryBackend::Custom(Box::new(session)))
+    }
+
+    fn rekey_backend(
+        &mut self,
+        method: WrapKeyMethod,
+        pass_key: PassKey<'_>,
+    ) -> BoxFuture<'_, Result<()>> {
+        self.0.rekey_backend(method, pass_key)
+    }
+
+    fn backup_to(&self, path: String) -> BoxFuture<'_, Result<()>> {
+        self.0.backup_to(path)
+    }
+
+    fn entry_cache_stats(&self) -> Option<EntryCacheStats> {
+        self.0.entry_cache_stats()
+    }
+
+    fn default_timeout(&self) -> Option<Duration> {
+        self.0.default_timeout()
+    }
+
+    fn subscribe(
+        &self,
+        profile: Option<String>,
+        category: Option<String>,
+    ) -> BoxFuture<'_, Result<ChangeStream>> {
+        self.0.subscribe(profile, category)
+    }
+
+    fn scan_audit(
+        &self,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> BoxFuture<'_, Result<Vec<AuditRecord>>> {
+        self.0.scan_audit(start, end)
+    }
+
+    fn set_audit_actor(&self, actor: Option<String>) {
+        self.0.set_audit_actor(actor)
+    }
+
+    fn set_soft_delete(&self, policy: SoftDelete) -> Result<()> {
+        self.0.set_soft_delete(policy)
+    }
+
+    fn purge_deleted(
+        &self,
+        profile: Option<String>,
+        older_than: Duration,
+    ) -> BoxFuture<'_, Result<i64>> {
+        self.0.purge_deleted(profile, older_than)
+    }
+
+    fn set_entry_history(&self, policy: EntryHistory) -> Result<()> {
+        self.0.set_entry_history(policy)
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<()>> {
+        self.0.close()
+    }
+}
+
 /// A generic `Session` implementation for any supported backend
 pub type AnySession = Session<AnyQueryBackend>;
 
 /// An enumeration of supported store backends
 #[derive(Debug)]
 pub enum AnyBackend {
+    /// An in-memory store
+    #[cfg(feature = "memory")]
+    Memory(MemoryStore),
+
     /// A PostgreSQL store
     #[cfg(feature = "postgres")]
     Postgres(PostgresStore),
@@ -28,6 +272,9 @@ pub enum AnyBackend {
     #[cfg(feature = "sqlite")]
     Sqlite(SqliteStore),
 
+    /// A backend registered for a custom URI scheme
+    Custom(Box<dyn DynBackend>),
+
     #[allow(unused)]
     #[doc(hidden)]
     Other,
@@ -36,12 +283,17 @@ pub enum AnyBackend {
 macro_rules! with_backend {
     ($slf:ident, $ident:ident, $body:expr) => {
         match $slf {
+            #[cfg(feature = "memory")]
+            Self::Memory($ident) => $body,
+
             #[cfg(feature = "postgres")]
             Self::Postgres($ident) => $body,
 
             #[cfg(feature = "sqlite")]
             Self::Sqlite($ident) => $body,
 
+            Self::Custom($ident) => $body,
+
             _ => unreachable!(),
         }
     };
@@ -62,6 +314,18 @@ impl Backend for AnyBackend {
         with_backend!(self, store, store.remove_profile(name))
     }
 
+    fn set_profile_quota(
+        &self,
+        profile: Option<String>,
+        quota: ProfileQuota,
+    ) -> BoxFuture<'_, Result<()>> {
+        with_backend!(self, store, store.set_profile_quota(profile, quota))
+    }
+
+    fn profile_usage(&self, profile: Option<String>) -> BoxFuture<'_, Result<ProfileUsage>> {
+        with_backend!(self, store, store.profile_usage(profile))
+    }
+
     fn scan(
         &self,
         profile: Option<String>,
@@ -80,6 +344,12 @@ impl Backend for AnyBackend {
 
     fn session(&self, profile: Option<String>, transaction: bool) -> Result<Self::Session> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::Memory(store) => {
+                let session = store.session(profile, transaction)?;
+                Ok(AnyQueryBackend::MemorySession(session))
+            }
+
             #[cfg(feature = "postgres")]
             Self::Postgres(store) => {
                 let session = store.session(profile, transaction)?;
@@ -93,6 +363,8 @@ impl Backend for AnyBackend {
                 Ok(AnyQueryBackend::SqliteSession(session))
             }
 
+            Self::Custom(store) => store.session(profile, transaction),
+
             _ => unreachable!(),
         }
     }
@@ -105,6 +377,54 @@ impl Backend for AnyBackend {
         with_backend!(self, store, store.rekey_backend(method, pass_key))
     }
 
+    fn backup_to(&self, path: String) -> BoxFuture<'_, Result<()>> {
+        with_backend!(self, store, store.backup_to(path))
+    }
+
+    fn entry_cache_stats(&self) -> Option<EntryCacheStats> {
+        with_backend!(self, store, store.entry_cache_stats())
+    }
+
+    fn default_timeout(&self) -> Option<Duration> {
+        with_backend!(self, store, store.default_timeout())
+    }
+
+    fn subscribe(
+        &self,
+        profile: Option<String>,
+        category: Option<String>,
+    ) -> BoxFuture<'_, Result<ChangeStream>> {
+        with_backend!(self, store, store.subscribe(profile, category))
+    }
+
+    fn scan_audit(
+        &self,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> BoxFuture<'_, Result<Vec<AuditRecord>>> {
+        with_backend!(self, store, store.scan_audit(start, end))
+    }
+
+    fn set_audit_actor(&self, actor: Option<String>) {
+        with_backend!(self, store, store.set_audit_actor(actor))
+    }
+
+    fn set_soft_delete(&self, policy: SoftDelete) -> Result<()> {
+        with_backend!(self, store, store.set_soft_delete(policy))
+    }
+
+    fn purge_deleted(
+        &self,
+        profile: Option<String>,
+        older_than: Duration,
+    ) -> BoxFuture<'_, Result<i64>> {
+        with_backend!(self, store, store.purge_deleted(profile, older_than))
+    }
+
+    fn set_entry_history(&self, policy: EntryHistory) -> Result<()> {
+        with_backend!(self, store, store.set_entry_history(policy))
+    }
+
     fn close(&self) -> BoxFuture<'_, Result<()>> {
         with_backend!(self, store, store.close())
     }
@@ -113,6 +433,10 @@ impl Backend for AnyBackend {
 /// An enumeration of supported backend session types
 #[derive(Debug)]
 pub enum AnyQueryBackend {
+    /// An in-memory store session
+    #[cfg(feature = "memory")]
+    MemorySession(<MemoryStore as Backend>::Session),
+
     /// A PostgreSQL store session
     #[cfg(feature = "postgres")]
     PostgresSession(<PostgresStore as Backend>::Session),
@@ -121,6 +445,9 @@ pub enum AnyQueryBackend {
     #[cfg(feature = "sqlite")]
     SqliteSession(<SqliteStore as Backend>::Session),
 
+    /// A session for a backend registered for a custom URI scheme
+    Custom(Box<dyn DynQueryBackend>),
+
     #[allow(unused)]
     #[doc(hidden)]
     Other,
@@ -134,12 +461,17 @@ impl QueryBackend for AnyQueryBackend {
         tag_filter: Option<TagFilter>,
     ) -> BoxFuture<'q, Result<i64>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.count(kind, category, tag_filter),
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => session.count(kind, category, tag_filter),
 
             #[cfg(feature = "sqlite")]
             Self::SqliteSession(session) => session.count(kind, category, tag_filter),
 
+            Self::Custom(session) => session.count(kind, category, tag_filter),
+
             _ => unreachable!(),
         }
     }
@@ -152,12 +484,17 @@ impl QueryBackend for AnyQueryBackend {
         for_update: bool,
     ) -> BoxFuture<'q, Result<Option<Entry>>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.fetch(kind, category, name, for_update),
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => session.fetch(kind, category, name, for_update),
 
             #[cfg(feature = "sqlite")]
             Self::SqliteSession(session) => session.fetch(kind, category, name, for_update),
 
+            Self::Custom(session) => session.fetch(kind, category, name, for_update),
+
             _ => unreachable!(),
         }
     }
@@ -171,6 +508,11 @@ impl QueryBackend for AnyQueryBackend {
         for_update: bool,
     ) -> BoxFuture<'q, Result<Vec<Entry>>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => {
+                session.fetch_all(kind, category, tag_filter, limit, for_update)
+            }
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => {
                 session.fetch_all(kind, category, tag_filter, limit, for_update)
@@ -181,6 +523,10 @@ impl QueryBackend for AnyQueryBackend {
                 session.fetch_all(kind, category, tag_filter, limit, for_update)
             }
 
+            Self::Custom(session) => {
+                session.fetch_all(kind, category, tag_filter, limit, for_update)
+            }
+
             _ => unreachable!(),
         }
     }
@@ -192,12 +538,61 @@ impl QueryBackend for AnyQueryBackend {
         tag_filter: Option<TagFilter>,
     ) -> BoxFuture<'q, Result<i64>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.remove_all(kind, category, tag_filter),
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => session.remove_all(kind, category, tag_filter),
 
             #[cfg(feature = "sqlite")]
             Self::SqliteSession(session) => session.remove_all(kind, category, tag_filter),
 
+            Self::Custom(session) => session.remove_all(kind, category, tag_filter),
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn fetch_history<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<Entry>>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.fetch_history(kind, category, name),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.fetch_history(kind, category, name),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.fetch_history(kind, category, name),
+
+            Self::Custom(session) => session.fetch_history(kind, category, name),
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn undelete<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+    ) -> BoxFuture<'q, Result<()>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.undelete(kind, category, name),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.undelete(kind, category, name),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.undelete(kind, category, name),
+
+            Self::Custom(session) => session.undelete(kind, category, name),
+
             _ => unreachable!(),
         }
     }
@@ -213,6 +608,11 @@ impl QueryBackend for AnyQueryBackend {
         expiry_ms: Option<i64>,
     ) -> BoxFuture<'q, Result<()>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => {
+                session.update(kind, operation, category, name, value, tags, expiry_ms)
+            }
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => {
                 session.update(kind, operation, category, name, value, tags, expiry_ms)
@@ -223,18 +623,61 @@ impl QueryBackend for AnyQueryBackend {
                 session.update(kind, operation, category, name, value, tags, expiry_ms)
             }
 
+            Self::Custom(session) => {
+                session.update(kind, operation, category, name, value, tags, expiry_ms)
+            }
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn set_audit_actor(&mut self, actor: Option<String>) {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.set_audit_actor(actor),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.set_audit_actor(actor),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.set_audit_actor(actor),
+
+            Self::Custom(session) => session.set_audit_actor(actor),
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn set_profile(&mut self, profile: String) -> Result<()> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.set_profile(profile),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.set_profile(profile),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.set_profile(profile),
+
+            Self::Custom(session) => session.set_profile(profile),
+
             _ => unreachable!(),
         }
     }
 
     fn close(self, commit: bool) -> BoxFuture<'static, Result<()>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => Box::pin(session.close(commit)),
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => Box::pin(session.close(commit)),
 
             #[cfg(feature = "sqlite")]
             Self::SqliteSession(session) => Box::pin(session.close(commit)),
 
+            Self::Custom(session) => session.close_boxed(commit),
+
             _ => unreachable!(),
         }
     }
@@ -254,6 +697,13 @@ impl<'a> ManageBackend<'a> for &'a str {
             debug!("Open store with options: {:?}", &opts);
 
             match opts.schema.as_ref() {
+                #[cfg(feature = "memory")]
+                "memory" => {
+                    let opts = super::memory::MemoryStoreOptions::new(opts)?;
+                    let mgr = opts.open(method, pass_key, profile).await?;
+                    Ok(Store::new(AnyBackend::Memory(mgr.into_inner())))
+                }
+
                 #[cfg(feature = "postgres")]
                 "postgres" => {
                     let opts = super::postgres::PostgresStoreOptions::new(opts)?;
@@ -268,7 +718,13 @@ impl<'a> ManageBackend<'a> for &'a str {
                     Ok(Store::new(AnyBackend::Sqlite(mgr.into_inner())))
                 }
 
-                _ => Err(err_msg!(Unsupported, "Invalid backend: {}", &opts.schema)),
+                scheme => {
+                    let factory = registered_backend(scheme)?;
+                    let backend = factory
+                        .open_backend(self, method, pass_key, profile)
+                        .await?;
+                    Ok(Store::new(backend))
+                }
             }
         })
     }
@@ -285,6 +741,13 @@ impl<'a> ManageBackend<'a> for &'a str {
             debug!("Provision store with options: {:?}", &opts);
 
             match opts.schema.as_ref() {
+                #[cfg(feature = "memory")]
+                "memory" => {
+                    let opts = super::memory::MemoryStoreOptions::new(opts)?;
+                    let mgr = opts.provision(method, pass_key, profile, recreate).await?;
+                    Ok(Store::new(AnyBackend::Memory(mgr.into_inner())))
+                }
+
                 #[cfg(feature = "postgres")]
                 "postgres" => {
                     let opts = super::postgres::PostgresStoreOptions::new(opts)?;
@@ -299,7 +762,13 @@ impl<'a> ManageBackend<'a> for &'a str {
                     Ok(Store::new(AnyBackend::Sqlite(mgr.into_inner())))
                 }
 
-                _ => Err(err_msg!(Unsupported, "Invalid backend: {}", &opts.schema)),
+                scheme => {
+                    let factory = registered_backend(scheme)?;
+                    let backend = factory
+                        .provision_backend(self, method, pass_key, profile, recreate)
+                        .await?;
+                    Ok(Store::new(backend))
+                }
             }
         })
     }
@@ -310,6 +779,12 @@ impl<'a> ManageBackend<'a> for &'a str {
             debug!("Remove store with options: {:?}", &opts);
 
             match opts.schema.as_ref() {
+                #[cfg(feature = "memory")]
+                "memory" => {
+                    let opts = super::memory::MemoryStoreOptions::new(opts)?;
+                    Ok(opts.remove().await?)
+                }
+
                 #[cfg(feature = "postgres")]
                 "postgres" => {
                     let opts = super::postgres::PostgresStoreOptions::new(opts)?;
@@ -322,7 +797,10 @@ impl<'a> ManageBackend<'a> for &'a str {
                     Ok(opts.remove().await?)
                 }
 
-                _ => Err(err_msg!(Unsupported, "Invalid backend: {}", &opts.schema)),
+                scheme => {
+                    let factory = registered_backend(scheme)?;
+                    factory.remove_backend(self).await
+                }
             }
         })
     }
@@ -0,0 +1,295 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{TimeZone, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use super::error::Result;
+use super::keys::{
+    encrypt::{aead::ChaChaEncrypt, SymEncrypt, SymEncryptKey},
+    store::EncKey,
+    wrap::WrapKey,
+};
+use super::types::{AuditOperation, AuditRecord, EntryOperation};
+
+type AuditEncrypt = ChaChaEncrypt;
+
+/// The name of the configuration value holding the wrapped audit key
+pub(crate) const AUDIT_KEY_CONFIG: &'static str = "audit_key";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AuditKey {
+    enc_key: EncKey<AuditEncrypt>,
+    mac_key: EncKey<AuditEncrypt>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct AuditPayload {
+    operation: AuditOperation,
+    profile: Option<String>,
+    category: Option<String>,
+    name_hash: Option<String>,
+    timestamp: i64,
+    actor: Option<String>,
+}
+
+/// An operation to be recorded in the audit log
+#[derive(Debug)]
+pub(crate) struct AuditEvent {
+    operation: AuditOperation,
+    profile: Option<String>,
+    category: Option<String>,
+    name_hash: Option<String>,
+    actor: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(operation: AuditOperation, profile: Option<&str>, actor: Option<String>) -> Self {
+        Self {
+            operation,
+            profile: profile.map(str::to_string),
+            category: None,
+            name_hash: None,
+            actor,
+        }
+    }
+
+    pub fn entry(mut self, category: &str, name: Option<&str>) -> Self {
+        self.category = Some(category.to_string());
+        self.name_hash = name.map(|name| hex::encode(Sha256::digest(name.as_bytes())));
+        self
+    }
+}
+
+/// The audit log settings of a store session
+#[derive(Debug)]
+pub(crate) struct SessionAudit {
+    log: Arc<AuditLog>,
+    profile: String,
+    actor: Option<String>,
+}
+
+impl SessionAudit {
+    pub fn new(log: Arc<AuditLog>, profile: String) -> Self {
+        let actor = log.actor();
+        Self {
+            log,
+            profile,
+            actor,
+        }
+    }
+
+    pub fn set_actor(&mut self, actor: Option<String>) {
+        self.actor = actor;
+    }
+
+    pub fn set_profile(&mut self, profile: String) {
+        self.profile = profile;
+    }
+
+    pub fn event(&self, operation: AuditOperation) -> (Arc<AuditLog>, AuditEvent) {
+        (
+            self.log.clone(),
+            AuditEvent::new(operation, Some(&self.profile), self.actor.clone()),
+        )
+    }
+}
+
+/// An encrypted, append-only log of store operations
+///
+/// Each row is sealed with an HMAC over the previous row's MAC, its own
+/// identifier and its ciphertext, so that any removal, reordering or
+/// modification of rows is detected when the log is scanned.
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+    key: AuditKey,
+    actor: Mutex<Option<String>>,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self {
+            key: AuditKey {
+                enc_key: EncKey::<AuditEncrypt>::random_key(),
+                mac_key: EncKey::<AuditEncrypt>::random_key(),
+            },
+            actor: Mutex::new(None),
+        }
+    }
+
+    pub fn from_wrapped(enc_key: &str, wrap_key: &WrapKey) -> Result<Self> {
+        let enc_key = hex::decode(enc_key).map_err(err_map!(Unsupported, "Invalid audit key"))?;
+        let mut data = wrap_key
+            .unwrap_data(enc_key)
+            .map_err(err_map!(Encryption, "Error decrypting audit key"))?;
+        let key = serde_json::from_slice(&data).map_err(err_map!(Unsupported, "Invalid audit key"));
+        data.zeroize();
+        Ok(Self {
+            key: key?,
+            actor: Mutex::new(None),
+        })
+    }
+
+    pub fn to_wrapped(&self, wrap_key: &WrapKey) -> Result<String> {
+        let data = serde_json::to_vec(&self.key)
+            .map_err(err_map!(Unexpected, "Error serializing audit key"))?;
+        Ok(hex::encode(wrap_key.wrap_data(data.into())?))
+    }
+
+    /// Get the default actor label for new sessions
+    pub fn actor(&self) -> Option<String> {
+        self.actor.lock().unwrap().clone()
+    }
+
+    pub fn set_actor(&self, actor: Option<String>) {
+        *self.actor.lock().unwrap() = actor;
+    }
+
+    fn mac(&self, prev_mac: &[u8], id: i64, data: &[u8]) -> Result<Hmac<Sha256>> {
+        let mut mac = Hmac::<Sha256>::new_varkey(self.key.mac_key.as_bytes())
+            .map_err(|e| err_msg!(Encryption, "{}", e))?;
+        mac.update(prev_mac);
+        mac.update(&id.to_be_bytes());
+        mac.update(data);
+        Ok(mac)
+    }
+
+    /// Encrypt an event and produce the next row of the log, given the
+    /// identifier and MAC of the last row (if any)
+    pub fn seal(
+        &self,
+        last: Option<(i64, Vec<u8>)>,
+        event: AuditEvent,
+    ) -> Result<(i64, Vec<u8>, Vec<u8>)> {
+        let (id, prev_mac) = match last {
+            Some((last_id, last_mac)) => (last_id + 1, last_mac),
+            None => (1, Vec::new()),
+        };
+        let payload = AuditPayload {
+            operation: event.operation,
+            profile: event.profile,
+            category: event.category,
+            name_hash: event.name_hash,
+            timestamp: Utc::now().timestamp_millis(),
+            actor: event.actor,
+        };
+        let data = serde_json::to_vec(&payload)
+            .map_err(err_map!(Unexpected, "Error serializing audit record"))?;
+        let data = AuditEncrypt::encrypt(data.into(), &self.key.enc_key, None)?;
+        let mac = self
+            .mac(&prev_mac, id, &data)?
+            .finalize()
+            .into_bytes()
+            .to_vec();
+        Ok((id, data, mac))
+    }
+
+    /// Verify and decrypt a consecutive sequence of rows beginning at `start`
+    ///
+    /// Unless `start` is the first row of the log, the MAC of the preceding
+    /// row must be provided in order to verify the chain.
+    pub fn open_rows(
+        &self,
+        start: i64,
+        prev_mac: Option<Vec<u8>>,
+        rows: Vec<(i64, Vec<u8>, Vec<u8>)>,
+    ) -> Result<Vec<AuditRecord>> {
+        let mut prev_mac = match prev_mac {
+            Some(mac) => mac,
+            None if start <= 1 || rows.is_empty() => Vec::new(),
+            None => return Err(err_msg!(Encryption, "Audit log integrity check failed")),
+        };
+        let mut expect_id = start.max(1);
+        let mut records = Vec::with_capacity(rows.len());
+        for (id, data, mac) in rows {
+            if id != expect_id {
+                return Err(err_msg!(Encryption, "Audit log integrity check failed"));
+            }
+            self.mac(&prev_mac, id, &data)?
+                .verify(&mac)
+                .map_err(|_| err_msg!(Encryption, "Audit log integrity check failed"))?;
+            let data = AuditEncrypt::decrypt(data, &self.key.enc_key)?;
+            let payload: AuditPayload = serde_json::from_slice(&data)
+                .map_err(err_map!(Unexpected, "Error deserializing audit record"))?;
+            records.push(AuditRecord {
+                id,
+                operation: payload.operation,
+                profile: payload.profile,
+                category: payload.category,
+                name_hash: payload.name_hash,
+                timestamp: Utc.timestamp_millis(payload.timestamp),
+                actor: payload.actor,
+            });
+            prev_mac = mac;
+            expect_id += 1;
+        }
+        Ok(records)
+    }
+}
+
+impl From<EntryOperation> for AuditOperation {
+    fn from(operation: EntryOperation) -> Self {
+        match operation {
+            EntryOperation::Insert => Self::Insert,
+            EntryOperation::Replace => Self::Replace,
+            EntryOperation::Remove => Self::Remove,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str) -> AuditEvent {
+        AuditEvent::new(
+            AuditOperation::Insert,
+            Some("profile"),
+            Some("actor".into()),
+        )
+        .entry("category", Some(name))
+    }
+
+    #[test]
+    fn audit_chain_round_trip() {
+        let log = AuditLog::new();
+        let first = log.seal(None, event("a")).unwrap();
+        let second = log
+            .seal(Some((first.0, first.2.clone())), event("b"))
+            .unwrap();
+        assert_eq!(second.0, 2);
+
+        let records = log
+            .open_rows(1, None, vec![first.clone(), second.clone()])
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].operation, AuditOperation::Insert);
+        assert_eq!(records[1].category.as_deref(), Some("category"));
+        assert_eq!(records[1].actor.as_deref(), Some("actor"));
+
+        // a range which does not start at the first row requires the prior MAC
+        assert!(log.open_rows(2, None, vec![second.clone()]).is_err());
+        assert!(log
+            .open_rows(2, Some(first.2.clone()), vec![second.clone()])
+            .is_ok());
+
+        // removed rows are detected
+        assert!(log.open_rows(1, None, vec![second.clone()]).is_err());
+
+        // modified rows are detected
+        let mut altered = second.clone();
+        altered.1 = log.seal(None, event("c")).unwrap().1;
+        assert!(log.open_rows(1, None, vec![first, altered]).is_err());
+    }
+
+    #[test]
+    fn audit_key_wrap() {
+        let wrap_key = WrapKey::random().unwrap();
+        let log = AuditLog::new();
+        let row = log.seal(None, event("a")).unwrap();
+        let wrapped = log.to_wrapped(&wrap_key).unwrap();
+        let loaded = AuditLog::from_wrapped(&wrapped, &wrap_key).unwrap();
+        assert_eq!(loaded.open_rows(1, None, vec![row]).unwrap().len(), 1);
+    }
+}
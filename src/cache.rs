@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use zeroize::Zeroize;
+
+use super::types::{Entry, EntryCacheStats, EntryKind, ProfileId};
+
+/// The identifier of a cached entry
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    pub profile_id: ProfileId,
+    pub kind: EntryKind,
+    pub category: String,
+    pub name: String,
+}
+
+impl CacheKey {
+    pub fn new(profile_id: ProfileId, kind: EntryKind, category: &str, name: &str) -> Self {
+        Self {
+            profile_id,
+            kind,
+            category: category.to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+
+/// A set of cached entries to be invalidated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum CacheTarget {
+    Entry(CacheKey),
+    Category(ProfileId, EntryKind, String),
+}
+
+#[derive(Debug)]
+struct CachedEntry(Entry);
+
+impl Drop for CachedEntry {
+    fn drop(&mut self) {
+        // the entry value is zeroized by SecretBytes
+        self.0.category.zeroize();
+        self.0.name.zeroize();
+        if let Some(tags) = self.0.tags.as_mut() {
+            tags.zeroize();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, (u64, CachedEntry)>,
+    order: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    generation: u64,
+}
+
+impl CacheState {
+    fn touch(&mut self, key: &CacheKey) -> Option<&CachedEntry> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (last, entry) = self.entries.get_mut(key)?;
+        let key = self.order.remove(last).unwrap();
+        *last = tick;
+        self.order.insert(tick, key);
+        Some(entry)
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some((tick, _)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+        }
+    }
+}
+
+/// A bounded LRU cache of decrypted entries, shared by the sessions of a store
+#[derive(Debug)]
+pub(crate) struct EntryCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl EntryCache {
+    /// Create a new cache, or `None` if the capacity is zero
+    pub fn new(capacity: usize) -> Option<Self> {
+        if capacity == 0 {
+            None
+        } else {
+            Some(Self {
+                capacity,
+                state: Mutex::new(CacheState::default()),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            })
+        }
+    }
+
+    /// Get the current generation of the cache, to be passed to `insert`
+    pub fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<Entry> {
+        let found = self
+            .state
+            .lock()
+            .unwrap()
+            .touch(key)
+            .map(|entry| entry.0.clone());
+        if found.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// Add an entry to the cache
+    ///
+    /// The entry is discarded if any invalidation has occurred since `generation`
+    /// was obtained, as it may have been loaded before a concurrent update.
+    pub fn insert(&self, key: CacheKey, entry: Entry, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+        state.remove(&key);
+        while state.entries.len() >= self.capacity {
+            match state.order.values().next().cloned() {
+                Some(oldest) => state.remove(&oldest),
+                None => break,
+            }
+        }
+        state.tick += 1;
+        let tick = state.tick;
+        state.order.insert(tick, key.clone());
+        state.entries.insert(key, (tick, CachedEntry(entry)));
+    }
+
+    pub fn invalidate(&self, target: &CacheTarget) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        match target {
+            CacheTarget::Entry(key) => state.remove(key),
+            CacheTarget::Category(profile_id, kind, category) => {
+                let keys = state
+                    .entries
+                    .keys()
+                    .filter(|k| {
+                        k.profile_id == *profile_id && k.kind == *kind && &k.category == category
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for key in keys {
+                    state.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.entries.clear();
+        state.order.clear();
+    }
+
+    pub fn stats(&self) -> EntryCacheStats {
+        EntryCacheStats {
+            capacity: self.capacity,
+            entries: self.state.lock().unwrap().entries.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> CacheKey {
+        CacheKey::new(1, EntryKind::Item, "category", name)
+    }
+
+    fn entry(name: &str) -> Entry {
+        Entry::new("category", name, "value", None)
+    }
+
+    #[test]
+    fn entry_cache_evict_lru() {
+        let cache = EntryCache::new(2).unwrap();
+        let gen = cache.generation();
+        cache.insert(key("a"), entry("a"), gen);
+        cache.insert(key("b"), entry("b"), gen);
+        assert_eq!(cache.get(&key("a")), Some(entry("a")));
+        cache.insert(key("c"), entry("c"), gen);
+        assert_eq!(cache.get(&key("b")), None);
+        assert_eq!(cache.get(&key("a")), Some(entry("a")));
+        assert_eq!(cache.get(&key("c")), Some(entry("c")));
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (2, 3, 1));
+    }
+
+    #[test]
+    fn entry_cache_invalidate() {
+        let cache = EntryCache::new(10).unwrap();
+        let gen = cache.generation();
+        cache.insert(key("a"), entry("a"), gen);
+        cache.insert(key("b"), entry("b"), gen);
+        cache.invalidate(&CacheTarget::Entry(key("a")));
+        assert_eq!(cache.get(&key("a")), None);
+        assert_eq!(cache.get(&key("b")), Some(entry("b")));
+
+        // stale generation is not cached
+        cache.insert(key("a"), entry("a"), gen);
+        assert_eq!(cache.get(&key("a")), None);
+
+        cache.invalidate(&CacheTarget::Category(
+            1,
+            EntryKind::Item,
+            "category".to_string(),
+        ));
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Mutex;
+
+use async_channel::{bounded, Sender};
+use futures_lite::stream::StreamExt;
+
+use super::error::Result;
+use super::store::ChangeStream;
+use super::types::{EntryChange, ProfileId};
+
+/// The number of undelivered changes buffered for each subscriber
+const CHANGE_BUFFER_SIZE: usize = 1024;
+
+#[derive(Debug)]
+struct Subscriber {
+    profile_id: ProfileId,
+    category: Option<String>,
+    sender: Sender<Result<EntryChange>>,
+    lagged: bool,
+}
+
+/// Distributes committed entry changes to in-process subscribers
+#[derive(Debug, Default)]
+pub(crate) struct ChangeBroadcast {
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl ChangeBroadcast {
+    pub fn subscribe(&self, profile_id: ProfileId, category: Option<String>) -> ChangeStream {
+        let (sender, receiver) = bounded(CHANGE_BUFFER_SIZE);
+        self.subscribers.lock().unwrap().push(Subscriber {
+            profile_id,
+            category,
+            sender,
+            lagged: false,
+        });
+        receiver.boxed()
+    }
+
+    pub fn publish(&self, profile_id: ProfileId, change: &EntryChange) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // subscribers are dropped once the receiving stream is closed
+        subscribers.retain(|sub| !sub.sender.is_closed());
+        for sub in subscribers.iter_mut() {
+            if sub.profile_id == profile_id
+                && sub
+                    .category
+                    .as_ref()
+                    .map(|cat| cat == &change.category)
+                    .unwrap_or(true)
+            {
+                // changes are dropped while the buffer of a slow subscriber is full,
+                // and an error is delivered ahead of the next change to report the gap
+                if sub.lagged {
+                    let lag = err_msg!(Busy, "Subscriber lagged behind, changes were dropped");
+                    if sub.sender.try_send(Err(lag)).is_err() {
+                        continue;
+                    }
+                    sub.lagged = false;
+                }
+                if sub.sender.try_send(Ok(change.clone())).is_err() {
+                    sub.lagged = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::future::block_on;
+    use crate::types::EntryOperation;
+
+    fn change(name: &str) -> EntryChange {
+        EntryChange {
+            operation: EntryOperation::Insert,
+            category: "category".to_string(),
+            name: Some(name.to_string()),
+        }
+    }
+
+    #[test]
+    fn change_broadcast_lagged() {
+        let changes = ChangeBroadcast::default();
+        let mut stream = changes.subscribe(1, None);
+        for idx in 0..=CHANGE_BUFFER_SIZE {
+            changes.publish(1, &change(&idx.to_string()));
+        }
+        block_on(async {
+            for idx in 0..CHANGE_BUFFER_SIZE {
+                let recv = stream.next().await.unwrap().unwrap();
+                assert_eq!(recv, change(&idx.to_string()));
+            }
+            changes.publish(1, &change("next"));
+            let err = stream.next().await.unwrap().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Busy);
+            assert_eq!(stream.next().await.unwrap().unwrap(), change("next"));
+        });
+    }
+}
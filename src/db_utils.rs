@@ -9,6 +9,8 @@ use sqlx::{
 
 use crate::EntryTag;
 
+use super::audit::{AuditEvent, AuditLog, SessionAudit};
+use super::cache::{CacheTarget, EntryCache};
 use super::error::Result;
 use super::future::BoxFuture;
 use super::keys::{
@@ -16,7 +18,9 @@ use super::keys::{
     wrap::{WrapKey, WrapKeyMethod},
     EntryEncryptor, KeyCache, PassKey,
 };
-use super::types::{EncEntryTag, Entry, Expiry, ProfileId, TagFilter};
+use super::types::{
+    AuditOperation, EncEntryTag, Entry, EntryHistory, Expiry, ProfileId, SoftDelete, TagFilter,
+};
 use super::wql::{
     sql::TagSqlEncoder,
     tags::{tag_query, TagQueryEncoder},
@@ -34,9 +38,15 @@ unsafe impl<DB: ExtDatabase> Sync for DbSessionState<DB> where DB::Connection: S
 
 #[derive(Debug)]
 pub struct DbSession<DB: ExtDatabase> {
+    key_cache: Arc<KeyCache>,
     profile_key: DbSessionKey,
     state: DbSessionState<DB>,
     transaction: bool,
+    entry_cache: Option<Arc<EntryCache>>,
+    cache_pending: Vec<CacheTarget>,
+    audit: Option<SessionAudit>,
+    soft_delete: Arc<SoftDelete>,
+    history: Arc<EntryHistory>,
 }
 
 impl<DB: ExtDatabase> DbSession<DB> {
@@ -45,14 +55,24 @@ impl<DB: ExtDatabase> DbSession<DB> {
         cache: Arc<KeyCache>,
         profile: String,
         transaction: bool,
+        entry_cache: Option<Arc<EntryCache>>,
+        audit_log: Option<Arc<AuditLog>>,
+        soft_delete: Arc<SoftDelete>,
+        history: Arc<EntryHistory>,
     ) -> Self
     where
         DB: Database,
     {
         Self {
+            audit: audit_log.map(|log| SessionAudit::new(log, profile.clone())),
+            key_cache: cache.clone(),
             profile_key: DbSessionKey::Pending { cache, profile },
             state: DbSessionState::Pending { pool },
             transaction,
+            entry_cache,
+            cache_pending: Vec::new(),
+            soft_delete,
+            history,
         }
     }
 
@@ -71,7 +91,12 @@ impl<DB: ExtDatabase> DbSession<DB> {
     }
 
     #[inline]
-    fn pool(&self) -> Option<&Pool<DB>> {
+    pub(crate) fn is_active(&self) -> bool {
+        matches!(self.state, DbSessionState::Active { .. })
+    }
+
+    #[inline]
+    pub(crate) fn pool(&self) -> Option<&Pool<DB>> {
         if let DbSessionState::Pending { pool, .. } = &self.state {
             Some(pool)
         } else {
@@ -79,6 +104,86 @@ impl<DB: ExtDatabase> DbSession<DB> {
         }
     }
 
+    /// Get the entry cache, unless this session is a transaction
+    ///
+    /// Transactions bypass the cache, as they may observe their own
+    /// uncommitted updates.
+    #[inline]
+    pub(crate) fn entry_cache(&self) -> Option<&Arc<EntryCache>> {
+        if self.transaction {
+            None
+        } else {
+            self.entry_cache.as_ref()
+        }
+    }
+
+    /// Invalidate cached entries following an update
+    ///
+    /// Within a transaction the invalidation is deferred until commit.
+    pub(crate) fn invalidate_cache(&mut self, target: CacheTarget) {
+        if let Some(cache) = self.entry_cache.as_ref() {
+            if self.transaction {
+                self.cache_pending.push(target);
+            } else {
+                cache.invalidate(&target);
+            }
+        }
+    }
+
+    /// Check whether removed entries in a category are retained as deleted
+    #[inline]
+    pub(crate) fn soft_delete(&self, category: &str) -> bool {
+        self.soft_delete.applies(category)
+    }
+
+    /// Get the retention limit for previous versions of entries in a category,
+    /// if entry history is enabled for the category
+    #[inline]
+    pub(crate) fn entry_history(&self, category: &str) -> Option<Option<usize>> {
+        if self.history.applies(category) {
+            Some(self.history.max_versions)
+        } else {
+            None
+        }
+    }
+
+    /// Prepare an audit log event for an operation in this session, if
+    /// audit logging is enabled
+    pub(crate) fn audit_event(
+        &self,
+        operation: AuditOperation,
+    ) -> Option<(Arc<AuditLog>, AuditEvent)> {
+        self.audit.as_ref().map(|audit| audit.event(operation))
+    }
+
+    pub(crate) fn set_audit_actor(&mut self, actor: Option<String>) {
+        if let Some(audit) = self.audit.as_mut() {
+            audit.set_actor(actor);
+        }
+    }
+
+    /// Switch the profile used by subsequent operations in this session
+    ///
+    /// The profile key is resolved on the next operation, using the
+    /// existing connection and transaction if the session is active.
+    pub(crate) fn set_profile(&mut self, profile: String) {
+        if let Some(audit) = self.audit.as_mut() {
+            audit.set_profile(profile.clone());
+        }
+        self.profile_key = DbSessionKey::Pending {
+            cache: self.key_cache.clone(),
+            profile,
+        };
+    }
+
+    pub(crate) fn pending_profile(&self) -> Option<(Arc<KeyCache>, String)> {
+        if let DbSessionKey::Pending { cache, profile } = &self.profile_key {
+            Some((cache.clone(), profile.clone()))
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn profile_and_key(&mut self) -> Option<(ProfileId, Arc<StoreKey>)> {
         if let DbSessionKey::Active {
             profile_id,
@@ -136,7 +241,7 @@ impl<DB: ExtDatabase> DbSession<DB> {
         DbSessionRef::Owned(self)
     }
 
-    pub(crate) async fn close(mut self, commit: bool) -> Result<()> {
+    pub(crate) async fn close(&mut self, commit: bool) -> Result<()> {
         if self.transaction {
             if let Some(conn) = self.connection_mut() {
                 if commit {
@@ -149,6 +254,12 @@ impl<DB: ExtDatabase> DbSession<DB> {
                 .map_err(err_map!(Backend, "Error closing transaction"))?;
             }
             self.transaction = false;
+            let pending = std::mem::take(&mut self.cache_pending);
+            if let (true, Some(cache)) = (commit, self.entry_cache.as_ref()) {
+                for target in pending {
+                    cache.invalidate(&target);
+                }
+            }
         }
         Ok(())
     }
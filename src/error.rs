@@ -12,18 +12,39 @@ pub enum ErrorKind {
     /// The store backend was too busy to handle the request
     Busy,
 
+    /// The operation was aborted using a cancellation handle
+    Cancelled,
+
+    /// The connection to the store backend failed or was lost
+    Connection,
+
+    /// The store database is corrupted or is not a database
+    Corrupt,
+
     /// An insert operation failed due to a unique key conflict
     Duplicate,
 
     /// An encryption or decryption operation failed
     Encryption,
 
+    /// The operation is not permitted by the policy of the target record
+    Forbidden,
+
     /// The input parameters to the method were incorrect
     Input,
 
     /// The requested record was not found
     NotFound,
 
+    /// An update would exceed the storage quota of the profile
+    QuotaExceeded,
+
+    /// The store backend has run out of storage space
+    StorageFull,
+
+    /// The operation did not complete within the configured time limit
+    Timeout,
+
     /// An unexpected error occurred
     Unexpected,
 
@@ -37,10 +58,17 @@ impl ErrorKind {
         match self {
             Self::Backend => "Backend error",
             Self::Busy => "Busy",
+            Self::Cancelled => "Cancelled",
+            Self::Connection => "Connection error",
+            Self::Corrupt => "Corrupt database",
             Self::Duplicate => "Duplicate",
             Self::Encryption => "Encryption error",
+            Self::Forbidden => "Forbidden",
             Self::Input => "Input error",
             Self::NotFound => "Not found",
+            Self::QuotaExceeded => "Quota exceeded",
+            Self::StorageFull => "Storage full",
+            Self::Timeout => "Timeout",
             Self::Unexpected => "Unexpected error",
             Self::Unsupported => "Unsupported",
         }
@@ -59,6 +87,7 @@ pub struct Error {
     pub(crate) kind: ErrorKind,
     pub(crate) cause: Option<Box<dyn StdError + Send + Sync + 'static>>,
     pub(crate) message: Option<String>,
+    pub(crate) code: Option<String>,
 }
 
 impl Error {
@@ -67,6 +96,7 @@ impl Error {
             kind,
             cause: None,
             message: Some(msg.into()),
+            code: None,
         }
     }
 
@@ -75,6 +105,7 @@ impl Error {
             kind,
             cause: None,
             message: msg.map(Into::into),
+            code: None,
         }
     }
 
@@ -83,10 +114,133 @@ impl Error {
         self.kind
     }
 
+    /// Accessor for the error code reported by the store backend, if any
+    ///
+    /// This is the extended result code for Sqlite, or the SQLSTATE code
+    /// for Postgres.
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+
     pub(crate) fn with_cause<T: Into<Box<dyn StdError + Send + Sync>>>(mut self, err: T) -> Self {
-        self.cause = Some(err.into());
+        let cause: Box<dyn StdError + Send + Sync> = err.into();
+        #[cfg(any(feature = "indy_compat", feature = "postgres", feature = "sqlite"))]
+        {
+            if let Some(sqlx_err) = cause.downcast_ref::<sqlx::Error>() {
+                let (kind, code) = classify_sqlx(sqlx_err);
+                // a generic backend error is refined by the cause
+                if self.kind == ErrorKind::Backend {
+                    self.kind = kind;
+                }
+                if self.code.is_none() {
+                    self.code = code;
+                }
+            }
+        }
+        self.cause = Some(cause);
         self
     }
+
+    /// Check whether the operation may succeed if it is repeated
+    pub(crate) fn is_transient(&self) -> bool {
+        match self.kind {
+            ErrorKind::Busy => true,
+            // only a timeout waiting for a pooled connection is retried
+            #[cfg(any(feature = "indy_compat", feature = "postgres", feature = "sqlite"))]
+            ErrorKind::Timeout => matches!(
+                self.cause
+                    .as_ref()
+                    .and_then(|cause| cause.downcast_ref::<sqlx::Error>()),
+                Some(sqlx::Error::PoolTimedOut)
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// Determine the error kind and backend error code for a sqlx error
+#[cfg(any(feature = "indy_compat", feature = "postgres", feature = "sqlite"))]
+fn classify_sqlx(err: &sqlx::Error) -> (ErrorKind, Option<String>) {
+    match err {
+        sqlx::Error::Database(db_err) => {
+            let code = db_err.code().map(|code| code.into_owned());
+            #[cfg(feature = "postgres")]
+            {
+                if let Some(pg_err) = db_err.try_downcast_ref::<sqlx::postgres::PgDatabaseError>() {
+                    return (postgres_error_kind(pg_err.code()), code);
+                }
+            }
+            #[cfg(any(feature = "indy_compat", feature = "sqlite"))]
+            {
+                if db_err
+                    .try_downcast_ref::<sqlx::sqlite::SqliteError>()
+                    .is_some()
+                {
+                    let kind = code
+                        .as_ref()
+                        .and_then(|code| code.parse().ok())
+                        .map(sqlite_error_kind)
+                        .unwrap_or(ErrorKind::Backend);
+                    return (kind, code);
+                }
+            }
+            (ErrorKind::Backend, code)
+        }
+        sqlx::Error::Configuration(_) => (ErrorKind::Input, None),
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) => (ErrorKind::Connection, None),
+        sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed => (ErrorKind::Connection, None),
+        sqlx::Error::PoolTimedOut => (ErrorKind::Timeout, None),
+        sqlx::Error::RowNotFound => (ErrorKind::NotFound, None),
+        _ => (ErrorKind::Backend, None),
+    }
+}
+
+/// Map a Sqlite extended result code to an error kind
+#[cfg(any(feature = "indy_compat", feature = "sqlite"))]
+fn sqlite_error_kind(code: i32) -> ErrorKind {
+    match code {
+        // SQLITE_CONSTRAINT_PRIMARYKEY, SQLITE_CONSTRAINT_UNIQUE
+        1555 | 2067 => ErrorKind::Duplicate,
+        // the primary result code is held in the lower 8 bits
+        code => match code & 0xff {
+            // SQLITE_BUSY, SQLITE_LOCKED
+            5 | 6 => ErrorKind::Busy,
+            // SQLITE_INTERRUPT
+            9 => ErrorKind::Cancelled,
+            // SQLITE_CORRUPT, SQLITE_NOTADB
+            11 | 26 => ErrorKind::Corrupt,
+            // SQLITE_FULL
+            13 => ErrorKind::StorageFull,
+            // SQLITE_CANTOPEN
+            14 => ErrorKind::NotFound,
+            _ => ErrorKind::Backend,
+        },
+    }
+}
+
+/// Map a Postgres SQLSTATE code to an error kind
+#[cfg(feature = "postgres")]
+fn postgres_error_kind(code: &str) -> ErrorKind {
+    match code {
+        // unique_violation
+        "23505" => ErrorKind::Duplicate,
+        // serialization_failure, deadlock_detected, lock_not_available,
+        // too_many_connections
+        "40001" | "40P01" | "55P03" | "53300" => ErrorKind::Busy,
+        // query_canceled, raised when the statement timeout is exceeded
+        "57014" => ErrorKind::Timeout,
+        // invalid_catalog_name: the database does not exist
+        "3D000" => ErrorKind::NotFound,
+        // data_corrupted, index_corrupted
+        "XX001" | "XX002" => ErrorKind::Corrupt,
+        // disk_full
+        "53100" => ErrorKind::StorageFull,
+        // admin_shutdown, crash_shutdown, cannot_connect_now
+        "57P01" | "57P02" | "57P03" => ErrorKind::Connection,
+        // connection_exception class
+        code if code.starts_with("08") => ErrorKind::Connection,
+        _ => ErrorKind::Backend,
+    }
 }
 
 impl Display for Error {
@@ -123,6 +277,7 @@ impl From<ErrorKind> for Error {
             kind,
             cause: None,
             message: None,
+            code: None,
         }
     }
 }
@@ -132,6 +287,7 @@ impl From<ErrorKind> for Error {
 #[cfg(any(feature = "indy_compat", feature = "postgres", feature = "sqlite"))]
 impl From<sqlx::Error> for Error {
     fn from(err: sqlx::Error) -> Self {
+        // the kind is refined according to the backend error code
         Error::from(ErrorKind::Backend).with_cause(err)
     }
 }
@@ -174,3 +330,47 @@ macro_rules! err_map {
         |err| err_msg!($($params)*).with_cause(err)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_error_kinds() {
+        // SQLITE_CONSTRAINT_UNIQUE
+        assert_eq!(sqlite_error_kind(2067), ErrorKind::Duplicate);
+        // SQLITE_CONSTRAINT_FOREIGNKEY
+        assert_eq!(sqlite_error_kind(787), ErrorKind::Backend);
+        // SQLITE_BUSY_SNAPSHOT
+        assert_eq!(sqlite_error_kind(517), ErrorKind::Busy);
+        // SQLITE_NOTADB
+        assert_eq!(sqlite_error_kind(26), ErrorKind::Corrupt);
+        // SQLITE_FULL
+        assert_eq!(sqlite_error_kind(13), ErrorKind::StorageFull);
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn postgres_error_kinds() {
+        assert_eq!(postgres_error_kind("23505"), ErrorKind::Duplicate);
+        assert_eq!(postgres_error_kind("40P01"), ErrorKind::Busy);
+        assert_eq!(postgres_error_kind("08006"), ErrorKind::Connection);
+        assert_eq!(postgres_error_kind("57014"), ErrorKind::Timeout);
+        assert_eq!(postgres_error_kind("XX001"), ErrorKind::Corrupt);
+        assert_eq!(postgres_error_kind("53100"), ErrorKind::StorageFull);
+        // out_of_memory
+        assert_eq!(postgres_error_kind("53200"), ErrorKind::Backend);
+    }
+
+    #[test]
+    fn pool_timeout_transient() {
+        #[cfg(feature = "sqlite")]
+        {
+            let err = Error::from(sqlx::Error::PoolTimedOut);
+            assert_eq!(err.kind(), ErrorKind::Timeout);
+            assert!(err.is_transient());
+        }
+        assert!(!err_msg!(Timeout, "Operation timed out").is_transient());
+    }
+}
@@ -21,6 +21,13 @@ pub enum ErrorCode {
     NotFound = 6,
     Unexpected = 7,
     Unsupported = 8,
+    QuotaExceeded = 9,
+    Timeout = 10,
+    Cancelled = 11,
+    Connection = 12,
+    Corrupt = 13,
+    StorageFull = 14,
+    Forbidden = 15,
 }
 
 impl From<ErrorKind> for ErrorCode {
@@ -28,10 +35,17 @@ impl From<ErrorKind> for ErrorCode {
         match kind {
             ErrorKind::Backend => ErrorCode::Backend,
             ErrorKind::Busy => ErrorCode::Busy,
+            ErrorKind::Cancelled => ErrorCode::Cancelled,
+            ErrorKind::Connection => ErrorCode::Connection,
+            ErrorKind::Corrupt => ErrorCode::Corrupt,
             ErrorKind::Duplicate => ErrorCode::Duplicate,
             ErrorKind::Encryption => ErrorCode::Encryption,
+            ErrorKind::Forbidden => ErrorCode::Forbidden,
             ErrorKind::Input => ErrorCode::Input,
             ErrorKind::NotFound => ErrorCode::NotFound,
+            ErrorKind::QuotaExceeded => ErrorCode::QuotaExceeded,
+            ErrorKind::StorageFull => ErrorCode::StorageFull,
+            ErrorKind::Timeout => ErrorCode::Timeout,
             ErrorKind::Unexpected => ErrorCode::Unexpected,
             ErrorKind::Unsupported => ErrorCode::Unsupported,
         }
@@ -61,8 +75,9 @@ pub fn get_current_error_json() -> String {
     if let Some(err) = Option::take(&mut *LAST_ERROR.write().unwrap()) {
         let message = err.to_string();
         let code = ErrorCode::from(err.kind()) as usize;
-        // let extra = err.extra();
-        json!({"code": code, "message": message}).to_string()
+        // the error code reported by the store backend, if any
+        let extra = err.code();
+        json!({"code": code, "message": message, "extra": extra}).to_string()
     } else {
         r#"{"code":0,"message":null}"#.to_owned()
     }
@@ -24,7 +24,9 @@ mod store;
 use self::error::{set_last_error, ErrorCode};
 use crate::error::Error;
 use crate::future::{spawn_ok, unblock};
-use crate::keys::{derive_verkey, verify_signature, wrap::generate_raw_wrap_key, KeyAlg};
+use crate::keys::{
+    derive_verkey, verify_jws, verify_signature, wrap::generate_raw_wrap_key, KeyAlg,
+};
 
 pub type CallbackId = i64;
 
@@ -152,6 +154,41 @@ pub extern "C" fn askar_verify_signature(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_verify_jws(
+    jws: FfiStr<'_>,
+    detached_payload: ByteBuffer,
+    signer_vk: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, verify: i8)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Verify JWS");
+        let jws = jws.into_opt_string().ok_or_else(|| err_msg!("JWS not provided"))?;
+        let detached_payload = match detached_payload.as_slice() {
+            p if p.is_empty() => None,
+            p => Some(p.to_vec()),
+        };
+        let signer_vk = signer_vk.into_opt_string().ok_or_else(|| err_msg!("Signer verkey not provided"))?;
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(verify) => cb(cb_id, ErrorCode::Success, verify as i8),
+                Err(err) => cb(cb_id, set_last_error(Some(err)), 0),
+            }
+        );
+        spawn_ok(async move {
+            let result = unblock(move || verify_jws(
+                &jws,
+                detached_payload.as_ref().map(Vec::as_slice),
+                &signer_vk,
+            )).await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_version() -> *mut c_char {
     rust_string_to_c(LIB_VERSION.to_owned())
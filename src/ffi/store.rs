@@ -8,6 +8,7 @@ use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
 };
+use std::time::Duration;
 
 use async_mutex::{Mutex, MutexGuardArc};
 use ffi_support::{rust_string_to_c, ByteBuffer, FfiStr};
@@ -20,8 +21,11 @@ use super::{CallbackId, EnsureCallback, ErrorCode};
 use crate::any::{AnySession, AnyStore};
 use crate::error::Result as KvResult;
 use crate::future::spawn_ok;
-use crate::keys::{wrap::WrapKeyMethod, KeyAlg, KeyCategory, KeyEntry, PassKey};
-use crate::store::{ManageBackend, Scan};
+use crate::keys::{
+    wrap::WrapKeyMethod, JweEncryption, JwsSerialization, KeyAgreementKdf, KeyAlg, KeyCategory,
+    KeyEntry, KeyFormat, PassKey,
+};
+use crate::store::{CancelHandle, ManageBackend, Scan};
 use crate::types::{Entry, EntryOperation, EntryTagSet, TagFilter};
 
 new_handle_type!(StoreHandle, FFI_STORE_COUNTER);
@@ -30,9 +34,11 @@ new_handle_type!(ScanHandle, FFI_SCAN_COUNTER);
 
 static FFI_STORES: Lazy<Mutex<BTreeMap<StoreHandle, Arc<AnyStore>>>> =
     Lazy::new(|| Mutex::new(BTreeMap::new()));
-static FFI_SESSIONS: Lazy<Mutex<BTreeMap<SessionHandle, Arc<Mutex<AnySession>>>>> =
+// cancel handles are held outside of the session and scan instances, which
+// are locked or taken while an operation is running
+static FFI_SESSIONS: Lazy<Mutex<BTreeMap<SessionHandle, (Arc<Mutex<AnySession>>, CancelHandle)>>> =
     Lazy::new(|| Mutex::new(BTreeMap::new()));
-static FFI_SCANS: Lazy<Mutex<BTreeMap<ScanHandle, Option<Scan<'static, Entry>>>>> =
+static FFI_SCANS: Lazy<Mutex<BTreeMap<ScanHandle, (Option<Scan<'static, Entry>>, CancelHandle)>>> =
     Lazy::new(|| Mutex::new(BTreeMap::new()));
 
 impl StoreHandle {
@@ -68,36 +74,51 @@ impl StoreHandle {
 impl SessionHandle {
     pub async fn create(value: AnySession) -> Self {
         let handle = Self::next();
+        let cancel = value.cancel_handle();
         let mut repo = FFI_SESSIONS.lock().await;
-        repo.insert(handle, Arc::new(Mutex::new(value)));
+        repo.insert(handle, (Arc::new(Mutex::new(value)), cancel));
         handle
     }
 
     pub async fn load(&self) -> KvResult<MutexGuardArc<AnySession>> {
-        Ok(Mutex::lock_arc(
-            FFI_SESSIONS
-                .lock()
-                .await
-                .get(self)
-                .ok_or_else(|| err_msg!("Invalid session handle"))?,
-        )
-        .await)
+        // release the repository before waiting on the session, so that
+        // a running operation may still be cancelled
+        let session = FFI_SESSIONS
+            .lock()
+            .await
+            .get(self)
+            .ok_or_else(|| err_msg!("Invalid session handle"))?
+            .0
+            .clone();
+        Ok(Mutex::lock_arc(&session).await)
+    }
+
+    pub async fn cancel_handle(&self) -> KvResult<CancelHandle> {
+        Ok(FFI_SESSIONS
+            .lock()
+            .await
+            .get(self)
+            .ok_or_else(|| err_msg!("Invalid session handle"))?
+            .1
+            .clone())
     }
 
     pub async fn remove(&self) -> KvResult<Arc<Mutex<AnySession>>> {
-        FFI_SESSIONS
+        Ok(FFI_SESSIONS
             .lock()
             .await
             .remove(self)
-            .ok_or_else(|| err_msg!("Invalid session handle"))
+            .ok_or_else(|| err_msg!("Invalid session handle"))?
+            .0)
     }
 }
 
 impl ScanHandle {
     pub async fn create(value: Scan<'static, Entry>) -> Self {
         let handle = Self::next();
+        let cancel = value.cancel_handle();
         let mut repo = FFI_SCANS.lock().await;
-        repo.insert(handle, Some(value));
+        repo.insert(handle, (Some(value), cancel));
         handle
     }
 
@@ -107,6 +128,7 @@ impl ScanHandle {
             .await
             .get_mut(self)
             .ok_or_else(|| err_msg!("Invalid scan handle"))?
+            .0
             .take()
             .ok_or_else(|| err_msg!(Busy, "Scan handle in use"))
     }
@@ -117,16 +139,28 @@ impl ScanHandle {
             .await
             .get_mut(self)
             .ok_or_else(|| err_msg!("Invalid scan handle"))?
+            .0
             .replace(value);
         Ok(())
     }
 
+    pub async fn cancel_handle(&self) -> KvResult<CancelHandle> {
+        Ok(FFI_SCANS
+            .lock()
+            .await
+            .get(self)
+            .ok_or_else(|| err_msg!("Invalid scan handle"))?
+            .1
+            .clone())
+    }
+
     pub async fn remove(&self) -> KvResult<Scan<'static, Entry>> {
         FFI_SCANS
             .lock()
             .await
             .remove(self)
             .ok_or_else(|| err_msg!("Invalid scan handle"))?
+            .0
             .ok_or_else(|| err_msg!(Busy, "Scan handle in use"))
     }
 }
@@ -512,6 +546,35 @@ pub extern "C" fn askar_store_rekey(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_store_backup(
+    handle: StoreHandle,
+    path: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Backup store");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let path = path.into_opt_string().ok_or_else(|| err_msg!("No backup path provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(_) => cb(cb_id, ErrorCode::Success),
+                Err(err) => cb(cb_id, set_last_error(Some(err))),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let store = handle.load().await?;
+                store.backup_to(&path).await?;
+                Ok(())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_store_close(
     handle: StoreHandle,
@@ -616,6 +679,23 @@ pub extern "C" fn askar_scan_next(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_scan_cancel(handle: ScanHandle) -> ErrorCode {
+    catch_err! {
+        trace!("Cancel scan");
+        spawn_ok(async move {
+            match handle.cancel_handle().await {
+                Ok(cancel) => {
+                    cancel.cancel();
+                    info!("Cancelled scan {}", handle);
+                }
+                Err(err) => error!("{}", err),
+            }
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_scan_free(handle: ScanHandle) -> ErrorCode {
     catch_err! {
@@ -936,6 +1016,118 @@ pub extern "C" fn askar_session_create_keypair(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_session_convert_keypair(
+    handle: SessionHandle,
+    ident: FfiStr<'_>,
+    alg: FfiStr<'_>,
+    metadata: FfiStr<'_>,
+    tags: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: *const c_char)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Convert keypair");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let ident = ident.into_opt_string().ok_or_else(|| err_msg!("No key ident provided"))?;
+        let alg = alg.as_opt_str().map(|alg| KeyAlg::from_str(alg).unwrap()).ok_or_else(|| err_msg!("Key algorithm not provided"))?;
+        let metadata = metadata.into_opt_string();
+        let tags = if let Some(tags) = tags.as_opt_str() {
+            Some(
+                serde_json::from_str::<EntryTagSet>(tags)
+                    .map_err(err_map!("Error decoding tags"))?
+                    .into_inner(),
+            )
+        } else {
+            None
+        };
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(ident) => {
+                    cb(cb_id, ErrorCode::Success, rust_string_to_c(ident))
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ptr::null()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let key_entry = session.convert_keypair(
+                    &ident,
+                    alg,
+                    metadata.as_ref().map(String::as_str),
+                    tags.as_ref().map(Vec::as_slice),
+                ).await?;
+                Ok(key_entry.ident.clone())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_import_key(
+    handle: SessionHandle,
+    alg: FfiStr<'_>,
+    format: FfiStr<'_>,
+    secret: ByteBuffer,
+    metadata: FfiStr<'_>,
+    tags: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: *const c_char)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Import key");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let alg = alg.as_opt_str().map(|alg| KeyAlg::from_str(alg).unwrap()).ok_or_else(|| err_msg!("Key algorithm not provided"))?;
+        let format = if let Some(format) = format.as_opt_str() {
+            KeyFormat::from_str(format)?
+        } else {
+            KeyFormat::default()
+        };
+        let metadata = metadata.into_opt_string();
+        let tags = if let Some(tags) = tags.as_opt_str() {
+            Some(
+                serde_json::from_str::<EntryTagSet>(tags)
+                    .map_err(err_map!("Error decoding tags"))?
+                    .into_inner(),
+            )
+        } else {
+            None
+        };
+        let mut secret = secret.as_slice().to_vec();
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(ident) => {
+                    cb(cb_id, ErrorCode::Success, rust_string_to_c(ident))
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ptr::null()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let key_entry = session.import_key(
+                    alg,
+                    &secret,
+                    format,
+                    metadata.as_ref().map(String::as_str),
+                    tags.as_ref().map(Vec::as_slice),
+                ).await;
+                secret.zeroize();
+                Ok(key_entry?.ident.clone())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_session_fetch_keypair(
     handle: SessionHandle,
@@ -978,6 +1170,53 @@ pub extern "C" fn askar_session_fetch_keypair(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_session_export_key(
+    handle: SessionHandle,
+    category: FfiStr<'_>,
+    ident: FfiStr<'_>,
+    format: FfiStr<'_>,
+    include_private: i8,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: ByteBuffer)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Export key");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let category = category.as_opt_str().map(|cat| KeyCategory::from_str(cat).unwrap()).unwrap_or(KeyCategory::KeyPair);
+        let ident = ident.into_opt_string().ok_or_else(|| err_msg!("No key ident provided"))?;
+        let format = if let Some(format) = format.as_opt_str() {
+            KeyFormat::from_str(format)?
+        } else {
+            KeyFormat::Jwk
+        };
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(key) => {
+                    cb(cb_id, ErrorCode::Success, key)
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ByteBuffer::default()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let key = session.export_key(
+                    category,
+                    &ident,
+                    format,
+                    include_private != 0,
+                ).await?;
+                Ok(ByteBuffer::from_vec(key.into_vec()))
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_session_update_keypair(
     handle: SessionHandle,
@@ -1067,6 +1306,300 @@ pub extern "C" fn askar_session_sign_message(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_session_sign_jws(
+    handle: SessionHandle,
+    key_ident: FfiStr<'_>,
+    payload: ByteBuffer,
+    headers: FfiStr<'_>,
+    serialization: FfiStr<'_>,
+    detached: i8,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: *const c_char)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Sign JWS");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let key_ident = key_ident.into_opt_string().ok_or_else(|| err_msg!("Key identity not provided"))?;
+        let payload = payload.as_slice().to_vec();
+        let headers = if let Some(headers) = headers.as_opt_str() {
+            Some(
+                serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(headers)
+                    .map_err(err_map!("Error decoding JWS headers"))?
+            )
+        } else {
+            None
+        };
+        let serialization = if let Some(serialization) = serialization.as_opt_str() {
+            JwsSerialization::from_str(serialization)?
+        } else {
+            JwsSerialization::default()
+        };
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(jws) => {
+                    cb(cb_id, ErrorCode::Success, rust_string_to_c(jws))
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ptr::null()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let jws = session.sign_jws(
+                    &key_ident,
+                    &payload,
+                    headers.as_ref(),
+                    serialization,
+                    detached != 0,
+                ).await?;
+                Ok(jws)
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_create_key(
+    handle: SessionHandle,
+    alg: FfiStr<'_>,
+    ident: FfiStr<'_>,
+    metadata: FfiStr<'_>,
+    tags: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: *const c_char)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Create key");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let alg = alg.as_opt_str().map(|alg| KeyAlg::from_str(alg).unwrap()).ok_or_else(|| err_msg!("Key algorithm not provided"))?;
+        let ident = ident.into_opt_string();
+        let metadata = metadata.into_opt_string();
+        let tags = if let Some(tags) = tags.as_opt_str() {
+            Some(
+                serde_json::from_str::<EntryTagSet>(tags)
+                    .map_err(err_map!("Error decoding tags"))?
+                    .into_inner(),
+            )
+        } else {
+            None
+        };
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(ident) => {
+                    cb(cb_id, ErrorCode::Success, rust_string_to_c(ident))
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ptr::null()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let key_entry = session.create_key(
+                    alg,
+                    ident.as_ref().map(String::as_str),
+                    metadata.as_ref().map(String::as_str),
+                    tags.as_ref().map(Vec::as_slice),
+                ).await?;
+                Ok(key_entry.ident.clone())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_encrypt_with_key(
+    handle: SessionHandle,
+    key_ident: FfiStr<'_>,
+    message: ByteBuffer,
+    aad: ByteBuffer,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: ByteBuffer)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Encrypt with key");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let key_ident = key_ident.into_opt_string().ok_or_else(|| err_msg!("Key identity not provided"))?;
+        // copy message and associated data so the caller can drop them
+        let message = message.as_slice().to_vec();
+        let aad = aad.as_slice().to_vec();
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(enc) => {
+                    cb(cb_id, ErrorCode::Success, ByteBuffer::from_vec(enc))
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ByteBuffer::default()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let enc = session.encrypt_with_key(
+                    &key_ident,
+                    &message,
+                    Some(aad.as_slice()),
+                ).await?;
+                Ok(enc)
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_decrypt_with_key(
+    handle: SessionHandle,
+    key_ident: FfiStr<'_>,
+    ciphertext: ByteBuffer,
+    aad: ByteBuffer,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: ByteBuffer)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Decrypt with key");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let key_ident = key_ident.into_opt_string().ok_or_else(|| err_msg!("Key identity not provided"))?;
+        let ciphertext = ciphertext.as_slice().to_vec();
+        let aad = aad.as_slice().to_vec();
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(dec) => {
+                    cb(cb_id, ErrorCode::Success, dec)
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ByteBuffer::default()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let dec = session.decrypt_with_key(
+                    &key_ident,
+                    &ciphertext,
+                    Some(aad.as_slice()),
+                ).await?;
+                Ok(ByteBuffer::from_vec(dec.into_vec()))
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_key_agreement_concat(
+    handle: SessionHandle,
+    local_ident: FfiStr<'_>,
+    peer_public_key: FfiStr<'_>,
+    alg_id: FfiStr<'_>,
+    apu: ByteBuffer,
+    apv: ByteBuffer,
+    key_len: i64,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: ByteBuffer)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Key agreement with Concat KDF");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let local_ident = local_ident.into_opt_string().ok_or_else(|| err_msg!("Key identity not provided"))?;
+        let peer_public_key = peer_public_key.into_opt_string().ok_or_else(|| err_msg!("Peer public key not provided"))?;
+        if key_len <= 0 {
+            return Err(err_msg!(Input, "Invalid derived key length"));
+        }
+        let kdf = KeyAgreementKdf::ConcatKdf {
+            alg_id: alg_id.into_opt_string().ok_or_else(|| err_msg!("Algorithm identifier not provided"))?,
+            apu: apu.as_slice().to_vec(),
+            apv: apv.as_slice().to_vec(),
+            key_len: key_len as usize,
+        };
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(key) => {
+                    cb(cb_id, ErrorCode::Success, key)
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ByteBuffer::default()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let key = session.key_agreement(
+                    &local_ident,
+                    &peer_public_key,
+                    &kdf,
+                ).await?;
+                Ok(ByteBuffer::from_vec(key.into_vec()))
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_key_agreement_hkdf(
+    handle: SessionHandle,
+    local_ident: FfiStr<'_>,
+    peer_public_key: FfiStr<'_>,
+    salt: ByteBuffer,
+    info: ByteBuffer,
+    key_len: i64,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: ByteBuffer)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Key agreement with HKDF");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let local_ident = local_ident.into_opt_string().ok_or_else(|| err_msg!("Key identity not provided"))?;
+        let peer_public_key = peer_public_key.into_opt_string().ok_or_else(|| err_msg!("Peer public key not provided"))?;
+        if key_len <= 0 {
+            return Err(err_msg!(Input, "Invalid derived key length"));
+        }
+        let kdf = KeyAgreementKdf::Hkdf {
+            salt: salt.as_slice().to_vec(),
+            info: info.as_slice().to_vec(),
+            key_len: key_len as usize,
+        };
+
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(key) => {
+                    cb(cb_id, ErrorCode::Success, key)
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ByteBuffer::default()),
+            }
+        );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let key = session.key_agreement(
+                    &local_ident,
+                    &peer_public_key,
+                    &kdf,
+                ).await?;
+                Ok(ByteBuffer::from_vec(key.into_vec()))
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_session_pack_message(
     handle: SessionHandle,
@@ -1160,6 +1693,145 @@ pub extern "C" fn askar_session_unpack_message(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_session_pack_message_v2(
+    handle: SessionHandle,
+    recipient_keys: FfiStr<'_>,
+    from_key_ident: FfiStr<'_>,
+    message: ByteBuffer,
+    enc: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, packed: ByteBuffer)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Pack message (DIDComm v2)");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let recipient_keys = recipient_keys
+            .as_opt_str()
+            .ok_or_else(|| err_msg!("Recipient key(s) not provided"))?
+            .split(',')
+            .filter(|key| !key.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let from_key_ident = from_key_ident.into_opt_string();
+        let message = message.as_slice().to_vec();
+        let enc = if let Some(enc) = enc.as_opt_str() {
+            JweEncryption::from_str(enc)?
+        } else {
+            JweEncryption::default()
+        };
+
+        let cb = EnsureCallback::new(move |result|
+                match result {
+                    Ok(packed) => {
+                        cb(cb_id, ErrorCode::Success, ByteBuffer::from_vec(packed))
+                    }
+                    Err(err) => cb(cb_id, set_last_error(Some(err)), ByteBuffer::default()),
+                }
+            );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let packed = session.pack_message_v2(
+                    recipient_keys.iter().map(String::as_str),
+                    from_key_ident.as_ref().map(String::as_str),
+                    &message,
+                    enc,
+                ).await?;
+                Ok(packed)
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_unpack_message_v2(
+    handle: SessionHandle,
+    message: ByteBuffer,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, result: FfiUnpackResult)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Unpack message (DIDComm v2)");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let message = message.as_slice().to_vec();
+
+        let cb = EnsureCallback::new(move |result: KvResult<(Vec<u8>, String, Option<String>)>|
+                match result {
+                    Ok((unpacked, recipient, sender)) => {
+                        cb(cb_id, ErrorCode::Success, FfiUnpackResult {
+                            unpacked: ByteBuffer::from_vec(unpacked), recipient: rust_string_to_c(recipient), sender: sender.map(rust_string_to_c).unwrap_or(ptr::null_mut())}
+                        )
+                    }
+                    Err(err) => {
+                        cb(cb_id, set_last_error(Some(err)), FfiUnpackResult { unpacked: ByteBuffer::default(), recipient: ptr::null(), sender: ptr::null() })
+                    }
+                }
+            );
+
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let (unpacked, recipient, sender) = session.unpack_message_v2(
+                    &message
+                ).await?;
+                Ok((unpacked.into_vec(), recipient, sender))
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_set_timeout(
+    handle: SessionHandle,
+    timeout_ms: i64,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Set session timeout");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let timeout = if timeout_ms < 0 { None } else { Some(Duration::from_millis(timeout_ms as u64)) };
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(_) => cb(cb_id, ErrorCode::Success),
+                Err(err) => cb(cb_id, set_last_error(Some(err))),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                session.set_timeout(timeout);
+                Ok(())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_cancel(handle: SessionHandle) -> ErrorCode {
+    catch_err! {
+        trace!("Cancel session");
+        spawn_ok(async move {
+            match handle.cancel_handle().await {
+                Ok(cancel) => {
+                    cancel.cancel();
+                    info!("Cancelled session {}", handle);
+                }
+                Err(err) => error!("{}", err),
+            }
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_session_close(
     handle: SessionHandle,
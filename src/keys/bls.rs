@@ -0,0 +1,215 @@
+use bls12_381::{G1Affine, G1Projective, G2Affine, G2Projective, Scalar};
+use hkdf::Hkdf;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::error::Result;
+use crate::types::SecretBytes;
+
+/// The length of a BLS12-381 private key
+pub const SECRET_KEY_LENGTH: usize = 32;
+
+/// The length of a compressed G1 public key
+pub const G1_PUBLIC_KEY_LENGTH: usize = 48;
+
+/// The length of a compressed G2 public key
+pub const G2_PUBLIC_KEY_LENGTH: usize = 96;
+
+/// The minimum length of the input keying material for key generation
+pub const MIN_SEED_LENGTH: usize = 32;
+
+const KEYGEN_SALT: &[u8] = b"BLS-SIG-KEYGEN-SALT-";
+
+/// Generate a new BLS12-381 keypair with a public key in G1, returning the
+/// compressed public key and the private key
+pub(crate) fn generate_g1_keypair(seed: Option<&[u8]>) -> Result<(Vec<u8>, SecretBytes)> {
+    Ok(g1_keypair(secret_scalar(seed)?))
+}
+
+/// Generate a new BLS12-381 keypair with a public key in G2, returning the
+/// compressed public key and the private key
+pub(crate) fn generate_g2_keypair(seed: Option<&[u8]>) -> Result<(Vec<u8>, SecretBytes)> {
+    Ok(g2_keypair(secret_scalar(seed)?))
+}
+
+/// Load a BLS12-381 keypair with a public key in G1 from an existing
+/// big-endian private key
+pub(crate) fn import_g1_keypair(secret: &[u8]) -> Result<(Vec<u8>, SecretBytes)> {
+    Ok(g1_keypair(import_scalar(secret)?))
+}
+
+/// Load a BLS12-381 keypair with a public key in G2 from an existing
+/// big-endian private key
+pub(crate) fn import_g2_keypair(secret: &[u8]) -> Result<(Vec<u8>, SecretBytes)> {
+    Ok(g2_keypair(import_scalar(secret)?))
+}
+
+/// Check that a public key is a valid compressed G1 point
+pub(crate) fn validate_g1_public_key(pub_key: &[u8]) -> Result<()> {
+    let valid = if pub_key.len() == G1_PUBLIC_KEY_LENGTH {
+        let mut pk = [0u8; G1_PUBLIC_KEY_LENGTH];
+        pk.copy_from_slice(pub_key);
+        bool::from(G1Affine::from_compressed(&pk).is_some())
+    } else {
+        false
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(err_msg!(Input, "Invalid BLS12-381 G1 public key"))
+    }
+}
+
+/// Check that a public key is a valid compressed G2 point
+pub(crate) fn validate_g2_public_key(pub_key: &[u8]) -> Result<()> {
+    let valid = if pub_key.len() == G2_PUBLIC_KEY_LENGTH {
+        let mut pk = [0u8; G2_PUBLIC_KEY_LENGTH];
+        pk.copy_from_slice(pub_key);
+        bool::from(G2Affine::from_compressed(&pk).is_some())
+    } else {
+        false
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(err_msg!(Input, "Invalid BLS12-381 G2 public key"))
+    }
+}
+
+/// Derive the private scalar from the seed, or from random keying material
+/// when no seed is provided
+fn secret_scalar(seed: Option<&[u8]>) -> Result<Scalar> {
+    match seed {
+        None => {
+            let mut ikm = [0u8; MIN_SEED_LENGTH];
+            OsRng.fill_bytes(&mut ikm);
+            let sk = key_gen(&ikm);
+            ikm.zeroize();
+            Ok(sk)
+        }
+        Some(seed) if seed.len() >= MIN_SEED_LENGTH => Ok(key_gen(seed)),
+        Some(_) => Err(err_msg!(Input, "Invalid length for seed")),
+    }
+}
+
+/// Derive a private key from input keying material using the `KeyGen`
+/// procedure of draft-irtf-cfrg-bls-signature-04, with an empty `key_info`
+fn key_gen(ikm: &[u8]) -> Scalar {
+    let mut salt = Sha256::digest(KEYGEN_SALT);
+    let mut input = Vec::with_capacity(ikm.len() + 1);
+    input.extend_from_slice(ikm);
+    input.push(0);
+    let sk = loop {
+        let mut okm = [0u8; 48];
+        Hkdf::<Sha256>::new(Some(&salt[..]), &input)
+            .expand(&[0, okm.len() as u8], &mut okm)
+            .expect("Invalid HKDF output length");
+        // reduce the big-endian integer modulo the group order
+        let mut wide = [0u8; 64];
+        for (dst, src) in wide.iter_mut().zip(okm.iter().rev()) {
+            *dst = *src;
+        }
+        let sk = Scalar::from_bytes_wide(&wide);
+        okm.zeroize();
+        wide.zeroize();
+        if sk != Scalar::zero() {
+            break sk;
+        }
+        salt = Sha256::digest(&salt[..]);
+    };
+    input.zeroize();
+    sk
+}
+
+fn import_scalar(secret: &[u8]) -> Result<Scalar> {
+    if secret.len() != SECRET_KEY_LENGTH {
+        return Err(err_msg!(Input, "Invalid BLS12-381 private key"));
+    }
+    let mut bytes = [0u8; SECRET_KEY_LENGTH];
+    bytes.copy_from_slice(secret);
+    bytes.reverse();
+    let sk = Option::<Scalar>::from(Scalar::from_bytes(&bytes));
+    bytes.zeroize();
+    sk.filter(|sk| *sk != Scalar::zero())
+        .ok_or_else(|| err_msg!(Input, "Invalid BLS12-381 private key"))
+}
+
+fn g1_keypair(sk: Scalar) -> (Vec<u8>, SecretBytes) {
+    let pk = G1Affine::from(G1Projective::generator() * &sk);
+    (pk.to_compressed().to_vec(), secret_bytes(sk))
+}
+
+fn g2_keypair(sk: Scalar) -> (Vec<u8>, SecretBytes) {
+    let pk = G2Affine::from(G2Projective::generator() * &sk);
+    (pk.to_compressed().to_vec(), secret_bytes(sk))
+}
+
+fn secret_bytes(sk: Scalar) -> SecretBytes {
+    // private keys are stored in big-endian byte order
+    let mut bytes = sk.to_bytes();
+    bytes.reverse();
+    let result = SecretBytes::from(&bytes[..]);
+    bytes.zeroize();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // test case 0 from EIP-2333, which derives the master key using KeyGen
+    const SEED: &'static str = "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+                                1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04";
+    const SECRET_KEY: &'static str =
+        "0d7359d57963ab8fbbde1852dcf553fedbc31f464d80ee7d40ae683122b45070";
+
+    #[test]
+    fn g1_keypair_expected() {
+        let seed = hex::decode(SEED).unwrap();
+        let (pk, sk) = generate_g1_keypair(Some(&seed)).unwrap();
+        assert_eq!(hex::encode(&*sk), SECRET_KEY);
+        assert_eq!(
+            hex::encode(&pk),
+            "a2c975348667926acf12f3eecb005044e08a7a9b7d95f30bd281b55445107367\
+             a2e5d0558be7943c8bd13f9a1a7036fb"
+        );
+        validate_g1_public_key(&pk).unwrap();
+        assert!(validate_g1_public_key(&pk[1..]).is_err());
+    }
+
+    #[test]
+    fn g2_keypair_expected() {
+        let seed = hex::decode(SEED).unwrap();
+        let (pk, sk) = generate_g2_keypair(Some(&seed)).unwrap();
+        assert_eq!(hex::encode(&*sk), SECRET_KEY);
+        assert_eq!(
+            hex::encode(&pk),
+            "a5e43d5ecb7b8c01ceb3b91f7413b628ef02c6859dc42a4354b21f9195531988\
+             a648655037faafd1bac2fd2d7d9466180baa3705a45a6c597853db51eaf43161\
+             6057fd8049c6bee8764292f9a104200a45a63ceae9d3c368643ab9e5ff0f8810"
+        );
+        validate_g2_public_key(&pk).unwrap();
+        assert!(validate_g2_public_key(&pk[..G1_PUBLIC_KEY_LENGTH]).is_err());
+    }
+
+    #[test]
+    fn import_round_trip() {
+        let seed = hex::decode(SEED).unwrap();
+        let (pk, sk) = generate_g2_keypair(Some(&seed)).unwrap();
+        assert_eq!(import_g2_keypair(&sk).unwrap(), (pk, sk.clone()));
+        assert_eq!(
+            import_g1_keypair(&sk).unwrap(),
+            generate_g1_keypair(Some(&seed)).unwrap()
+        );
+        assert!(import_g1_keypair(&[0u8; SECRET_KEY_LENGTH]).is_err());
+        assert!(import_g1_keypair(&[0xffu8; SECRET_KEY_LENGTH]).is_err());
+    }
+
+    #[test]
+    fn seed_length() {
+        assert!(generate_g1_keypair(Some(b"short")).is_err());
+        let (pk, _) = generate_g2_keypair(None).unwrap();
+        assert_eq!(pk.len(), G2_PUBLIC_KEY_LENGTH);
+    }
+}
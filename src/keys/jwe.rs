@@ -0,0 +1,737 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::kdf::concat::{derive_key, jwa_other_info};
+use super::symmetric::{self, CBC_HMAC_IV_LENGTH, CBC_HMAC_KEY_LENGTH, CBC_HMAC_TAG_LENGTH};
+use super::types::{KeyAlg, KeyEntry, KeyParams};
+use super::{decode_key_ident, generate_keypair, jwk, key_exchange};
+use crate::error::{Error, Result};
+use crate::future::BoxFuture;
+use crate::types::SecretBytes;
+
+/// The media type of an encrypted DIDComm v2 message
+const ENCRYPTED_MESSAGE_TYPE: &str = "application/didcomm-encrypted+json";
+
+const ALG_ECDH_ES_A256KW: &str = "ECDH-ES+A256KW";
+const ALG_ECDH_1PU_A256KW: &str = "ECDH-1PU+A256KW";
+
+/// The length of the key encryption key for A256KW
+const KEK_LENGTH: usize = 32;
+
+/// Content encryption algorithms for DIDComm v2 encrypted messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JweEncryption {
+    /// XChaCha20-Poly1305 (XC20P), only supported for anonymous encryption
+    XChaCha20Poly1305,
+    /// AES-256-CBC with HMAC-SHA-512 (A256CBC-HS512)
+    Aes256CbcHmacSha512,
+}
+
+impl JweEncryption {
+    /// Get a reference to a string representing the `JweEncryption`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::XChaCha20Poly1305 => "XC20P",
+            Self::Aes256CbcHmacSha512 => "A256CBC-HS512",
+        }
+    }
+
+    fn nonce_length(&self) -> usize {
+        match self {
+            Self::XChaCha20Poly1305 => 24,
+            Self::Aes256CbcHmacSha512 => CBC_HMAC_IV_LENGTH,
+        }
+    }
+
+    fn tag_length(&self) -> usize {
+        match self {
+            Self::XChaCha20Poly1305 => 16,
+            Self::Aes256CbcHmacSha512 => CBC_HMAC_TAG_LENGTH,
+        }
+    }
+
+    fn generate_key(&self) -> Result<SecretBytes> {
+        match self {
+            Self::XChaCha20Poly1305 => symmetric::generate_key(&KeyAlg::XChaCha20Poly1305),
+            Self::Aes256CbcHmacSha512 => {
+                let mut key = vec![0u8; CBC_HMAC_KEY_LENGTH];
+                OsRng.fill_bytes(&mut key);
+                Ok(SecretBytes::from(key))
+            }
+        }
+    }
+
+    /// Encrypt the message content, returning the nonce, ciphertext and tag
+    fn encrypt(
+        &self,
+        cek: &[u8],
+        message: &[u8],
+        aad: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        let mut enc = match self {
+            Self::XChaCha20Poly1305 => {
+                symmetric::encrypt(&KeyAlg::XChaCha20Poly1305, cek, None, message, aad)?
+            }
+            Self::Aes256CbcHmacSha512 => symmetric::encrypt_cbc_hmac(cek, None, message, aad)?,
+        };
+        let tag = enc.split_off(enc.len() - self.tag_length());
+        let ciphertext = enc.split_off(self.nonce_length());
+        Ok((enc, ciphertext, tag))
+    }
+
+    fn decrypt(
+        &self,
+        cek: &[u8],
+        nonce: &[u8],
+        ciphertext: &[u8],
+        tag: &[u8],
+        aad: &[u8],
+    ) -> Result<SecretBytes> {
+        if nonce.len() != self.nonce_length() || tag.len() != self.tag_length() {
+            return Err(err_msg!(Encryption, "Invalid JWE nonce or tag length"));
+        }
+        let mut enc = Vec::with_capacity(nonce.len() + ciphertext.len() + tag.len());
+        enc.extend_from_slice(nonce);
+        enc.extend_from_slice(ciphertext);
+        enc.extend_from_slice(tag);
+        match self {
+            Self::XChaCha20Poly1305 => {
+                symmetric::decrypt(&KeyAlg::XChaCha20Poly1305, cek, &enc, aad)
+            }
+            Self::Aes256CbcHmacSha512 => symmetric::decrypt_cbc_hmac(cek, &enc, aad),
+        }
+    }
+}
+
+impl Default for JweEncryption {
+    fn default() -> Self {
+        Self::Aes256CbcHmacSha512
+    }
+}
+
+impl FromStr for JweEncryption {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "XC20P" => Self::XChaCha20Poly1305,
+            "A256CBC-HS512" => Self::Aes256CbcHmacSha512,
+            _ => return Err(err_msg!(Unsupported, "Unsupported JWE content encryption")),
+        })
+    }
+}
+
+impl Display for JweEncryption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Look up the keypair for one of the recipients of an encrypted message
+pub(crate) trait JweKeyLookup<'a> {
+    /// Find the first available keypair, returning its index in `kids`
+    fn find<'f>(self, kids: &'f [String]) -> BoxFuture<'f, Option<(usize, KeyEntry)>>
+    where
+        'a: 'f;
+}
+
+#[derive(Deserialize, Serialize)]
+struct JweEnvelope {
+    protected: String,
+    recipients: Vec<JweRecipient>,
+    iv: String,
+    ciphertext: String,
+    tag: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct JweRecipient {
+    header: RecipientHeader,
+    encrypted_key: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RecipientHeader {
+    kid: String,
+}
+
+/// Encrypt a DIDComm v2 message for one or more recipient keys
+///
+/// Without a sender the message is anonymously encrypted (anoncrypt) using
+/// ECDH-ES+A256KW. Providing the identifier and parameters of the sender
+/// keypair produces an authenticated message (authcrypt) using
+/// ECDH-1PU+A256KW, which requires A256CBC-HS512 content encryption. The
+/// recipient and sender keys must all use the same curve.
+pub(crate) fn pack_message(
+    recipient_keys: &[&str],
+    sender: Option<(&str, &KeyParams)>,
+    message: &[u8],
+    enc: JweEncryption,
+) -> Result<Vec<u8>> {
+    let mut recipients = Vec::with_capacity(recipient_keys.len());
+    for ident in recipient_keys {
+        match decode_key_ident(ident)? {
+            Some((alg, pub_key)) if alg == KeyAlg::X25519 || alg == KeyAlg::P256 => {
+                recipients.push((*ident, alg, pub_key))
+            }
+            _ => {
+                return Err(err_msg!(
+                    Unsupported,
+                    "Unsupported recipient key: {}",
+                    ident
+                ))
+            }
+        }
+    }
+    let alg = match recipients.first() {
+        Some((_, alg, _)) => alg.clone(),
+        None => return Err(err_msg!(Input, "No message recipients")),
+    };
+    if recipients.iter().any(|(_, a, _)| *a != alg) {
+        return Err(err_msg!(
+            Input,
+            "Message recipients must use the same key algorithm"
+        ));
+    }
+    let sender = match sender {
+        Some((ident, params)) => {
+            if params.alg != alg {
+                return Err(err_msg!(
+                    Input,
+                    "Sender key algorithm does not match the recipients"
+                ));
+            }
+            if enc != JweEncryption::Aes256CbcHmacSha512 {
+                return Err(err_msg!(
+                    Input,
+                    "Authenticated encryption requires A256CBC-HS512"
+                ));
+            }
+            let prv_key = params
+                .prv_key
+                .as_ref()
+                .ok_or_else(|| err_msg!(Input, "Undefined private key"))?;
+            Some((ident, prv_key))
+        }
+        None => None,
+    };
+    let key_alg = if sender.is_some() {
+        ALG_ECDH_1PU_A256KW
+    } else {
+        ALG_ECDH_ES_A256KW
+    };
+
+    let (epk, esk) = generate_keypair(&alg, None)?;
+    let apu = sender
+        .map(|(ident, _)| ident.as_bytes().to_vec())
+        .unwrap_or_default();
+    let mut kids = recipient_keys.to_vec();
+    kids.sort_unstable();
+    let apv = Sha256::digest(kids.join(".").as_bytes()).to_vec();
+
+    let mut protected = Map::new();
+    protected.insert("typ".to_string(), ENCRYPTED_MESSAGE_TYPE.into());
+    protected.insert("alg".to_string(), key_alg.into());
+    protected.insert("enc".to_string(), enc.as_str().into());
+    if let Some((ident, _)) = sender {
+        protected.insert("skid".to_string(), ident.into());
+        protected.insert("apu".to_string(), encode(&apu).into());
+    }
+    protected.insert("apv".to_string(), encode(&apv).into());
+    protected.insert(
+        "epk".to_string(),
+        serde_json::from_str(&jwk::encode_jwk(&alg, Some(&epk), None)?)
+            .map_err(err_map!(Unexpected, "Error encoding JWK"))?,
+    );
+    let protected = encode(
+        &serde_json::to_vec(&protected).map_err(err_map!(Unexpected, "Error encoding header"))?,
+    );
+
+    let cek = enc.generate_key()?;
+    let (iv, ciphertext, tag) = enc.encrypt(&cek, message, protected.as_bytes())?;
+    let cc_tag = sender.map(|_| tag.as_slice());
+
+    let recipients = recipients
+        .into_iter()
+        .map(|(kid, _, pub_key)| {
+            let ze = key_exchange(&alg, &esk, &pub_key)?;
+            let zs = match sender {
+                Some((_, sender_key)) => Some(key_exchange(&alg, sender_key, &pub_key)?),
+                None => None,
+            };
+            let kek = derive_kek(ze, zs, key_alg, &apu, &apv, KEK_LENGTH, cc_tag);
+            Ok(JweRecipient {
+                header: RecipientHeader {
+                    kid: kid.to_string(),
+                },
+                encrypted_key: encode(&symmetric::wrap_key(&kek, &cek)?),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    serde_json::to_vec(&JweEnvelope {
+        protected,
+        recipients,
+        iv: encode(&iv),
+        ciphertext: encode(&ciphertext),
+        tag: encode(&tag),
+    })
+    .map_err(err_map!(Unexpected, "Error encoding JWE"))
+}
+
+/// Decrypt a DIDComm v2 message, using `lookup` to find the keypair for one
+/// of the recipients
+///
+/// Returns the decrypted message, the identifier of the recipient key, and
+/// the identifier of the sender key for authenticated messages.
+pub(crate) async fn unpack_message<'a>(
+    message: &[u8],
+    lookup: impl JweKeyLookup<'a>,
+) -> Result<(SecretBytes, String, Option<String>)> {
+    let jwe = Jwe::from_slice(message)?;
+    let kids = jwe
+        .recipients
+        .iter()
+        .map(|(kid, _)| kid.clone())
+        .collect::<Vec<_>>();
+    let (idx, recip) = lookup
+        .find(&kids)
+        .await
+        .ok_or_else(|| err_msg!(NotFound, "No matching recipient key"))?;
+    if recip.params.alg != jwe.epk.0 {
+        return Err(err_msg!(
+            Input,
+            "Recipient key algorithm does not match the message"
+        ));
+    }
+    let recip_key = recip
+        .params
+        .prv_key
+        .as_ref()
+        .ok_or_else(|| err_msg!(Input, "Undefined private key"))?;
+
+    let sender = match (jwe.authcrypt, jwe.skid.as_ref()) {
+        (true, Some(skid)) => match decode_key_ident(skid)? {
+            Some((alg, pub_key)) if alg == jwe.epk.0 => Some((skid.clone(), pub_key)),
+            _ => return Err(err_msg!(Unsupported, "Unsupported sender key")),
+        },
+        (true, None) => return Err(err_msg!(Input, "Missing JWE header: skid")),
+        (false, _) => None,
+    };
+
+    let message = jwe.decrypt(
+        idx,
+        recip_key,
+        sender.as_ref().map(|(_, pub_key)| pub_key.as_slice()),
+    )?;
+    Ok((
+        message,
+        kids.into_iter().nth(idx).unwrap_or_default(),
+        sender.map(|(skid, _)| skid),
+    ))
+}
+
+/// A parsed JWE in the general JSON serialization
+struct Jwe {
+    protected: String,
+    key_alg: String,
+    authcrypt: bool,
+    kek_len: usize,
+    enc: JweEncryption,
+    epk: (KeyAlg, Vec<u8>),
+    apu: Vec<u8>,
+    apv: Vec<u8>,
+    skid: Option<String>,
+    recipients: Vec<(String, Vec<u8>)>,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+    tag: Vec<u8>,
+}
+
+impl Jwe {
+    fn from_slice(data: &[u8]) -> Result<Self> {
+        let envelope: JweEnvelope =
+            serde_json::from_slice(data).map_err(err_map!(Input, "Invalid JWE"))?;
+        let header: Map<String, Value> = serde_json::from_slice(&decode(&envelope.protected)?)
+            .map_err(err_map!(Input, "Invalid JWE header"))?;
+        let field = |name: &str| match header.get(name) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.as_str())),
+            _ => Err(err_msg!(Input, "Invalid JWE header: {}", name)),
+        };
+
+        let key_alg = field("alg")?
+            .ok_or_else(|| err_msg!(Input, "Missing JWE header: alg"))?
+            .to_string();
+        let (authcrypt, kek_len) = match key_alg.as_str() {
+            "ECDH-ES+A128KW" => (false, 16),
+            ALG_ECDH_ES_A256KW => (false, KEK_LENGTH),
+            "ECDH-1PU+A128KW" => (true, 16),
+            ALG_ECDH_1PU_A256KW => (true, KEK_LENGTH),
+            _ => return Err(err_msg!(Unsupported, "Unsupported JWE algorithm")),
+        };
+        let enc = JweEncryption::from_str(
+            field("enc")?.ok_or_else(|| err_msg!(Input, "Missing JWE header: enc"))?,
+        )?;
+        // ECDH-1PU in key wrapping mode requires an AEAD with a compact tag
+        if authcrypt && enc != JweEncryption::Aes256CbcHmacSha512 {
+            return Err(err_msg!(
+                Input,
+                "Authenticated encryption requires A256CBC-HS512"
+            ));
+        }
+        let epk = jwk::decode_public_key(
+            header
+                .get("epk")
+                .cloned()
+                .ok_or_else(|| err_msg!(Input, "Missing JWE header: epk"))?,
+        )?;
+        let apu = field("apu")?.map(decode).transpose()?.unwrap_or_default();
+        let apv = field("apv")?.map(decode).transpose()?.unwrap_or_default();
+        let skid = field("skid")?.map(str::to_string);
+
+        let recipients = envelope
+            .recipients
+            .iter()
+            .map(|recip| Ok((recip.header.kid.clone(), decode(&recip.encrypted_key)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if recipients.is_empty() {
+            return Err(err_msg!(Input, "No message recipients"));
+        }
+
+        Ok(Self {
+            key_alg,
+            authcrypt,
+            kek_len,
+            enc,
+            epk,
+            apu,
+            apv,
+            skid,
+            recipients,
+            iv: decode(&envelope.iv)?,
+            ciphertext: decode(&envelope.ciphertext)?,
+            tag: decode(&envelope.tag)?,
+            protected: envelope.protected,
+        })
+    }
+
+    /// Decrypt the message content using the private key of the recipient at
+    /// index `idx`, along with the sender public key for ECDH-1PU
+    fn decrypt(
+        &self,
+        idx: usize,
+        recip_key: &[u8],
+        sender_key: Option<&[u8]>,
+    ) -> Result<SecretBytes> {
+        let (alg, epk) = &self.epk;
+        let encrypted_key = &self
+            .recipients
+            .get(idx)
+            .ok_or_else(|| err_msg!(Input, "Invalid recipient index"))?
+            .1;
+        let ze = key_exchange(alg, recip_key, epk)?;
+        let zs = match (self.authcrypt, sender_key) {
+            (false, _) => None,
+            (true, Some(sender_key)) => Some(key_exchange(alg, recip_key, sender_key)?),
+            (true, None) => return Err(err_msg!(Input, "Missing sender key")),
+        };
+        let cc_tag = if self.authcrypt {
+            Some(self.tag.as_slice())
+        } else {
+            None
+        };
+        let kek = derive_kek(
+            ze,
+            zs,
+            &self.key_alg,
+            &self.apu,
+            &self.apv,
+            self.kek_len,
+            cc_tag,
+        );
+        let cek = symmetric::unwrap_key(&kek, encrypted_key)?;
+        self.enc.decrypt(
+            &cek,
+            &self.iv,
+            &self.ciphertext,
+            &self.tag,
+            self.protected.as_bytes(),
+        )
+    }
+}
+
+/// Derive the key encryption key for a recipient from the ephemeral shared
+/// secret and, for ECDH-1PU, the static shared secret
+fn derive_kek(
+    ze: SecretBytes,
+    zs: Option<SecretBytes>,
+    alg: &str,
+    apu: &[u8],
+    apv: &[u8],
+    kek_len: usize,
+    cc_tag: Option<&[u8]>,
+) -> SecretBytes {
+    let mut z = Vec::with_capacity(ze.len() * 2);
+    z.extend_from_slice(&ze);
+    if let Some(zs) = zs {
+        z.extend_from_slice(&zs);
+    }
+    let z = SecretBytes::from(z);
+    derive_key(&z, &jwa_other_info(alg, apu, apv, kek_len, cc_tag), kek_len)
+}
+
+fn encode(value: &[u8]) -> String {
+    base64::encode_config(value, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+        .map_err(err_map!(Input, "Invalid base64url value in JWE"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::encode_key_ident;
+    use super::*;
+
+    // draft-madden-jose-ecdh-1pu-04 appendix A
+    const ALICE_PK: &'static str =
+        "2a76e6fc171d42bed6228cfebaa8adf4cd306dc7c4afacbef547c867c427043e";
+    const BOB_SK: &'static str = "d600e2ae5febfd8dfea946b75971e0117aeb1079e0593854ddcf738fd036b818";
+    const CHARLIE_SK: &'static str =
+        "25cbfc8249613230b46fe96c939a2705ba69580c799dc36d6cceb726bf710501";
+    const ECDH_1PU_JWE: &'static str = r#"{
+        "protected": "eyJhbGciOiJFQ0RILTFQVStBMTI4S1ciLCJlbmMiOiJBMjU2Q0JDLUhTNTEyIiwiYXB1IjoiUVd4cFkyVSIsImFwdiI6IlFtOWlJR0Z1WkNCRGFHRnliR2xsIiwiZXBrIjp7Imt0eSI6Ik9LUCIsImNydiI6IlgyNTUxOSIsIngiOiJrOW9mX2NwQWFqeTBwb1c1Z2FpeFhHczluSGt3ZzFBRnFVQUZhMzlkeUJjIn19",
+        "unprotected": {"jku": "https://alice.example.com/keys.jwks"},
+        "recipients": [
+            {
+                "header": {"kid": "bob-key-2"},
+                "encrypted_key": "pOMVA9_PtoRe7xXW1139NzzN1UhiFoio8lGto9cf0t8PyU-sjNXH8-LIRLycq8CHJQbDwvQeU1cSl55cQ0hGezJu2N9IY0QN"
+            },
+            {
+                "header": {"kid": "2021-05-06"},
+                "encrypted_key": "56GVudgRLIMEElQ7DpXsijJVRSWUSDNdbWkdV3g0GUNq6hcT_GkxwnxlPIWrTXCqRpVKQC8fe4z3PQ2YH2afvjQ28aiCTWFE"
+            }
+        ],
+        "iv": "AAECAwQFBgcICQoLDA0ODw",
+        "ciphertext": "Az2IWsISEMDJvyc5XRL-3-d-RgNBOGolCsxFFoUXFYw",
+        "tag": "HLb4fTlm8spGmij3RyOs2gJ4DpHM4hhVRwdF_hGb3WQ"
+    }"#;
+
+    // Anoncrypt and authcrypt examples from the DIDComm Messaging
+    // specification (appendix C), addressed to Bob's three X25519 keys.
+    // The A256CBC-HS512 anoncrypt example uses P-384 keys, which are not
+    // supported here.
+    const DIDCOMM_BOB_SKS: [&'static str; 3] = [
+        "6fd367b8e081d219bb60636f684f43321c07ff08d9035fa0583e9d03425674bd",
+        "a7ebed7a8175828a67cb51d7cb0b7beb1cffb82f375149ab82ccec23e4e104a9",
+        "7fd5897ae417108b6418cf2c84de1da85af97cb40b05ab079d667ef1d3da4a8d",
+    ];
+    const DIDCOMM_ALICE_PK: &'static str =
+        "6af1f43b6638b6a2c0abccbdce989a9ebf1a8e28b99b817f9880abccd95ab57b";
+    const DIDCOMM_MESSAGE: &'static [u8] = br#"{"id":"1234567890","typ":"application/didcomm-plain+json","type":"http://example.com/protocols/lets_do_lunch/1.0/proposal","from":"did:example:alice","to":["did:example:bob"],"created_time":1516269022,"expires_time":1516385931,"body":{"messagespecificattribute":"and its value"}}"#;
+    const ANONCRYPT_XC20P_JWE: &'static str = r#"{
+        "protected": "eyJlcGsiOnsia3R5IjoiT0tQIiwiY3J2IjoiWDI1NTE5IiwieCI6IkpIanNtSVJaQWFCMHpSR193TlhMVjJyUGdnRjAwaGRIYlc1cmo4ZzBJMjQifSwiYXB2IjoiTmNzdUFuclJmUEs2OUEtcmtaMEw5WFdVRzRqTXZOQzNaZzc0QlB6NTNQQSIsInR5cCI6ImFwcGxpY2F0aW9uL2RpZGNvbW0tZW5jcnlwdGVkK2pzb24iLCJlbmMiOiJYQzIwUCIsImFsZyI6IkVDREgtRVMrQTI1NktXIn0",
+        "recipients": [
+            {
+                "header": {"kid": "did:example:bob#key-x25519-1"},
+                "encrypted_key": "3n1olyBR3nY7ZGAprOx-b7wYAKza6cvOYjNwVg3miTnbLwPP_FmE1A"
+            },
+            {
+                "header": {"kid": "did:example:bob#key-x25519-2"},
+                "encrypted_key": "j5eSzn3kCrIkhQAWPnEwrFPMW6hG0zF_y37gUvvc5gvlzsuNX4hXrQ"
+            },
+            {
+                "header": {"kid": "did:example:bob#key-x25519-3"},
+                "encrypted_key": "TEWlqlq-ao7Lbynf0oZYhxs7ZB39SUWBCK4qjqQqfeItfwmNyDm73A"
+            }
+        ],
+        "iv": "ESpmcyGiZpRjc5urDela21TOOTW8Wqd1",
+        "ciphertext": "KWS7gJU7TbyJlcT9dPkCw-ohNigGaHSukR9MUqFM0THbCTCNkY-g5tahBFyszlKIKXs7qOtqzYyWbPou2q77XlAeYs93IhF6NvaIjyNqYklvj-OtJt9W2Pj5CLOMdsR0C30wchGoXd6wEQZY4ttbzpxYznqPmJ0b9KW6ZP-l4_DSRYe9B-1oSWMNmqMPwluKbtguC-riy356Xbu2C9ShfWmpmjz1HyJWQhZfczuwkWWlE63g26FMskIZZd_jGpEhPFHKUXCFwbuiw_Iy3R0BIzmXXdK_w7PZMMPbaxssl2UeJmLQgCAP8j8TukxV96EKa6rGgULvlo7qibjJqsS5j03bnbxkuxwbfyu3OxwgVzFWlyHbUH6p",
+        "tag": "6ylC_iAs4JvDQzXeY6MuYQ"
+    }"#;
+    const AUTHCRYPT_JWE: &'static str = r#"{
+        "protected": "eyJlcGsiOnsia3R5IjoiT0tQIiwiY3J2IjoiWDI1NTE5IiwieCI6IkdGY01vcEpsamY0cExaZmNoNGFfR2hUTV9ZQWY2aU5JMWRXREd5VkNhdzAifSwiYXB2IjoiTmNzdUFuclJmUEs2OUEtcmtaMEw5WFdVRzRqTXZOQzNaZzc0QlB6NTNQQSIsInNraWQiOiJkaWQ6ZXhhbXBsZTphbGljZSNrZXkteDI1NTE5LTEiLCJhcHUiOiJaR2xrT21WNFlXMXdiR1U2WVd4cFkyVWphMlY1TFhneU5UVXhPUzB4IiwidHlwIjoiYXBwbGljYXRpb24vZGlkY29tbS1lbmNyeXB0ZWQranNvbiIsImVuYyI6IkEyNTZDQkMtSFM1MTIiLCJhbGciOiJFQ0RILTFQVStBMjU2S1cifQ",
+        "recipients": [
+            {
+                "header": {"kid": "did:example:bob#key-x25519-1"},
+                "encrypted_key": "o0FJASHkQKhnFo_rTMHTI9qTm_m2mkJp-wv96mKyT5TP7QjBDuiQ0AMKaPI_RLLB7jpyE-Q80Mwos7CvwbMJDhIEBnk2qHVB"
+            },
+            {
+                "header": {"kid": "did:example:bob#key-x25519-2"},
+                "encrypted_key": "rYlafW0XkNd8kaXCqVbtGJ9GhwBC3lZ9AihHK4B6J6V2kT7vjbSYuIpr1IlAjvxYQOw08yqEJNIwrPpB0ouDzKqk98FVN7rK"
+            },
+            {
+                "header": {"kid": "did:example:bob#key-x25519-3"},
+                "encrypted_key": "aqfxMY2sV-njsVo-_9Ke9QbOf6hxhGrUVh_m-h_Aq530w3e_4IokChfKWG1tVJvXYv_AffY7vxj0k5aIfKZUxiNmBwC_QsNo"
+            }
+        ],
+        "iv": "o02OXDQ6_-sKz2PX_6oyJg",
+        "ciphertext": "MJezmxJ8DzUB01rMjiW6JViSaUhsZBhMvYtezkhmwts1qXWtDB63i4-FHZP6cJSyCI7eU-gqH8lBXO_UVuviWIqnIUrTRLaumanZ4q1dNKAnxNL-dHmb3coOqSvy3ZZn6W17lsVudjw7hUUpMbeMbQ5W8GokK9ZCGaaWnqAzd1ZcuGXDuemWeA8BerQsfQw_IQm-aUKancldedHSGrOjVWgozVL97MH966j3i9CJc3k9jS9xDuE0owoWVZa7SxTmhl1PDetmzLnYIIIt-peJtNYGdpd-FcYxIFycQNRUoFEr77h4GBTLbC-vqbQHJC1vW4O2LEKhnhOAVlGyDYkNbA4DSL-LMwKxenQXRARsKSIMn7z-ZIqTE-VCNj9vbtgR",
+        "tag": "uYeo7IsZjN7AnvBjUZE5lNryNENbf6_zew_VC-d4b3U"
+    }"#;
+
+    fn keypair(alg: KeyAlg) -> (String, KeyParams) {
+        let (pk, sk) = generate_keypair(&alg, None).unwrap();
+        let ident = encode_key_ident(&alg, &pk).unwrap();
+        (
+            ident,
+            KeyParams {
+                alg,
+                metadata: None,
+                reference: None,
+                pub_key: Some(pk),
+                prv_key: Some(sk),
+                exportable: true,
+            },
+        )
+    }
+
+    #[test]
+    fn ecdh_1pu_expected() {
+        let jwe = Jwe::from_slice(ECDH_1PU_JWE.as_bytes()).unwrap();
+        assert!(jwe.authcrypt);
+        assert_eq!(jwe.recipients[1].0, "2021-05-06");
+        let alice_pk = hex::decode(ALICE_PK).unwrap();
+        for (idx, sk) in [BOB_SK, CHARLIE_SK].iter().enumerate() {
+            let sk = hex::decode(sk).unwrap();
+            let message = jwe.decrypt(idx, &sk, Some(alice_pk.as_slice())).unwrap();
+            assert_eq!(message, &b"Three is a magic number."[..]);
+            assert!(jwe.decrypt(idx, &sk, None).is_err());
+        }
+        let bob_sk = hex::decode(BOB_SK).unwrap();
+        assert!(jwe.decrypt(1, &bob_sk, Some(alice_pk.as_slice())).is_err());
+    }
+
+    fn check_didcomm_vector(
+        message: &str,
+        key_alg: &str,
+        enc: JweEncryption,
+        sender_pk: Option<&str>,
+    ) {
+        let jwe = Jwe::from_slice(message.as_bytes()).unwrap();
+        assert_eq!(jwe.key_alg, key_alg);
+        assert_eq!(jwe.enc, enc);
+        assert_eq!(jwe.recipients[0].0, "did:example:bob#key-x25519-1");
+        let sender_pk = sender_pk.map(|pk| hex::decode(pk).unwrap());
+        for (idx, sk) in DIDCOMM_BOB_SKS.iter().enumerate() {
+            let sk = hex::decode(sk).unwrap();
+            let message = jwe
+                .decrypt(idx, &sk, sender_pk.as_ref().map(Vec::as_slice))
+                .unwrap();
+            assert_eq!(message, DIDCOMM_MESSAGE);
+        }
+        let bob_sk = hex::decode(DIDCOMM_BOB_SKS[0]).unwrap();
+        assert!(jwe
+            .decrypt(1, &bob_sk, sender_pk.as_ref().map(Vec::as_slice))
+            .is_err());
+    }
+
+    #[test]
+    fn anoncrypt_xc20p_expected() {
+        check_didcomm_vector(
+            ANONCRYPT_XC20P_JWE,
+            ALG_ECDH_ES_A256KW,
+            JweEncryption::XChaCha20Poly1305,
+            None,
+        );
+    }
+
+    #[test]
+    fn authcrypt_expected() {
+        check_didcomm_vector(
+            AUTHCRYPT_JWE,
+            ALG_ECDH_1PU_A256KW,
+            JweEncryption::Aes256CbcHmacSha512,
+            Some(DIDCOMM_ALICE_PK),
+        );
+        let jwe = Jwe::from_slice(AUTHCRYPT_JWE.as_bytes()).unwrap();
+        assert_eq!(
+            jwe.skid.as_ref().map(String::as_str),
+            Some("did:example:alice#key-x25519-1")
+        );
+        assert_eq!(jwe.apu, b"did:example:alice#key-x25519-1");
+        let bob_sk = hex::decode(DIDCOMM_BOB_SKS[0]).unwrap();
+        assert!(jwe.decrypt(0, &bob_sk, None).is_err());
+    }
+
+    #[test]
+    fn anoncrypt_round_trip() {
+        for alg in &[KeyAlg::X25519, KeyAlg::P256] {
+            for enc in &[
+                JweEncryption::XChaCha20Poly1305,
+                JweEncryption::Aes256CbcHmacSha512,
+            ] {
+                let (bob, bob_params) = keypair(alg.clone());
+                let (carol, carol_params) = keypair(alg.clone());
+                let packed =
+                    pack_message(&[bob.as_str(), carol.as_str()], None, b"hello there", *enc)
+                        .unwrap();
+                let jwe = Jwe::from_slice(&packed).unwrap();
+                assert!(!jwe.authcrypt);
+                assert_eq!(jwe.key_alg, ALG_ECDH_ES_A256KW);
+                assert_eq!(jwe.enc, *enc);
+                assert_eq!(jwe.skid, None);
+                for (idx, params) in [bob_params, carol_params].iter().enumerate() {
+                    let sk = params.prv_key.as_ref().unwrap();
+                    assert_eq!(jwe.decrypt(idx, sk, None).unwrap(), &b"hello there"[..]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn authcrypt_round_trip() {
+        for alg in &[KeyAlg::X25519, KeyAlg::P256] {
+            let (alice, alice_params) = keypair(alg.clone());
+            let (bob, bob_params) = keypair(alg.clone());
+            let packed = pack_message(
+                &[bob.as_str()],
+                Some((alice.as_str(), &alice_params)),
+                b"hello there",
+                JweEncryption::Aes256CbcHmacSha512,
+            )
+            .unwrap();
+            let jwe = Jwe::from_slice(&packed).unwrap();
+            assert!(jwe.authcrypt);
+            assert_eq!(jwe.key_alg, ALG_ECDH_1PU_A256KW);
+            assert_eq!(jwe.skid.as_ref(), Some(&alice));
+            assert_eq!(jwe.apu, alice.as_bytes());
+            let bob_sk = bob_params.prv_key.as_ref().unwrap();
+            let alice_pk = alice_params.pub_key.as_ref().unwrap();
+            assert_eq!(
+                jwe.decrypt(0, bob_sk, Some(alice_pk.as_slice())).unwrap(),
+                &b"hello there"[..]
+            );
+            // the message is bound to the sender key
+            let (_, other_params) = keypair(alg.clone());
+            let other_pk = other_params.pub_key.as_ref().unwrap();
+            assert!(jwe.decrypt(0, bob_sk, Some(other_pk.as_slice())).is_err());
+
+            assert!(pack_message(
+                &[bob.as_str()],
+                Some((alice.as_str(), &alice_params)),
+                b"hello there",
+                JweEncryption::XChaCha20Poly1305,
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn pack_invalid_recipients() {
+        let (x25519, _) = keypair(KeyAlg::X25519);
+        let (p256, _) = keypair(KeyAlg::P256);
+        let (ed25519, _) = keypair(KeyAlg::ED25519);
+        let enc = JweEncryption::default();
+        assert!(pack_message(&[], None, b"hello", enc).is_err());
+        assert!(pack_message(&[x25519.as_str(), p256.as_str()], None, b"hello", enc).is_err());
+        assert!(pack_message(&[ed25519.as_str()], None, b"hello", enc).is_err());
+    }
+}
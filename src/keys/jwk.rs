@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use super::{normalize_public_key, secp256k1, secp256r1, types::KeyAlg};
+use crate::error::Result;
+use crate::types::SecretBytes;
+
+/// The key type and curve (or symmetric algorithm) used to represent a
+/// `KeyAlg` as a JWK
+pub(crate) fn jwk_params(alg: &KeyAlg) -> Result<(&'static str, &'static str)> {
+    Ok(match alg {
+        KeyAlg::ED25519 => ("OKP", "Ed25519"),
+        KeyAlg::X25519 => ("OKP", "X25519"),
+        KeyAlg::Secp256k1 => ("EC", "secp256k1"),
+        KeyAlg::P256 => ("EC", "P-256"),
+        KeyAlg::Bls12381G1 => ("OKP", "Bls12381G1"),
+        KeyAlg::Bls12381G2 => ("OKP", "Bls12381G2"),
+        KeyAlg::ChaCha20Poly1305 => ("oct", "C20P"),
+        KeyAlg::XChaCha20Poly1305 => ("oct", "XC20P"),
+        KeyAlg::Aes256Gcm => ("oct", "A256GCM"),
+        KeyAlg::Other(_) => return Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+    })
+}
+
+#[derive(Deserialize, Serialize)]
+struct JwkParts {
+    kty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crv: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    y: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    d: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    k: Option<String>,
+}
+
+impl Drop for JwkParts {
+    fn drop(&mut self) {
+        self.d.zeroize();
+        self.k.zeroize();
+    }
+}
+
+/// Encode a key as a JWK, including the private key if one is provided
+///
+/// Symmetric keys can only be encoded along with the secret key.
+pub(crate) fn encode_jwk(
+    alg: &KeyAlg,
+    pub_key: Option<&[u8]>,
+    prv_key: Option<&[u8]>,
+) -> Result<String> {
+    let (kty, crv) = jwk_params(alg)?;
+    let mut parts = JwkParts {
+        kty: kty.to_string(),
+        crv: None,
+        alg: None,
+        x: None,
+        y: None,
+        d: None,
+        k: None,
+    };
+    if kty == "oct" {
+        let secret = prv_key.ok_or_else(|| err_msg!(Input, "Undefined secret key"))?;
+        parts.alg = Some(crv.to_string());
+        parts.k = Some(encode_param(secret));
+    } else {
+        let pub_key = pub_key.ok_or_else(|| err_msg!(Input, "Undefined public key"))?;
+        parts.crv = Some(crv.to_string());
+        if kty == "EC" {
+            let point = match alg {
+                KeyAlg::Secp256k1 => secp256k1::expand_public_key(pub_key)?,
+                _ => secp256r1::expand_public_key(pub_key)?,
+            };
+            let (x, y) = point[1..].split_at((point.len() - 1) / 2);
+            parts.x = Some(encode_param(x));
+            parts.y = Some(encode_param(y));
+        } else {
+            parts.x = Some(encode_param(pub_key));
+        }
+        if let Some(prv_key) = prv_key {
+            // Ed25519 private keys are stored along with the public key
+            let secret = match alg {
+                KeyAlg::ED25519 => &prv_key[..prv_key.len().min(32)],
+                _ => prv_key,
+            };
+            parts.d = Some(encode_param(secret));
+        }
+    }
+    serde_json::to_string(&parts).map_err(err_map!(Unexpected, "Error encoding JWK"))
+}
+
+/// Decode a private or secret key JWK, returning the raw private key and the
+/// public key if one is included
+///
+/// Public keys for elliptic curve (`EC`) keys are returned in uncompressed
+/// SEC1 format.
+pub(crate) fn decode_private_key(
+    alg: &KeyAlg,
+    jwk: &[u8],
+) -> Result<(SecretBytes, Option<Vec<u8>>)> {
+    let parts: JwkParts = serde_json::from_slice(jwk).map_err(err_map!(Input, "Invalid JWK"))?;
+    let (kty, crv) = jwk_params(alg)?;
+    if parts.kty != kty {
+        return Err(err_msg!(Input, "JWK key type mismatch"));
+    }
+    if kty == "oct" {
+        // the algorithm is optional for symmetric keys
+        if parts.alg.as_ref().map(|a| a != crv).unwrap_or(false) {
+            return Err(err_msg!(Input, "JWK algorithm mismatch"));
+        }
+        let secret = decode_param(parts.k.as_ref(), "k")?;
+        return Ok((secret.into(), None));
+    }
+    if parts.crv.as_ref().map(String::as_str) != Some(crv) {
+        return Err(err_msg!(Input, "JWK curve mismatch"));
+    }
+    let secret = decode_param(parts.d.as_ref(), "d")?;
+    let pub_key = match (&parts.x, &parts.y) {
+        (Some(x), Some(y)) if kty == "EC" => {
+            let mut pk = vec![4u8];
+            pk.extend(decode_param(Some(x), "x")?);
+            pk.extend(decode_param(Some(y), "y")?);
+            Some(pk)
+        }
+        (Some(x), None) if kty == "OKP" => Some(decode_param(Some(x), "x")?),
+        (None, None) => None,
+        _ => return Err(err_msg!(Input, "Invalid JWK public key")),
+    };
+    Ok((secret.into(), pub_key))
+}
+
+/// Decode a public key JWK, returning the key algorithm and the normalized
+/// public key
+pub(crate) fn decode_public_key(jwk: serde_json::Value) -> Result<(KeyAlg, Vec<u8>)> {
+    let parts: JwkParts = serde_json::from_value(jwk).map_err(err_map!(Input, "Invalid JWK"))?;
+    let alg = [
+        KeyAlg::ED25519,
+        KeyAlg::X25519,
+        KeyAlg::Secp256k1,
+        KeyAlg::P256,
+        KeyAlg::Bls12381G1,
+        KeyAlg::Bls12381G2,
+    ]
+    .iter()
+    .find(|alg| match jwk_params(alg) {
+        Ok((kty, crv)) => parts.kty == kty && parts.crv.as_ref().map(String::as_str) == Some(crv),
+        Err(_) => false,
+    })
+    .cloned()
+    .ok_or_else(|| err_msg!(Unsupported, "Unsupported JWK key type"))?;
+    let pub_key = match (&parts.x, &parts.y) {
+        (Some(x), Some(y)) if parts.kty == "EC" => {
+            let mut pk = vec![4u8];
+            pk.extend(decode_param(Some(x), "x")?);
+            pk.extend(decode_param(Some(y), "y")?);
+            pk
+        }
+        (Some(x), None) if parts.kty == "OKP" => decode_param(Some(x), "x")?,
+        _ => return Err(err_msg!(Input, "Invalid JWK public key")),
+    };
+    let pub_key = normalize_public_key(&alg, pub_key)?;
+    Ok((alg, pub_key))
+}
+
+fn encode_param(value: &[u8]) -> String {
+    base64::encode_config(value, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode_param(value: Option<&String>, name: &str) -> Result<Vec<u8>> {
+    let value = value.ok_or_else(|| err_msg!(Input, "Missing JWK parameter: {}", name))?;
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD).map_err(err_map!(
+        Input,
+        "Invalid JWK parameter: {}",
+        name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_ed25519() {
+        // RFC 8037 appendix A.1
+        let jwk = br#"{"kty":"OKP","crv":"Ed25519",
+            "d":"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A",
+            "x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#;
+        let (sk, pk) = decode_private_key(&KeyAlg::ED25519, jwk).unwrap();
+        assert_eq!(
+            hex::encode(&*sk),
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+        );
+        assert_eq!(
+            hex::encode(pk.unwrap()),
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"
+        );
+        assert!(decode_private_key(&KeyAlg::X25519, jwk).is_err());
+    }
+
+    #[test]
+    fn encode_ed25519() {
+        let pk = hex::decode("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+            .unwrap();
+        let sk = hex::decode(
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60\
+             d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+        )
+        .unwrap();
+        assert_eq!(
+            encode_jwk(&KeyAlg::ED25519, Some(&pk), None).unwrap(),
+            r#"{"kty":"OKP","crv":"Ed25519","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#
+        );
+        assert_eq!(
+            encode_jwk(&KeyAlg::ED25519, Some(&pk), Some(&sk)).unwrap(),
+            r#"{"kty":"OKP","crv":"Ed25519","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo","d":"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A"}"#
+        );
+    }
+
+    #[test]
+    fn encode_p256() {
+        let pk = hex::decode("037fcdce2770f6c45d4183cbee6fdb4b7b580733357be9ef13bacf6e3c7bd15445")
+            .unwrap();
+        assert_eq!(
+            encode_jwk(&KeyAlg::P256, Some(&pk), None).unwrap(),
+            r#"{"kty":"EC","crv":"P-256","x":"f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU","y":"x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0"}"#
+        );
+        assert!(encode_jwk(&KeyAlg::ChaCha20Poly1305, None, None).is_err());
+    }
+
+    #[test]
+    fn decode_p256() {
+        // RFC 7515 appendix A.3
+        let jwk = br#"{"kty":"EC","crv":"P-256",
+            "x":"f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+            "y":"x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0",
+            "d":"jpsQnnGQmL-YBIffH1136cspYG6-0iY7X1fCE9-E9LI"}"#;
+        let (sk, pk) = decode_private_key(&KeyAlg::P256, jwk).unwrap();
+        assert_eq!(
+            hex::encode(&*sk),
+            "8e9b109e719098bf980487df1f5d77e9cb29606ebed2263b5f57c213df84f4b2"
+        );
+        assert_eq!(pk.unwrap().len(), 65);
+        assert!(decode_private_key(&KeyAlg::Secp256k1, jwk).is_err());
+    }
+
+    #[test]
+    fn decode_public_p256() {
+        let pk = hex::decode("037fcdce2770f6c45d4183cbee6fdb4b7b580733357be9ef13bacf6e3c7bd15445")
+            .unwrap();
+        let jwk = encode_jwk(&KeyAlg::P256, Some(&pk), None).unwrap();
+        let (alg, decoded) = decode_public_key(serde_json::from_str(&jwk).unwrap()).unwrap();
+        assert_eq!(alg, KeyAlg::P256);
+        assert_eq!(decoded, pk);
+        assert!(decode_public_key(serde_json::json!({"kty": "EC", "crv": "P-384"})).is_err());
+    }
+
+    #[test]
+    fn decode_symmetric() {
+        let jwk = br#"{"kty":"oct","alg":"C20P",
+            "k":"gIGCg4SFhoeIiYqLjI2Oj5CRkpOUlZaXmJmam5ydnp8"}"#;
+        let (sk, pk) = decode_private_key(&KeyAlg::ChaCha20Poly1305, jwk).unwrap();
+        assert_eq!(
+            hex::encode(&*sk),
+            "808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f"
+        );
+        assert_eq!(pk, None);
+        assert!(decode_private_key(&KeyAlg::Aes256Gcm, jwk).is_err());
+        assert!(decode_private_key(&KeyAlg::ChaCha20Poly1305, b"{}").is_err());
+    }
+}
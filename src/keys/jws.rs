@@ -0,0 +1,402 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+use super::types::{KeyAlg, KeyParams, SignatureFormat};
+use super::{decode_key_ident, sign_message, verify_signature};
+use crate::error::{Error, Result};
+
+/// Serialization formats for a JSON Web Signature
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JwsSerialization {
+    /// The JWS compact serialization
+    Compact,
+    /// The flattened JWS JSON serialization
+    Json,
+}
+
+impl JwsSerialization {
+    /// Get a reference to a string representing the `JwsSerialization`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Compact => "compact",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl Default for JwsSerialization {
+    fn default() -> Self {
+        Self::Compact
+    }
+}
+
+impl FromStr for JwsSerialization {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "compact" => Self::Compact,
+            "json" => Self::Json,
+            _ => return Err(err_msg!(Input, "Unsupported JWS serialization")),
+        })
+    }
+}
+
+impl Display for JwsSerialization {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Get the JWS algorithm identifier for a signing key algorithm
+fn jws_alg(alg: &KeyAlg) -> Result<&'static str> {
+    match alg {
+        KeyAlg::ED25519 => Ok("EdDSA"),
+        KeyAlg::Secp256k1 => Ok("ES256K"),
+        KeyAlg::P256 => Ok("ES256"),
+        _ => Err(err_msg!(Unsupported, "Unsupported key algorithm for JWS")),
+    }
+}
+
+/// Produce a JWS using the private key of a keypair
+///
+/// The `alg` header is determined by the key algorithm, and the `kid` header
+/// defaults to the key identifier. Setting the `b64` header to `false`
+/// produces an unencoded payload, in which case `b64` is added to the `crit`
+/// header.
+pub(crate) fn sign_jws(
+    key_ident: &str,
+    params: &KeyParams,
+    payload: &[u8],
+    headers: Option<&Map<String, Value>>,
+    serialization: JwsSerialization,
+    detached: bool,
+) -> Result<String> {
+    let alg = jws_alg(&params.alg)?;
+    let mut protected = headers.cloned().unwrap_or_default();
+    match protected.get("alg") {
+        None => (),
+        Some(Value::String(a)) if a == alg => (),
+        _ => {
+            return Err(err_msg!(
+                Input,
+                "JWS algorithm does not match the signing key"
+            ))
+        }
+    }
+    protected.insert("alg".to_string(), alg.into());
+    protected.entry("kid").or_insert_with(|| key_ident.into());
+    if !payload_encoded(&protected)? {
+        match protected
+            .entry("crit")
+            .or_insert_with(|| Value::Array(vec![]))
+        {
+            Value::Array(crit) => {
+                if !crit.iter().any(|c| c == "b64") {
+                    crit.push("b64".into());
+                }
+            }
+            _ => return Err(err_msg!(Input, "Invalid JWS crit header")),
+        }
+    }
+    sign_with_header(params, &protected, payload, serialization, detached)
+}
+
+fn sign_with_header(
+    params: &KeyParams,
+    protected: &Map<String, Value>,
+    payload: &[u8],
+    serialization: JwsSerialization,
+    detached: bool,
+) -> Result<String> {
+    let b64 = payload_encoded(protected)?;
+    let header = encode(
+        &serde_json::to_vec(protected).map_err(err_map!(Unexpected, "Error encoding header"))?,
+    );
+    let payload = if b64 {
+        encode(payload).into_bytes()
+    } else {
+        payload.to_vec()
+    };
+    let signature = encode(&sign_message(
+        params,
+        &signing_input(&header, &payload),
+        SignatureFormat::Raw,
+    )?);
+
+    let payload = if detached {
+        None
+    } else {
+        let payload = String::from_utf8(payload)
+            .map_err(|_| err_msg!(Input, "Unencoded JWS payload must be valid UTF-8"))?;
+        if serialization == JwsSerialization::Compact && payload.contains('.') {
+            return Err(err_msg!(
+                Input,
+                "Unencoded JWS payload cannot contain '.' in the compact serialization"
+            ));
+        }
+        Some(payload)
+    };
+
+    match serialization {
+        JwsSerialization::Compact => Ok(format!(
+            "{}.{}.{}",
+            header,
+            payload.unwrap_or_default(),
+            signature
+        )),
+        JwsSerialization::Json => {
+            let mut jws = Map::new();
+            jws.insert("protected".to_string(), header.into());
+            if let Some(payload) = payload {
+                jws.insert("payload".to_string(), payload.into());
+            }
+            jws.insert("signature".to_string(), signature.into());
+            serde_json::to_string(&jws).map_err(err_map!(Unexpected, "Error encoding JWS"))
+        }
+    }
+}
+
+/// Verify a JWS in the compact or flattened JSON serialization
+///
+/// A detached payload must be provided as `detached_payload`. An empty
+/// payload in the compact serialization is treated as detached only when a
+/// detached payload is provided. The signature is always checked against
+/// `signer_vk`: the `kid` header is not trusted to select the key.
+pub fn verify_jws(jws: &str, detached_payload: Option<&[u8]>, signer_vk: &str) -> Result<bool> {
+    let jws = jws.trim();
+    let (header, payload, signature) = if jws.starts_with('{') {
+        let parts: Map<String, Value> =
+            serde_json::from_str(jws).map_err(err_map!(Input, "Invalid JWS"))?;
+        let field = |name: &str| match parts.get(name) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            _ => Err(err_msg!(Input, "Invalid JWS")),
+        };
+        let header = field("protected")?.ok_or_else(|| err_msg!(Input, "Invalid JWS"))?;
+        let signature = field("signature")?.ok_or_else(|| err_msg!(Input, "Invalid JWS"))?;
+        (header, field("payload")?, signature)
+    } else {
+        let (first, last) = match (jws.find('.'), jws.rfind('.')) {
+            (Some(first), Some(last)) if first < last => (first, last),
+            _ => return Err(err_msg!(Input, "Invalid JWS")),
+        };
+        let payload = &jws[(first + 1)..last];
+        // the payload section of a detached JWS is empty, but so is the
+        // section for an attached empty payload
+        let payload = if payload.is_empty() && detached_payload.is_some() {
+            None
+        } else {
+            Some(payload.to_string())
+        };
+        (
+            jws[..first].to_string(),
+            payload,
+            jws[(last + 1)..].to_string(),
+        )
+    };
+
+    let protected: Map<String, Value> =
+        serde_json::from_slice(&decode(&header)?).map_err(err_map!(Input, "Invalid JWS header"))?;
+    let b64 = payload_encoded(&protected)?;
+    match protected.get("crit") {
+        None => (),
+        Some(Value::Array(crit)) if crit.iter().all(|c| c == "b64") => (),
+        _ => return Err(err_msg!(Unsupported, "Unsupported critical JWS header")),
+    }
+    if !b64
+        && !protected
+            .get("crit")
+            .and_then(Value::as_array)
+            .map(|crit| crit.iter().any(|c| c == "b64"))
+            .unwrap_or(false)
+    {
+        return Err(err_msg!(Input, "The b64 header must be marked as critical"));
+    }
+
+    let payload = match (payload, detached_payload) {
+        (Some(payload), None) => payload.into_bytes(),
+        (None, Some(payload)) if b64 => encode(payload).into_bytes(),
+        (None, Some(payload)) => payload.to_vec(),
+        (Some(_), Some(_)) => return Err(err_msg!(Input, "JWS payload is not detached")),
+        (None, None) => return Err(err_msg!(Input, "Missing JWS payload")),
+    };
+
+    let key_alg = decode_key_ident(signer_vk)?
+        .map(|(alg, _)| alg)
+        .unwrap_or(KeyAlg::ED25519);
+    match protected.get("alg") {
+        Some(Value::String(alg)) if alg == jws_alg(&key_alg)? => (),
+        _ => {
+            return Err(err_msg!(
+                Input,
+                "JWS algorithm does not match the verification key"
+            ))
+        }
+    }
+
+    let signature = decode(&signature)?;
+    if signature.len() != 64 {
+        return Ok(false);
+    }
+    verify_signature(signer_vk, &signing_input(&header, &payload), &signature)
+}
+
+fn payload_encoded(protected: &Map<String, Value>) -> Result<bool> {
+    match protected.get("b64") {
+        None => Ok(true),
+        Some(Value::Bool(b64)) => Ok(*b64),
+        _ => Err(err_msg!(Input, "Invalid JWS b64 header")),
+    }
+}
+
+fn signing_input(header: &str, payload: &[u8]) -> Vec<u8> {
+    let mut input = Vec::with_capacity(header.len() + payload.len() + 1);
+    input.extend_from_slice(header.as_bytes());
+    input.push(b'.');
+    input.extend_from_slice(payload);
+    input
+}
+
+fn encode(value: &[u8]) -> String {
+    base64::encode_config(value, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+        .map_err(err_map!(Input, "Invalid base64url value in JWS"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{encode_key_ident, generate_keypair};
+    use super::*;
+
+    // RFC 8037 appendix A.4
+    const SEED: &'static str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+    const PAYLOAD: &'static [u8] = b"Example of Ed25519 signing";
+    const EXPECTED: &'static str = "eyJhbGciOiJFZERTQSJ9.RXhhbXBsZSBvZiBFZDI1NTE5IHNpZ25pbmc.\
+        hgyY0il_MGCjP0JzlnLWG1PPOt7-09PGcvMg3AIbQR6dWbhijcNR4ki4iylGjg5BhVsPt9g7sVvpAr_MuM0KAg";
+
+    fn ed25519_params() -> (String, KeyParams) {
+        let seed = hex::decode(SEED).unwrap();
+        let (pk, sk) = generate_keypair(&KeyAlg::ED25519, Some(&seed)).unwrap();
+        let ident = encode_key_ident(&KeyAlg::ED25519, &pk).unwrap();
+        (
+            ident,
+            KeyParams {
+                alg: KeyAlg::ED25519,
+                metadata: None,
+                reference: None,
+                pub_key: Some(pk),
+                prv_key: Some(sk),
+                exportable: true,
+            },
+        )
+    }
+
+    #[test]
+    fn eddsa_expected() {
+        let (ident, params) = ed25519_params();
+        let mut header = Map::new();
+        header.insert("alg".to_string(), "EdDSA".into());
+        let jws =
+            sign_with_header(&params, &header, PAYLOAD, JwsSerialization::Compact, false).unwrap();
+        assert_eq!(jws, EXPECTED);
+        assert!(verify_jws(&jws, None, &ident).unwrap());
+
+        // a detached payload is verified when provided separately
+        let (first, last) = (jws.find('.').unwrap(), jws.rfind('.').unwrap());
+        let detached = format!("{}.{}", &jws[..first], &jws[last..]);
+        assert!(verify_jws(&detached, Some(PAYLOAD), &ident).unwrap());
+        assert!(!verify_jws(&detached, Some(b"other"), &ident).unwrap());
+        // without the detached payload the empty payload section is verified
+        assert!(!verify_jws(&detached, None, &ident).unwrap());
+    }
+
+    #[test]
+    fn empty_payload() {
+        let (ident, params) = ed25519_params();
+        let jws = sign_jws(&ident, &params, b"", None, JwsSerialization::Compact, false).unwrap();
+        assert!(jws.contains(".."));
+        // an empty attached payload is verified without a detached payload
+        assert!(verify_jws(&jws, None, &ident).unwrap());
+        // the empty payload section is also accepted as a detached payload
+        assert!(verify_jws(&jws, Some(b""), &ident).unwrap());
+        assert!(!verify_jws(&jws, Some(b"other"), &ident).unwrap());
+    }
+
+    #[test]
+    fn kid_not_trusted() {
+        let (ident, _) = ed25519_params();
+        let (pk, sk) = generate_keypair(&KeyAlg::ED25519, None).unwrap();
+        let other_ident = encode_key_ident(&KeyAlg::ED25519, &pk).unwrap();
+        let other = KeyParams {
+            alg: KeyAlg::ED25519,
+            metadata: None,
+            reference: None,
+            pub_key: Some(pk),
+            prv_key: Some(sk),
+            exportable: true,
+        };
+        // a JWS self-signed by another key does not verify against the expected key
+        let jws = sign_jws(
+            &other_ident,
+            &other,
+            PAYLOAD,
+            None,
+            JwsSerialization::Compact,
+            false,
+        )
+        .unwrap();
+        assert!(verify_jws(&jws, None, &other_ident).unwrap());
+        assert!(!verify_jws(&jws, None, &ident).unwrap());
+    }
+
+    #[test]
+    fn sign_json_unencoded() {
+        let (ident, params) = ed25519_params();
+        let mut headers = Map::new();
+        headers.insert("b64".to_string(), false.into());
+        let jws = sign_jws(
+            &ident,
+            &params,
+            b"$.02",
+            Some(&headers),
+            JwsSerialization::Json,
+            false,
+        )
+        .unwrap();
+        let parts: Map<String, Value> = serde_json::from_str(&jws).unwrap();
+        assert_eq!(parts["payload"], "$.02");
+        let protected: Map<String, Value> =
+            serde_json::from_slice(&decode(parts["protected"].as_str().unwrap()).unwrap()).unwrap();
+        assert_eq!(protected["alg"], "EdDSA");
+        assert_eq!(protected["kid"], ident.as_str());
+        assert_eq!(protected["crit"], serde_json::json!(["b64"]));
+        assert!(verify_jws(&jws, None, &ident).unwrap());
+
+        // the unencoded payload cannot be represented in the compact serialization
+        assert!(sign_jws(
+            &ident,
+            &params,
+            b"$.02",
+            Some(&headers),
+            JwsSerialization::Compact,
+            false,
+        )
+        .is_err());
+        let detached = sign_jws(
+            &ident,
+            &params,
+            b"$.02",
+            Some(&headers),
+            JwsSerialization::Compact,
+            true,
+        )
+        .unwrap();
+        assert!(verify_jws(&detached, Some(b"$.02"), &ident).unwrap());
+    }
+}
@@ -8,6 +8,8 @@ use crate::options::Options;
 pub mod argon2;
 use self::argon2::{generate_salt, Level as Argon2Level, SALT_SIZE};
 
+pub mod concat;
+
 pub const METHOD_ARGON2I: &'static str = "argon2i";
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
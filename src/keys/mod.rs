@@ -1,8 +1,14 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use async_mutex::Mutex;
-use indy_utils::keys::{EncodedVerKey, PrivateKey};
+use hkdf::Hkdf;
+use indy_utils::{
+    base58,
+    keys::{EncodedVerKey, KeyType as IndyKeyAlg, PrivateKey, VerKey},
+};
+use sha2::Sha256;
 use zeroize::Zeroize;
 
 use super::error::Result;
@@ -12,46 +18,383 @@ use super::types::{EncEntryTag, EntryTag, ProfileId, SecretBytes};
 use self::store::StoreKey;
 use self::wrap::WrapKey;
 
+mod bls;
+
 pub mod encrypt;
 
+mod jwe;
+pub use self::jwe::JweEncryption;
+pub(crate) use self::jwe::{
+    pack_message as pack_message_v2, unpack_message as unpack_message_v2, JweKeyLookup,
+};
+
+mod jwk;
+
+mod jws;
+pub use self::jws::{verify_jws, JwsSerialization};
+
 pub mod kdf;
 
+mod pkcs8;
+
+mod secp256k1;
+
+mod secp256r1;
+
 pub mod store;
 
+mod symmetric;
+
 mod types;
-pub use self::types::{KeyAlg, KeyCategory, KeyEntry, KeyParams, PassKey};
+pub use self::types::{
+    KeyAgreementKdf, KeyAlg, KeyCategory, KeyEntry, KeyFormat, KeyParams, PassKey, SignatureFormat,
+};
 
 pub mod wrap;
 
+mod x25519;
+
 // #[cfg(target_os = "macos")]
 // mod keychain;
 
+/// The maximum length of a key derived by key agreement, matching the output
+/// limit of HKDF with SHA-256
+pub const MAX_DERIVED_KEY_LENGTH: usize = 255 * kdf::concat::HASH_SIZE;
+
 /// Derive the (public) verification key for a keypair
 pub fn derive_verkey(alg: KeyAlg, seed: &[u8]) -> Result<String> {
+    let (pub_key, _) = generate_keypair(&alg, Some(seed))?;
+    encode_key_ident(&alg, &pub_key)
+}
+
+/// Generate the public and private keys for a new keypair
+pub(crate) fn generate_keypair(
+    alg: &KeyAlg,
+    seed: Option<&[u8]>,
+) -> Result<(Vec<u8>, SecretBytes)> {
+    match alg {
+        KeyAlg::ED25519 => {
+            let sk = match seed {
+                None => PrivateKey::generate(Some(IndyKeyAlg::ED25519)),
+                Some(s) => PrivateKey::from_seed(s),
+            }
+            .map_err(err_map!(Unexpected, "Error generating keypair"))?;
+            let pk = sk
+                .public_key()
+                .map_err(err_map!(Unexpected, "Error generating public key"))?;
+            Ok((pk.key_bytes(), sk.key_bytes().into()))
+        }
+        KeyAlg::X25519 => x25519::generate_keypair(seed),
+        KeyAlg::Secp256k1 => secp256k1::generate_keypair(seed),
+        KeyAlg::P256 => secp256r1::generate_keypair(seed),
+        KeyAlg::Bls12381G1 => bls::generate_g1_keypair(seed),
+        KeyAlg::Bls12381G2 => bls::generate_g2_keypair(seed),
+        _ => Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+    }
+}
+
+/// Generate a new symmetric key
+pub(crate) fn generate_secret_key(alg: &KeyAlg) -> Result<SecretBytes> {
+    symmetric::generate_key(alg)
+}
+
+/// Import an existing private or secret key, returning the public key (for
+/// asymmetric algorithms) and the normalized private key
+///
+/// When the encoded key includes a public key, it must correspond to the
+/// private key.
+pub(crate) fn import_key(
+    alg: &KeyAlg,
+    data: &[u8],
+    format: KeyFormat,
+) -> Result<(Option<Vec<u8>>, SecretBytes)> {
+    let (secret, expected_pk) = match format {
+        KeyFormat::Raw => (SecretBytes::from(data), None),
+        KeyFormat::Jwk => jwk::decode_private_key(alg, data)?,
+        KeyFormat::Pkcs8 => pkcs8::decode_private_key(alg, data)?,
+    };
+    let (pub_key, prv_key) = match alg {
+        KeyAlg::ED25519 => {
+            // accept either the 32 byte seed or the 64 byte expanded secret key
+            if secret.len() != 32 && secret.len() != 64 {
+                return Err(err_msg!(Input, "Invalid Ed25519 private key"));
+            }
+            let (pk, sk) = generate_keypair(alg, Some(&secret[..32]))?;
+            if secret.len() == 64 && secret[32..] != pk[..] {
+                return Err(err_msg!(Input, "Public key does not match private key"));
+            }
+            (Some(pk), sk)
+        }
+        KeyAlg::X25519 => x25519::import_keypair(&secret).map(|(pk, sk)| (Some(pk), sk))?,
+        KeyAlg::Secp256k1 | KeyAlg::P256 => {
+            if secret.len() != 32 {
+                return Err(err_msg!(Input, "Invalid {} private key", alg));
+            }
+            generate_keypair(alg, Some(&secret)).map(|(pk, sk)| (Some(pk), sk))?
+        }
+        KeyAlg::Bls12381G1 => bls::import_g1_keypair(&secret).map(|(pk, sk)| (Some(pk), sk))?,
+        KeyAlg::Bls12381G2 => bls::import_g2_keypair(&secret).map(|(pk, sk)| (Some(pk), sk))?,
+        KeyAlg::ChaCha20Poly1305 | KeyAlg::XChaCha20Poly1305 | KeyAlg::Aes256Gcm => {
+            (None, symmetric::import_key(alg, &secret)?)
+        }
+        KeyAlg::Other(_) => return Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+    };
+    if let (Some(expected), Some(pub_key)) = (expected_pk, pub_key.as_ref()) {
+        if normalize_public_key(alg, expected)? != *pub_key {
+            return Err(err_msg!(Input, "Public key does not match private key"));
+        }
+    }
+    Ok((pub_key, prv_key))
+}
+
+/// Derive a stable identifier for an imported secret key
+///
+/// The identifier is derived from the key itself using HKDF, so that a key
+/// imported twice is detected as a duplicate without revealing the key.
+pub(crate) fn secret_key_ident(alg: &KeyAlg, key: &[u8]) -> Result<String> {
+    let mut info = b"askar:secret-key-ident:".to_vec();
+    info.extend_from_slice(alg.as_str().as_bytes());
+    let mut ident = [0u8; 16];
+    Hkdf::<Sha256>::new(None, key)
+        .expand(&info, &mut ident)
+        .map_err(|_| err_msg!(Unexpected, "Error deriving key identifier"))?;
+    Ok(uuid::Builder::from_bytes(ident)
+        .set_variant(uuid::Variant::RFC4122)
+        .set_version(uuid::Version::Sha1)
+        .build()
+        .to_string())
+}
+
+/// Convert the private key of an existing keypair for use with another algorithm
+pub(crate) fn convert_keypair(params: &KeyParams, alg: &KeyAlg) -> Result<(Vec<u8>, SecretBytes)> {
+    match (&params.alg, alg, &params.prv_key) {
+        (_, _, None) => Err(err_msg!(Input, "Undefined private key")),
+        (KeyAlg::ED25519, KeyAlg::X25519, Some(prv_key)) => {
+            x25519::convert_ed25519_keypair(prv_key)
+        }
+        _ => Err(err_msg!(Unsupported, "Unsupported key conversion")),
+    }
+}
+
+/// Perform an ECDH key exchange between a private key and a peer public key
+/// of the same algorithm
+pub(crate) fn key_exchange(alg: &KeyAlg, prv_key: &[u8], pub_key: &[u8]) -> Result<SecretBytes> {
+    match alg {
+        KeyAlg::X25519 => x25519::diffie_hellman(prv_key, pub_key),
+        KeyAlg::P256 => secp256r1::diffie_hellman(prv_key, pub_key),
+        _ => Err(err_msg!(
+            Unsupported,
+            "Unsupported key algorithm for key exchange"
+        )),
+    }
+}
+
+/// Derive a shared key from the private key of a keypair and a peer public key
+///
+/// Only X25519 and P-256 keypairs are supported. Ed25519 keypairs are
+/// converted to X25519 for the key exchange, in which case the peer may also
+/// be identified by an Ed25519 verkey. The derived key length must be between
+/// 1 and [`MAX_DERIVED_KEY_LENGTH`] bytes.
+pub(crate) fn key_agreement(
+    params: &KeyParams,
+    peer_ident: &str,
+    kdf: &KeyAgreementKdf,
+) -> Result<SecretBytes> {
+    let converted;
+    let (alg, prv_key) = match (&params.alg, &params.prv_key) {
+        (_, None) => return Err(err_msg!(Input, "Undefined private key")),
+        (KeyAlg::ED25519, Some(_)) => {
+            converted = convert_keypair(params, &KeyAlg::X25519)?.1;
+            (KeyAlg::X25519, &converted)
+        }
+        (alg, Some(prv_key)) => (alg.clone(), prv_key),
+    };
+    let peer_key = match decode_key_ident(peer_ident)? {
+        Some((peer_alg, pub_key)) if peer_alg == alg => pub_key,
+        None if alg == KeyAlg::X25519 => {
+            let vk = EncodedVerKey::from_str(peer_ident)
+                .and_then(|k| k.decode())
+                .map_err(err_map!(Input, "Invalid key"))?;
+            x25519::convert_ed25519_public_key(&vk.key_bytes())?
+        }
+        _ => {
+            return Err(err_msg!(
+                Input,
+                "Incompatible key algorithm for key agreement"
+            ))
+        }
+    };
+    let key_len = match kdf {
+        KeyAgreementKdf::ConcatKdf { key_len, .. } | KeyAgreementKdf::Hkdf { key_len, .. } => {
+            *key_len
+        }
+    };
+    if key_len == 0 || key_len > MAX_DERIVED_KEY_LENGTH {
+        return Err(err_msg!(Input, "Invalid derived key length"));
+    }
+    let z = key_exchange(&alg, prv_key, &peer_key)?;
+    match kdf {
+        KeyAgreementKdf::ConcatKdf {
+            alg_id,
+            apu,
+            apv,
+            key_len,
+        } => {
+            let info = kdf::concat::jwa_other_info(alg_id, apu, apv, *key_len, None);
+            Ok(kdf::concat::derive_key(&z, &info, *key_len))
+        }
+        KeyAgreementKdf::Hkdf {
+            salt,
+            info,
+            key_len,
+        } => {
+            let mut okm = vec![0u8; *key_len];
+            Hkdf::<Sha256>::new(Some(salt.as_slice()), &z)
+                .expand(info, &mut okm)
+                .map_err(|_| err_msg!(Input, "Invalid derived key length"))?;
+            Ok(SecretBytes::from(okm))
+        }
+    }
+}
+
+/// Encode the identifier of a key entry from its public key
+///
+/// Ed25519 keys are identified by their long-form verkey, while other
+/// algorithms use the base58-encoded public key with an algorithm suffix.
+pub(crate) fn encode_key_ident(alg: &KeyAlg, pub_key: &[u8]) -> Result<String> {
     match alg {
-        KeyAlg::ED25519 => (),
-        _ => return Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+        KeyAlg::ED25519 => Ok(VerKey::new(pub_key, Some(IndyKeyAlg::ED25519))
+            .as_base58()
+            .map_err(err_map!(Unexpected, "Error encoding public key"))?
+            .long_form()),
+        KeyAlg::Other(_) => Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+        alg => Ok(format!("{}:{}", base58::encode(pub_key), alg)),
     }
+}
 
-    let sk =
-        PrivateKey::from_seed(seed).map_err(err_map!(Unexpected, "Error generating keypair"))?;
-    let pk = sk
-        .public_key()
-        .map_err(err_map!(Unexpected, "Error generating public key"))?
-        .as_base58()
-        .map_err(err_map!(Unexpected, "Error encoding public key"))?
-        .long_form();
-    Ok(pk)
+/// Decode a key identifier with an algorithm suffix, returning the
+/// algorithm and normalized public key
+///
+/// Identifiers for Ed25519 keys are handled as indy verkeys and are not
+/// decoded here.
+fn decode_key_ident(ident: &str) -> Result<Option<(KeyAlg, Vec<u8>)>> {
+    let mut parts = ident.splitn(2, ':');
+    let key = parts.next().unwrap_or_default();
+    let alg = match parts.next().map(|alg| KeyAlg::from_str(alg).unwrap()) {
+        None | Some(KeyAlg::ED25519) | Some(KeyAlg::Other(_)) => return Ok(None),
+        Some(alg) => alg,
+    };
+    let pub_key = base58::decode(key).map_err(err_map!(Input, "Invalid key"))?;
+    let pub_key = normalize_public_key(&alg, pub_key)?;
+    Ok(Some((alg, pub_key)))
+}
+
+/// Validate a public key, converting it to the form used in key identifiers
+fn normalize_public_key(alg: &KeyAlg, pub_key: Vec<u8>) -> Result<Vec<u8>> {
+    match alg {
+        KeyAlg::ED25519 if pub_key.len() == 32 => Ok(pub_key),
+        KeyAlg::ED25519 => Err(err_msg!(Input, "Invalid Ed25519 public key")),
+        KeyAlg::X25519 => {
+            x25519::validate_public_key(&pub_key)?;
+            Ok(pub_key)
+        }
+        KeyAlg::Secp256k1 => secp256k1::compress_public_key(&pub_key),
+        KeyAlg::P256 => secp256r1::compress_public_key(&pub_key),
+        KeyAlg::Bls12381G1 => {
+            bls::validate_g1_public_key(&pub_key)?;
+            Ok(pub_key)
+        }
+        KeyAlg::Bls12381G2 => {
+            bls::validate_g2_public_key(&pub_key)?;
+            Ok(pub_key)
+        }
+        _ => Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+    }
+}
+
+/// Normalize a key identifier to the form used for the stored key entry
+pub(crate) fn normalize_key_ident(ident: &str) -> Result<String> {
+    if let Some((alg, pub_key)) = decode_key_ident(ident)? {
+        encode_key_ident(&alg, &pub_key)
+    } else {
+        Ok(EncodedVerKey::from_str(&ident)
+            .and_then(|k| k.as_base58())
+            .map_err(err_map!("Invalid key"))?
+            .long_form())
+    }
+}
+
+/// Produce a JWS using the private key of a keypair
+pub(crate) fn sign_jws(
+    key_ident: &str,
+    params: &KeyParams,
+    payload: &[u8],
+    headers: Option<&serde_json::Map<String, serde_json::Value>>,
+    serialization: JwsSerialization,
+    detached: bool,
+) -> Result<String> {
+    match &params.prv_key {
+        Some(_) => jws::sign_jws(key_ident, params, payload, headers, serialization, detached),
+        None => Err(err_msg!(Input, "Undefined private key")),
+    }
+}
+
+/// Sign a message using the private key of a keypair
+pub(crate) fn sign_message(
+    params: &KeyParams,
+    data: &[u8],
+    format: SignatureFormat,
+) -> Result<Vec<u8>> {
+    match (&params.alg, &params.prv_key, format) {
+        (_, None, _) => Err(err_msg!(Input, "Undefined private key")),
+        (KeyAlg::ED25519, Some(prv_key), SignatureFormat::Raw) => {
+            PrivateKey::new(prv_key, Some(IndyKeyAlg::ED25519))
+                .sign(&data)
+                .map_err(|e| err_msg!(Unexpected, "Signature error: {}", e))
+        }
+        (KeyAlg::ED25519, _, _) => Err(err_msg!(Unsupported, "Unsupported signature format")),
+        (KeyAlg::Secp256k1, Some(prv_key), format) => {
+            secp256k1::sign_message(prv_key, data, format)
+        }
+        (KeyAlg::P256, Some(prv_key), format) => secp256r1::sign_message(prv_key, data, format),
+        _ => Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+    }
+}
+
+/// Encrypt a message using a symmetric key, producing the combined nonce,
+/// ciphertext and authentication tag
+pub(crate) fn encrypt_message(params: &KeyParams, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    match &params.prv_key {
+        Some(key) => symmetric::encrypt(&params.alg, key, None, data, aad),
+        None => Err(err_msg!(Input, "Undefined secret key")),
+    }
+}
+
+/// Decrypt a message produced by [`encrypt_message`]
+pub(crate) fn decrypt_message(params: &KeyParams, data: &[u8], aad: &[u8]) -> Result<SecretBytes> {
+    match &params.prv_key {
+        Some(key) => symmetric::decrypt(&params.alg, key, data, aad),
+        None => Err(err_msg!(Input, "Undefined secret key")),
+    }
 }
 
 /// Verify that a message signature is consistent with the signer's key
 pub fn verify_signature(signer_vk: &str, data: &[u8], signature: &[u8]) -> Result<bool> {
-    let vk = EncodedVerKey::from_str(&signer_vk).map_err(err_map!("Invalid verkey"))?;
-    Ok(vk
-        .decode()
-        .map_err(err_map!("Unsupported verkey"))?
-        .verify_signature(&data, &signature)
-        .unwrap_or(false))
+    match decode_key_ident(signer_vk)? {
+        Some((KeyAlg::Secp256k1, pub_key)) => {
+            secp256k1::verify_signature(&pub_key, data, signature)
+        }
+        Some((KeyAlg::P256, pub_key)) => secp256r1::verify_signature(&pub_key, data, signature),
+        Some(_) => Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+        None => {
+            let vk = EncodedVerKey::from_str(&signer_vk).map_err(err_map!("Invalid verkey"))?;
+            Ok(vk
+                .decode()
+                .map_err(err_map!("Unsupported verkey"))?
+                .verify_signature(&data, &signature)
+                .unwrap_or(false))
+        }
+    }
 }
 
 #[derive(Debug)]
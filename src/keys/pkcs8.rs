@@ -0,0 +1,217 @@
+use super::types::KeyAlg;
+use crate::error::{Error, Result};
+use crate::types::SecretBytes;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_CONTEXT_0: u8 = 0xa0;
+const TAG_CONTEXT_1: u8 = 0xa1;
+// the implicitly tagged public key of a OneAsymmetricKey (RFC 5958)
+const TAG_PUBLIC_KEY: u8 = 0x81;
+
+const OID_ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+const OID_X25519: &[u8] = &[0x2b, 0x65, 0x6e];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_SECP256K1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x0a];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+
+/// Decode a DER-encoded PKCS#8 private key, returning the raw private key
+/// and the public key if one is included
+pub(crate) fn decode_private_key(
+    alg: &KeyAlg,
+    der: &[u8],
+) -> Result<(SecretBytes, Option<Vec<u8>>)> {
+    let mut info = DerReader(DerReader::read_single(der, TAG_SEQUENCE)?);
+    match info.read_tag(TAG_INTEGER)? {
+        [0] | [1] => (),
+        _ => return Err(invalid_key()),
+    }
+    let mut alg_id = DerReader(info.read_tag(TAG_SEQUENCE)?);
+    let oid = alg_id.read_tag(TAG_OID)?;
+    let private_key = info.read_tag(TAG_OCTET_STRING)?;
+    if info.peek_tag() == Some(TAG_CONTEXT_0) {
+        // skip attributes
+        info.read_tag(TAG_CONTEXT_0)?;
+    }
+    let mut public_key = if info.peek_tag() == Some(TAG_PUBLIC_KEY) {
+        Some(bit_string(info.read_tag(TAG_PUBLIC_KEY)?)?)
+    } else {
+        None
+    };
+    info.finish()?;
+
+    let secret = match alg {
+        KeyAlg::ED25519 | KeyAlg::X25519 => {
+            let expected = if *alg == KeyAlg::ED25519 {
+                OID_ED25519
+            } else {
+                OID_X25519
+            };
+            // the algorithm identifier must not include parameters
+            if oid != expected || !alg_id.0.is_empty() {
+                return Err(err_msg!(Input, "Private key algorithm mismatch"));
+            }
+            DerReader::read_single(private_key, TAG_OCTET_STRING)?
+        }
+        KeyAlg::Secp256k1 | KeyAlg::P256 => {
+            let curve = if *alg == KeyAlg::Secp256k1 {
+                OID_SECP256K1
+            } else {
+                OID_P256
+            };
+            if oid != OID_EC_PUBLIC_KEY || alg_id.read_tag(TAG_OID)? != curve {
+                return Err(err_msg!(Input, "Private key algorithm mismatch"));
+            }
+            alg_id.finish()?;
+            // ECPrivateKey structure defined by RFC 5915
+            let mut ec_key = DerReader(DerReader::read_single(private_key, TAG_SEQUENCE)?);
+            match ec_key.read_tag(TAG_INTEGER)? {
+                [1] => (),
+                _ => return Err(invalid_key()),
+            }
+            let secret = ec_key.read_tag(TAG_OCTET_STRING)?;
+            if ec_key.peek_tag() == Some(TAG_CONTEXT_0) {
+                let params = ec_key.read_tag(TAG_CONTEXT_0)?;
+                if DerReader::read_single(params, TAG_OID)? != curve {
+                    return Err(err_msg!(Input, "Private key algorithm mismatch"));
+                }
+            }
+            if ec_key.peek_tag() == Some(TAG_CONTEXT_1) {
+                let pk = DerReader::read_single(ec_key.read_tag(TAG_CONTEXT_1)?, TAG_BIT_STRING)?;
+                public_key.replace(bit_string(pk)?);
+            }
+            ec_key.finish()?;
+            secret
+        }
+        _ => return Err(err_msg!(Unsupported, "Unsupported key format")),
+    };
+    Ok((SecretBytes::from(secret), public_key))
+}
+
+fn invalid_key() -> Error {
+    err_msg!(Input, "Invalid PKCS#8 private key")
+}
+
+fn bit_string(value: &[u8]) -> Result<Vec<u8>> {
+    match value.split_first() {
+        // only octet-aligned values are supported
+        Some((0, bits)) => Ok(bits.to_vec()),
+        _ => Err(invalid_key()),
+    }
+}
+
+/// A minimal reader for DER-encoded values
+struct DerReader<'a>(&'a [u8]);
+
+impl<'a> DerReader<'a> {
+    /// Read a value which must make up the entirety of the input
+    fn read_single(data: &'a [u8], tag: u8) -> Result<&'a [u8]> {
+        let mut reader = DerReader(data);
+        let value = reader.read_tag(tag)?;
+        reader.finish()?;
+        Ok(value)
+    }
+
+    /// Check that no trailing data remains
+    fn finish(&self) -> Result<()> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid_key())
+        }
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.0.first().cloned()
+    }
+
+    fn read_tag(&mut self, tag: u8) -> Result<&'a [u8]> {
+        let (found, value) = self.read()?;
+        if found == tag {
+            Ok(value)
+        } else {
+            Err(invalid_key())
+        }
+    }
+
+    fn read(&mut self) -> Result<(u8, &'a [u8])> {
+        let (tag, rest) = self.0.split_first().ok_or_else(invalid_key)?;
+        let (first, mut rest) = rest.split_first().ok_or_else(invalid_key)?;
+        let len = if *first < 0x80 {
+            *first as usize
+        } else {
+            let count = (*first & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return Err(invalid_key());
+            }
+            let (len_bytes, remain) = rest.split_at(count);
+            rest = remain;
+            let len = len_bytes
+                .iter()
+                .fold(0usize, |len, b| (len << 8) | *b as usize);
+            // DER requires the minimal length encoding
+            if len < 0x80 || len_bytes[0] == 0 {
+                return Err(invalid_key());
+            }
+            len
+        };
+        if rest.len() < len {
+            return Err(invalid_key());
+        }
+        let (value, remain) = rest.split_at(len);
+        self.0 = remain;
+        Ok((*tag, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_ed25519() {
+        // RFC 8410 section 10.3
+        let der = hex::decode(
+            "302e020100300506032b657004220420d4ee72dbf913584ad5b6d8f1f769f8ad\
+             3afe7c28cbf1d4fbe097a88f44755842",
+        )
+        .unwrap();
+        let (sk, pk) = decode_private_key(&KeyAlg::ED25519, &der).unwrap();
+        assert_eq!(
+            hex::encode(&*sk),
+            "d4ee72dbf913584ad5b6d8f1f769f8ad3afe7c28cbf1d4fbe097a88f44755842"
+        );
+        assert_eq!(pk, None);
+        assert!(decode_private_key(&KeyAlg::X25519, &der).is_err());
+        assert!(decode_private_key(&KeyAlg::ED25519, &der[..der.len() - 1]).is_err());
+        let mut trailing = der.clone();
+        trailing.push(0);
+        assert!(decode_private_key(&KeyAlg::ED25519, &trailing).is_err());
+    }
+
+    #[test]
+    fn decode_p256() {
+        let der = hex::decode(
+            "308187020100301306072a8648ce3d020106082a8648ce3d030107046d306b02\
+             010104208e9b109e719098bf980487df1f5d77e9cb29606ebed2263b5f57c213\
+             df84f4b2a144034200047fcdce2770f6c45d4183cbee6fdb4b7b580733357be9\
+             ef13bacf6e3c7bd15445c7f144cd1bbd9b7e872cdfedb9eeb9f4b3695d6ea90b\
+             24ad8a4623288588e5ad",
+        )
+        .unwrap();
+        let (sk, pk) = decode_private_key(&KeyAlg::P256, &der).unwrap();
+        assert_eq!(
+            hex::encode(&*sk),
+            "8e9b109e719098bf980487df1f5d77e9cb29606ebed2263b5f57c213df84f4b2"
+        );
+        assert_eq!(
+            hex::encode(pk.unwrap()),
+            "047fcdce2770f6c45d4183cbee6fdb4b7b580733357be9ef13bacf6e3c7bd15445\
+             c7f144cd1bbd9b7e872cdfedb9eeb9f4b3695d6ea90b24ad8a4623288588e5ad"
+        );
+        assert!(decode_private_key(&KeyAlg::Secp256k1, &der).is_err());
+    }
+}
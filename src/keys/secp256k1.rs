@@ -0,0 +1,134 @@
+use std::convert::TryFrom;
+
+use k256::{
+    ecdsa::{
+        signature::{Signer, Verifier},
+        Signature, SigningKey, VerifyingKey,
+    },
+    EncodedPoint,
+};
+use rand::rngs::OsRng;
+
+use super::types::SignatureFormat;
+use crate::error::Result;
+use crate::types::SecretBytes;
+
+/// The length of a secp256k1 private key
+pub const SECRET_KEY_LENGTH: usize = 32;
+
+/// The length of a raw ECDSA signature
+pub const SIGNATURE_LENGTH: usize = 64;
+
+/// Generate a new secp256k1 keypair, returning the compressed public key
+/// and the private key
+///
+/// A seed is used directly as the private scalar, and must be within the
+/// range of the curve order.
+pub(crate) fn generate_keypair(seed: Option<&[u8]>) -> Result<(Vec<u8>, SecretBytes)> {
+    let sk = match seed {
+        None => SigningKey::random(OsRng),
+        Some(seed) if seed.len() == SECRET_KEY_LENGTH => SigningKey::from_bytes(seed)
+            .map_err(|_| err_msg!(Input, "Invalid seed for secp256k1 key"))?,
+        Some(_) => return Err(err_msg!(Input, "Invalid length for seed")),
+    };
+    let pk = sk.verify_key().to_bytes().to_vec();
+    Ok((pk, SecretBytes::from(sk.to_bytes().as_slice())))
+}
+
+/// Normalize a SEC1-encoded public key to its compressed form
+pub(crate) fn compress_public_key(pub_key: &[u8]) -> Result<Vec<u8>> {
+    let vk = VerifyingKey::from_sec1_bytes(pub_key)
+        .map_err(|_| err_msg!(Input, "Invalid secp256k1 public key"))?;
+    Ok(vk.to_bytes().to_vec())
+}
+
+/// Convert a SEC1-encoded public key to its uncompressed form
+pub(crate) fn expand_public_key(pub_key: &[u8]) -> Result<Vec<u8>> {
+    let vk = VerifyingKey::from_sec1_bytes(pub_key)
+        .map_err(|_| err_msg!(Input, "Invalid secp256k1 public key"))?;
+    let point = EncodedPoint::from(&vk)
+        .decompress()
+        .ok_or_else(|| err_msg!(Input, "Invalid secp256k1 public key"))?;
+    Ok(point.as_bytes().to_vec())
+}
+
+/// Produce an ES256K signature (deterministic ECDSA with SHA-256 and low-S
+/// normalization)
+pub(crate) fn sign_message(
+    prv_key: &[u8],
+    message: &[u8],
+    format: SignatureFormat,
+) -> Result<Vec<u8>> {
+    let sk = SigningKey::from_bytes(prv_key)
+        .map_err(|_| err_msg!(Input, "Invalid secp256k1 private key"))?;
+    let sig: Signature = sk
+        .try_sign(message)
+        .map_err(|e| err_msg!(Unexpected, "Signature error: {}", e))?;
+    Ok(match format {
+        SignatureFormat::Raw => sig.as_ref().to_vec(),
+        SignatureFormat::Der => sig.to_asn1().as_bytes().to_vec(),
+    })
+}
+
+/// Verify an ES256K signature against a SEC1-encoded public key
+///
+/// The signature may be provided as either the concatenation of `r` and `s`
+/// or in DER format.
+pub(crate) fn verify_signature(pub_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool> {
+    let vk = VerifyingKey::from_sec1_bytes(pub_key)
+        .map_err(|_| err_msg!(Input, "Invalid secp256k1 public key"))?;
+    let sig = if signature.len() == SIGNATURE_LENGTH {
+        Signature::try_from(signature)
+    } else {
+        Signature::from_asn1(signature)
+    };
+    Ok(match sig {
+        Ok(sig) => vk.verify(message, &sig).is_ok(),
+        Err(_) => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET_KEY: &'static str =
+        "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+
+    #[test]
+    fn sign_expected() {
+        let seed = hex::decode(SECRET_KEY).unwrap();
+        let (pk, sk) = generate_keypair(Some(&seed)).unwrap();
+        assert_eq!(
+            hex::encode(&pk),
+            "032c8c31fc9f990c6b55e3865a184a4ce50e09481f2eaeb3e60ec1cea13a6ae645"
+        );
+        assert_eq!(&*sk, seed.as_slice());
+        // deterministic (RFC 6979) signature with a normalized `s` value
+        let sig = sign_message(&sk, b"sample", SignatureFormat::Raw).unwrap();
+        assert_eq!(
+            hex::encode(&sig),
+            "432310e32cb80eb6503a26ce83cc165c783b870845fb8aad6d970889fcd7a6c8\
+             530128b6b81c548874a6305d93ed071ca6e05074d85863d4056ce89b02bfab69"
+        );
+        assert!(verify_signature(&pk, b"sample", &sig).unwrap());
+        assert!(!verify_signature(&pk, b"other", &sig).unwrap());
+        assert!(!verify_signature(&pk, b"sample", b"bad sig").unwrap());
+
+        let der_sig = sign_message(&sk, b"sample", SignatureFormat::Der).unwrap();
+        assert!(verify_signature(&pk, b"sample", &der_sig).unwrap());
+    }
+
+    #[test]
+    fn compress_public_key_round_trip() {
+        let (pk, _) = generate_keypair(None).unwrap();
+        let point = VerifyingKey::from_sec1_bytes(&pk).unwrap();
+        let uncompressed = EncodedPoint::from(&point).decompress().unwrap();
+        assert_eq!(uncompressed.as_bytes().len(), 65);
+        assert_eq!(compress_public_key(uncompressed.as_bytes()).unwrap(), pk);
+        assert_eq!(
+            expand_public_key(&pk).unwrap(),
+            uncompressed.as_bytes().to_vec()
+        );
+    }
+}
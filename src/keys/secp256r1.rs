@@ -0,0 +1,179 @@
+use std::convert::TryFrom;
+
+use p256::{
+    ecdh,
+    ecdsa::{
+        signature::{Signer, Verifier},
+        Signature, SigningKey, VerifyingKey,
+    },
+    PublicKey, SecretKey,
+};
+use rand::rngs::OsRng;
+
+use super::types::SignatureFormat;
+use crate::error::Result;
+use crate::types::SecretBytes;
+
+/// The length of a P-256 private key
+pub const SECRET_KEY_LENGTH: usize = 32;
+
+/// The length of a raw ECDSA signature
+pub const SIGNATURE_LENGTH: usize = 64;
+
+/// Generate a new P-256 keypair, returning the compressed public key and
+/// the private key
+///
+/// A seed is used directly as the private scalar, and must be within the
+/// range of the curve order.
+pub(crate) fn generate_keypair(seed: Option<&[u8]>) -> Result<(Vec<u8>, SecretBytes)> {
+    let sk = match seed {
+        None => SigningKey::random(OsRng),
+        Some(seed) if seed.len() == SECRET_KEY_LENGTH => SigningKey::from_bytes(seed)
+            .map_err(|_| err_msg!(Input, "Invalid seed for P-256 key"))?,
+        Some(_) => return Err(err_msg!(Input, "Invalid length for seed")),
+    };
+    let pk = sk.verify_key().to_encoded_point(true).as_bytes().to_vec();
+    Ok((pk, SecretBytes::from(sk.to_bytes().as_slice())))
+}
+
+/// Normalize a SEC1-encoded public key to its compressed form
+pub(crate) fn compress_public_key(pub_key: &[u8]) -> Result<Vec<u8>> {
+    let vk = VerifyingKey::from_sec1_bytes(pub_key)
+        .map_err(|_| err_msg!(Input, "Invalid P-256 public key"))?;
+    Ok(vk.to_encoded_point(true).as_bytes().to_vec())
+}
+
+/// Convert a SEC1-encoded public key to its uncompressed form
+pub(crate) fn expand_public_key(pub_key: &[u8]) -> Result<Vec<u8>> {
+    let vk = VerifyingKey::from_sec1_bytes(pub_key)
+        .map_err(|_| err_msg!(Input, "Invalid P-256 public key"))?;
+    Ok(vk.to_encoded_point(false).as_bytes().to_vec())
+}
+
+/// Perform an ECDH key exchange between a private key and a SEC1-encoded
+/// peer public key, returning the x-coordinate of the shared point
+pub(crate) fn diffie_hellman(prv_key: &[u8], pub_key: &[u8]) -> Result<SecretBytes> {
+    let sk =
+        SecretKey::from_bytes(prv_key).map_err(|_| err_msg!(Input, "Invalid P-256 private key"))?;
+    let pk = PublicKey::from_sec1_bytes(pub_key)
+        .map_err(|_| err_msg!(Input, "Invalid P-256 public key"))?;
+    let shared = ecdh::diffie_hellman(sk.secret_scalar(), pk.as_affine());
+    Ok(SecretBytes::from(shared.as_bytes().as_slice()))
+}
+
+/// Produce an ES256 signature (deterministic ECDSA with SHA-256)
+pub(crate) fn sign_message(
+    prv_key: &[u8],
+    message: &[u8],
+    format: SignatureFormat,
+) -> Result<Vec<u8>> {
+    let sk = SigningKey::from_bytes(prv_key)
+        .map_err(|_| err_msg!(Input, "Invalid P-256 private key"))?;
+    let sig: Signature = sk
+        .try_sign(message)
+        .map_err(|e| err_msg!(Unexpected, "Signature error: {}", e))?;
+    Ok(match format {
+        SignatureFormat::Raw => sig.as_ref().to_vec(),
+        SignatureFormat::Der => sig.to_asn1().as_bytes().to_vec(),
+    })
+}
+
+/// Verify an ES256 signature against a SEC1-encoded public key
+///
+/// The signature may be provided as either the concatenation of `r` and `s`
+/// or in DER format.
+pub(crate) fn verify_signature(pub_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool> {
+    let vk = VerifyingKey::from_sec1_bytes(pub_key)
+        .map_err(|_| err_msg!(Input, "Invalid P-256 public key"))?;
+    let sig = if signature.len() == SIGNATURE_LENGTH {
+        Signature::try_from(signature)
+    } else {
+        Signature::from_asn1(signature)
+    };
+    Ok(match sig {
+        Ok(sig) => vk.verify(message, &sig).is_ok(),
+        Err(_) => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // test vectors from RFC 6979 appendix A.2.5
+    const SECRET_KEY: &'static str =
+        "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+    const PUBLIC_KEY: &'static str =
+        "0460fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6\
+         7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299";
+
+    #[test]
+    fn public_key_expected() {
+        let seed = hex::decode(SECRET_KEY).unwrap();
+        let (pk, sk) = generate_keypair(Some(&seed)).unwrap();
+        assert_eq!(
+            hex::encode(&pk),
+            "0360fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6"
+        );
+        assert_eq!(&*sk, seed.as_slice());
+        assert_eq!(
+            compress_public_key(&hex::decode(PUBLIC_KEY).unwrap()).unwrap(),
+            pk
+        );
+        assert_eq!(hex::encode(expand_public_key(&pk).unwrap()), PUBLIC_KEY);
+    }
+
+    #[test]
+    fn sign_expected() {
+        let sk = hex::decode(SECRET_KEY).unwrap();
+        let pk = hex::decode(PUBLIC_KEY).unwrap();
+        for (message, expected) in &[
+            (
+                &b"sample"[..],
+                "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716\
+                 f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+            ),
+            (
+                &b"test"[..],
+                "f1abb023518351cd71d881567b1ea663ed3efcf6c5132b354f28d3b0b7d38367\
+                 019f4113742a2b14bd25926b49c649155f267e60d3814b4c0cc84250e46f0083",
+            ),
+        ] {
+            let sig = sign_message(&sk, message, SignatureFormat::Raw).unwrap();
+            assert_eq!(hex::encode(&sig), *expected);
+            assert!(verify_signature(&pk, message, &sig).unwrap());
+            assert!(!verify_signature(&pk, b"other", &sig).unwrap());
+        }
+    }
+
+    #[test]
+    fn sign_der_expected() {
+        let sk = hex::decode(SECRET_KEY).unwrap();
+        let pk = hex::decode(PUBLIC_KEY).unwrap();
+        let sig = sign_message(&sk, b"sample", SignatureFormat::Der).unwrap();
+        assert_eq!(
+            hex::encode(&sig),
+            "3046022100efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716\
+             022100f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8"
+        );
+        assert!(verify_signature(&pk, b"sample", &sig).unwrap());
+        assert!(!verify_signature(&pk, b"sample", b"bad sig").unwrap());
+    }
+
+    #[test]
+    fn diffie_hellman_expected() {
+        // RFC 7518 appendix C
+        let sk = hex::decode("d3f3716913d4310a0026de741b3f18893afc8114f0c84682ba677e313a13988a")
+            .unwrap();
+        let pk = hex::decode(
+            "04c1e349cb61ec70248ce801034c3834e1b88ebe1161cb25af38741f785fcfc4c4\
+             7bc96708ef80952b53f8d2555fe72b841ed04588628b1d378a594939500ec9c9",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(&*diffie_hellman(&sk, &pk).unwrap()),
+            "9e56d91d817135d372834283bf84269cfb316ea3da806a48f6daa7798cfe90c4"
+        );
+        assert!(diffie_hellman(&sk, &pk[1..]).is_err());
+    }
+}
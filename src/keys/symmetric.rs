@@ -0,0 +1,413 @@
+use std::convert::TryFrom;
+
+use aead::{
+    generic_array::{typenum::Unsigned, GenericArray},
+    AeadInPlace, Buffer, NewAead,
+};
+use aes::Aes256;
+use aes_gcm::Aes256Gcm;
+use aes_kw::{KekAes128, KekAes256};
+use block_modes::{block_padding::Pkcs7, BlockMode, Cbc};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use hmac::{Hmac, Mac, NewMac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha512;
+
+use super::types::KeyAlg;
+use crate::error::Result;
+use crate::types::SecretBytes;
+
+/// The length of the combined MAC and encryption keys for A256CBC-HS512
+pub const CBC_HMAC_KEY_LENGTH: usize = 64;
+
+/// The length of the initialization vector for A256CBC-HS512
+pub const CBC_HMAC_IV_LENGTH: usize = 16;
+
+/// The length of the truncated authentication tag for A256CBC-HS512
+pub const CBC_HMAC_TAG_LENGTH: usize = 32;
+
+type Aes256Cbc = Cbc<Aes256, Pkcs7>;
+
+/// Generate a new random symmetric key for the given algorithm
+pub(crate) fn generate_key(alg: &KeyAlg) -> Result<SecretBytes> {
+    let mut key = vec![0u8; key_length(alg)?];
+    OsRng.fill_bytes(&mut key);
+    Ok(SecretBytes::from(key))
+}
+
+/// Load an existing symmetric key, checking its length for the algorithm
+pub(crate) fn import_key(alg: &KeyAlg, secret: &[u8]) -> Result<SecretBytes> {
+    if secret.len() == key_length(alg)? {
+        Ok(SecretBytes::from(secret))
+    } else {
+        Err(err_msg!(Input, "Invalid length for key"))
+    }
+}
+
+/// Encrypt a message, producing a Vec containing the nonce, ciphertext and tag
+///
+/// A random nonce is generated unless one is provided.
+pub(crate) fn encrypt(
+    alg: &KeyAlg,
+    key: &[u8],
+    nonce: Option<&[u8]>,
+    message: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>> {
+    match alg {
+        KeyAlg::ChaCha20Poly1305 => encrypt_aead::<ChaCha20Poly1305>(key, nonce, message, aad),
+        KeyAlg::XChaCha20Poly1305 => encrypt_aead::<XChaCha20Poly1305>(key, nonce, message, aad),
+        KeyAlg::Aes256Gcm => encrypt_aead::<Aes256Gcm>(key, nonce, message, aad),
+        _ => Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+    }
+}
+
+/// Decrypt a combined nonce, ciphertext and tag
+pub(crate) fn decrypt(alg: &KeyAlg, key: &[u8], enc: &[u8], aad: &[u8]) -> Result<SecretBytes> {
+    match alg {
+        KeyAlg::ChaCha20Poly1305 => decrypt_aead::<ChaCha20Poly1305>(key, enc, aad),
+        KeyAlg::XChaCha20Poly1305 => decrypt_aead::<XChaCha20Poly1305>(key, enc, aad),
+        KeyAlg::Aes256Gcm => decrypt_aead::<Aes256Gcm>(key, enc, aad),
+        _ => Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+    }
+}
+
+/// Encrypt a message using AES-256-CBC with HMAC-SHA-512 (A256CBC-HS512,
+/// RFC 7518 section 5.2.5), producing a Vec containing the IV, ciphertext
+/// and truncated tag
+///
+/// A random IV is generated unless one is provided.
+pub(crate) fn encrypt_cbc_hmac(
+    key: &[u8],
+    iv: Option<&[u8]>,
+    message: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>> {
+    if key.len() != CBC_HMAC_KEY_LENGTH {
+        return Err(err_msg!(Encryption, "Invalid length for key"));
+    }
+    let (mac_key, enc_key) = key.split_at(CBC_HMAC_KEY_LENGTH / 2);
+    let pad_len = 16 - message.len() % 16;
+    let mut buf =
+        Vec::with_capacity(CBC_HMAC_IV_LENGTH + message.len() + pad_len + CBC_HMAC_TAG_LENGTH);
+    match iv {
+        None => {
+            buf.resize(CBC_HMAC_IV_LENGTH, 0);
+            OsRng.fill_bytes(&mut buf);
+        }
+        Some(iv) if iv.len() == CBC_HMAC_IV_LENGTH => buf.extend_from_slice(iv),
+        Some(_) => return Err(err_msg!(Encryption, "Invalid length for nonce")),
+    }
+    buf.extend_from_slice(message);
+    buf.resize(buf.len() + pad_len, 0);
+    let (iv, msg) = buf.split_at_mut(CBC_HMAC_IV_LENGTH);
+    Aes256Cbc::new_var(enc_key, iv)
+        .map_err(|e| err_msg!(Encryption, "{}", e))?
+        .encrypt(msg, message.len())
+        .map_err(|e| err_msg!(Encryption, "{}", e))?;
+    let tag = cbc_hmac_tag(mac_key, &buf, aad)?;
+    buf.extend_from_slice(&tag);
+    Ok(buf)
+}
+
+/// Decrypt a combined IV, ciphertext and tag produced by [`encrypt_cbc_hmac`]
+pub(crate) fn decrypt_cbc_hmac(key: &[u8], enc: &[u8], aad: &[u8]) -> Result<SecretBytes> {
+    if key.len() != CBC_HMAC_KEY_LENGTH {
+        return Err(err_msg!(Encryption, "Invalid length for key"));
+    }
+    if enc.len() < CBC_HMAC_IV_LENGTH + 16 + CBC_HMAC_TAG_LENGTH
+        || (enc.len() - CBC_HMAC_IV_LENGTH - CBC_HMAC_TAG_LENGTH) % 16 != 0
+    {
+        return Err(err_msg!(
+            Encryption,
+            "Buffer is too short to represent an encrypted value"
+        ));
+    }
+    let (mac_key, enc_key) = key.split_at(CBC_HMAC_KEY_LENGTH / 2);
+    let (iv_ciphertext, tag) = enc.split_at(enc.len() - CBC_HMAC_TAG_LENGTH);
+    let expected = cbc_hmac_tag(mac_key, iv_ciphertext, aad)?;
+    // compare the tags in constant time
+    if expected
+        .iter()
+        .zip(tag)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        != 0
+    {
+        return Err(err_msg!(
+            Encryption,
+            "Error decrypting message: invalid tag"
+        ));
+    }
+
+    let (iv, ciphertext) = iv_ciphertext.split_at(CBC_HMAC_IV_LENGTH);
+    let mut result = SecretBytes::from(ciphertext);
+    {
+        let mut buf = result.as_buffer();
+        let msg_len = Aes256Cbc::new_var(enc_key, iv)
+            .map_err(|e| err_msg!(Encryption, "{}", e))?
+            .decrypt(buf.as_mut())
+            .map_err(|_| err_msg!(Encryption, "Error decrypting message: invalid padding"))?
+            .len();
+        buf.truncate(msg_len);
+    }
+    Ok(result)
+}
+
+fn cbc_hmac_tag(mac_key: &[u8], iv_ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = Hmac::<Sha512>::new_varkey(mac_key).map_err(|e| err_msg!(Encryption, "{}", e))?;
+    mac.update(aad);
+    mac.update(iv_ciphertext);
+    mac.update(&(aad.len() as u64 * 8).to_be_bytes());
+    Ok(mac.finalize().into_bytes()[..CBC_HMAC_TAG_LENGTH].to_vec())
+}
+
+/// Wrap a key using the AES key wrap algorithm (RFC 3394) with a 128 or 256
+/// bit key encryption key
+pub(crate) fn wrap_key(kek: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    if key.len() < 16 || key.len() % 8 != 0 {
+        return Err(err_msg!(Encryption, "Invalid length for wrapped key"));
+    }
+    let mut result = vec![0u8; key.len() + 8];
+    match kek.len() {
+        16 => KekAes128::try_from(kek).and_then(|kek| kek.wrap(key, &mut result)),
+        32 => KekAes256::try_from(kek).and_then(|kek| kek.wrap(key, &mut result)),
+        _ => {
+            return Err(err_msg!(
+                Encryption,
+                "Invalid length for key encryption key"
+            ))
+        }
+    }
+    .map_err(|e| err_msg!(Encryption, "Error wrapping key: {}", e))?;
+    Ok(result)
+}
+
+/// Unwrap a key produced by [`wrap_key`]
+pub(crate) fn unwrap_key(kek: &[u8], wrapped: &[u8]) -> Result<SecretBytes> {
+    if wrapped.len() < 24 || wrapped.len() % 8 != 0 {
+        return Err(err_msg!(Encryption, "Invalid length for wrapped key"));
+    }
+    let mut result = SecretBytes::from(vec![0u8; wrapped.len() - 8]);
+    {
+        let mut buf = result.as_buffer();
+        match kek.len() {
+            16 => KekAes128::try_from(kek).and_then(|kek| kek.unwrap(wrapped, buf.as_mut())),
+            32 => KekAes256::try_from(kek).and_then(|kek| kek.unwrap(wrapped, buf.as_mut())),
+            _ => {
+                return Err(err_msg!(
+                    Encryption,
+                    "Invalid length for key encryption key"
+                ))
+            }
+        }
+        .map_err(|_| err_msg!(Encryption, "Error unwrapping key"))?;
+    }
+    Ok(result)
+}
+
+fn key_length(alg: &KeyAlg) -> Result<usize> {
+    match alg {
+        KeyAlg::ChaCha20Poly1305 => Ok(<ChaCha20Poly1305 as NewAead>::KeySize::USIZE),
+        KeyAlg::XChaCha20Poly1305 => Ok(<XChaCha20Poly1305 as NewAead>::KeySize::USIZE),
+        KeyAlg::Aes256Gcm => Ok(<Aes256Gcm as NewAead>::KeySize::USIZE),
+        _ => Err(err_msg!(Unsupported, "Unsupported key algorithm")),
+    }
+}
+
+fn encrypt_aead<E: NewAead + AeadInPlace>(
+    key: &[u8],
+    nonce: Option<&[u8]>,
+    message: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>> {
+    let nonce_size = E::NonceSize::USIZE;
+    if key.len() != E::KeySize::USIZE {
+        return Err(err_msg!(Encryption, "Invalid length for key"));
+    }
+    let mut buf = Vec::with_capacity(nonce_size + message.len() + E::TagSize::USIZE);
+    match nonce {
+        None => {
+            buf.resize(nonce_size, 0);
+            OsRng.fill_bytes(&mut buf);
+        }
+        Some(nonce) if nonce.len() == nonce_size => buf.extend_from_slice(nonce),
+        Some(_) => return Err(err_msg!(Encryption, "Invalid length for nonce")),
+    }
+    buf.extend_from_slice(message);
+    let (nonce, msg) = buf.split_at_mut(nonce_size);
+    let tag = E::new(GenericArray::from_slice(key))
+        .encrypt_in_place_detached(GenericArray::from_slice(nonce), aad, msg)
+        .map_err(|e| err_msg!(Encryption, "{}", e))?;
+    buf.extend_from_slice(&tag);
+    Ok(buf)
+}
+
+fn decrypt_aead<E: NewAead + AeadInPlace>(
+    key: &[u8],
+    enc: &[u8],
+    aad: &[u8],
+) -> Result<SecretBytes> {
+    let nonce_size = E::NonceSize::USIZE;
+    let tag_size = E::TagSize::USIZE;
+    if key.len() != E::KeySize::USIZE {
+        return Err(err_msg!(Encryption, "Invalid length for key"));
+    }
+    if enc.len() < nonce_size + tag_size {
+        return Err(err_msg!(
+            Encryption,
+            "Buffer is too short to represent an encrypted value"
+        ));
+    }
+    let (nonce, enc) = enc.split_at(nonce_size);
+    let (ciphertext, tag) = enc.split_at(enc.len() - tag_size);
+    let mut result = SecretBytes::from(ciphertext);
+    E::new(GenericArray::from_slice(key))
+        .decrypt_in_place_detached(
+            GenericArray::from_slice(nonce),
+            aad,
+            result.as_buffer().as_mut(),
+            GenericArray::from_slice(tag),
+        )
+        .map_err(|e| err_msg!(Encryption, "Error decrypting message: {}", e))?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &'static str = "808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f";
+    const AAD: &'static str = "50515253c0c1c2c3c4c5c6c7";
+    const MESSAGE: &'static [u8] = b"Ladies and Gentlemen of the class of '99: \
+        If I could offer you only one tip for the future, sunscreen would be it.";
+
+    fn check_vector(
+        alg: KeyAlg,
+        key: &str,
+        nonce: &str,
+        message: &[u8],
+        aad: &str,
+        expected: &str,
+    ) {
+        let key = hex::decode(key).unwrap();
+        let nonce = hex::decode(nonce).unwrap();
+        let aad = hex::decode(aad).unwrap();
+        let enc = encrypt(&alg, &key, Some(&nonce), message, &aad).unwrap();
+        assert_eq!(hex::encode(&enc[nonce.len()..]), expected);
+        assert_eq!(&enc[..nonce.len()], nonce.as_slice());
+        let dec = decrypt(&alg, &key, &enc, &aad).unwrap();
+        assert_eq!(dec, message);
+        assert!(decrypt(&alg, &key, &enc, b"other").is_err());
+    }
+
+    #[test]
+    fn chacha20poly1305_expected() {
+        // RFC 8439 section 2.8.2
+        check_vector(
+            KeyAlg::ChaCha20Poly1305,
+            KEY,
+            "070000004041424344454647",
+            MESSAGE,
+            AAD,
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+             3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36\
+             92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+             3ff4def08e4b7a9de576d26586cec64b6116\
+             1ae10b594f09e26a7e902ecbd0600691",
+        );
+    }
+
+    #[test]
+    fn xchacha20poly1305_expected() {
+        // draft-irtf-cfrg-xchacha-03 appendix A.3.1
+        check_vector(
+            KeyAlg::XChaCha20Poly1305,
+            KEY,
+            "404142434445464748494a4b4c4d4e4f5051525354555657",
+            MESSAGE,
+            AAD,
+            "bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb\
+             731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b452\
+             2f8c9ba40db5d945b11b69b982c1bb9e3f3fac2bc369488f76b2383565d3fff9\
+             21f9664c97637da9768812f615c68b13b52e\
+             c0875924c1c7987947deafd8780acf49",
+        );
+    }
+
+    #[test]
+    fn aes256gcm_expected() {
+        // GCM specification test case 16
+        let message = hex::decode(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        )
+        .unwrap();
+        check_vector(
+            KeyAlg::Aes256Gcm,
+            "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+            "cafebabefacedbaddecaf888",
+            &message,
+            "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+            "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+             8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662\
+             76fc6ece0f4e1768cddf8853bb2d551b",
+        );
+    }
+
+    #[test]
+    fn cbc_hmac_expected() {
+        // RFC 7518 appendix B.3
+        let key: Vec<u8> = (0u8..64).collect();
+        let message = b"A cipher system must not be required to be secret, and it \
+            must be able to fall into the hands of the enemy without inconvenience";
+        let iv = hex::decode("1af38c2dc2b96ffdd86694092341bc04").unwrap();
+        let aad = b"The second principle of Auguste Kerckhoffs";
+        let enc = encrypt_cbc_hmac(&key, Some(&iv), message, aad).unwrap();
+        assert_eq!(
+            hex::encode(&enc[CBC_HMAC_IV_LENGTH..]),
+            "4affaaadb78c31c5da4b1b590d10ffbd3dd8d5d302423526912da037ecbcc7bd\
+             822c301dd67c373bccb584ad3e9279c2e6d12a1374b77f077553df829410446b\
+             36ebd97066296ae6427ea75c2e0846a11a09ccf5370dc80bfecbad28c73f09b3\
+             a3b75e662a2594410ae496b2e2e6609e31e6e02cc837f053d21f37ff4f51950b\
+             be2638d09dd7a4930930806d0703b1f6\
+             4dd3b4c088a7f45c216839645b2012bf2e6269a8c56a816dbc1b267761955bc5"
+        );
+        let dec = decrypt_cbc_hmac(&key, &enc, aad).unwrap();
+        assert_eq!(dec, &message[..]);
+        assert!(decrypt_cbc_hmac(&key, &enc, b"other").is_err());
+        assert!(decrypt_cbc_hmac(&key, &enc[..enc.len() - 1], aad).is_err());
+    }
+
+    #[test]
+    fn key_wrap_expected() {
+        // RFC 3394 section 4.6
+        let kek: Vec<u8> = (0u8..32).collect();
+        let key = hex::decode("00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f")
+            .unwrap();
+        let wrapped = wrap_key(&kek, &key).unwrap();
+        assert_eq!(
+            hex::encode(&wrapped),
+            "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326\
+             cbc7f0e71a99f43bfb988b9b7a02dd21"
+        );
+        assert_eq!(unwrap_key(&kek, &wrapped).unwrap(), key);
+        assert!(unwrap_key(&kek[..16], &wrapped).is_err());
+        assert!(wrap_key(&kek[..20], &key).is_err());
+    }
+
+    #[test]
+    fn random_key_round_trip() {
+        for alg in &[
+            KeyAlg::ChaCha20Poly1305,
+            KeyAlg::XChaCha20Poly1305,
+            KeyAlg::Aes256Gcm,
+        ] {
+            let key = generate_key(alg).unwrap();
+            assert_eq!(key.len(), 32);
+            let enc = encrypt(alg, &key, None, b"hello", &[]).unwrap();
+            assert_eq!(decrypt(alg, &key, &enc, &[]).unwrap(), &b"hello"[..]);
+            assert!(decrypt(alg, &key, &enc[..10], &[]).is_err());
+        }
+        assert!(generate_key(&KeyAlg::ED25519).is_err());
+    }
+}
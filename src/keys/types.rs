@@ -18,6 +18,22 @@ use crate::types::{sorted_tags, EntryTag, SecretBytes};
 pub enum KeyAlg {
     /// curve25519-based signature scheme
     ED25519,
+    /// curve25519-based key agreement scheme
+    X25519,
+    /// Koblitz curve signature scheme (ES256K)
+    Secp256k1,
+    /// NIST P-256 curve signature scheme (ES256)
+    P256,
+    /// BLS12-381 pairing-friendly curve, with the public key in G1
+    Bls12381G1,
+    /// BLS12-381 pairing-friendly curve, with the public key in G2
+    Bls12381G2,
+    /// ChaCha20-Poly1305 symmetric encryption
+    ChaCha20Poly1305,
+    /// XChaCha20-Poly1305 symmetric encryption
+    XChaCha20Poly1305,
+    /// AES-256-GCM symmetric encryption
+    Aes256Gcm,
     /// Unrecognized algorithm
     Other(String),
 }
@@ -29,6 +45,14 @@ impl KeyAlg {
     pub fn as_str(&self) -> &str {
         match self {
             Self::ED25519 => "ed25519",
+            Self::X25519 => "x25519",
+            Self::Secp256k1 => "secp256k1",
+            Self::P256 => "p256",
+            Self::Bls12381G1 => "bls12381g1",
+            Self::Bls12381G2 => "bls12381g2",
+            Self::ChaCha20Poly1305 => "c20p",
+            Self::XChaCha20Poly1305 => "xc20p",
+            Self::Aes256Gcm => "a256gcm",
             Self::Other(other) => other.as_str(),
         }
     }
@@ -46,6 +70,14 @@ impl FromStr for KeyAlg {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
             "ed25519" => Self::ED25519,
+            "x25519" => Self::X25519,
+            "secp256k1" => Self::Secp256k1,
+            "p256" => Self::P256,
+            "bls12381g1" => Self::Bls12381G1,
+            "bls12381g2" => Self::Bls12381G2,
+            "c20p" => Self::ChaCha20Poly1305,
+            "xc20p" => Self::XChaCha20Poly1305,
+            "a256gcm" => Self::Aes256Gcm,
             other => Self::Other(other.to_owned()),
         })
     }
@@ -57,6 +89,98 @@ impl Display for KeyAlg {
     }
 }
 
+/// Output formats for message signatures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureFormat {
+    /// The native signature format of the key algorithm, which for ECDSA
+    /// is the concatenation of the `r` and `s` values
+    Raw,
+    /// An ASN.1 DER-encoded ECDSA signature
+    Der,
+}
+
+impl Default for SignatureFormat {
+    fn default() -> Self {
+        Self::Raw
+    }
+}
+
+/// Serialization formats for keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// The native binary format of the key algorithm
+    Raw,
+    /// A JSON Web Key
+    Jwk,
+    /// A DER-encoded PKCS#8 private key
+    Pkcs8,
+}
+
+impl KeyFormat {
+    /// Get a reference to a string representing the `KeyFormat`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Raw => "raw",
+            Self::Jwk => "jwk",
+            Self::Pkcs8 => "pkcs8",
+        }
+    }
+}
+
+impl Default for KeyFormat {
+    fn default() -> Self {
+        Self::Raw
+    }
+}
+
+impl FromStr for KeyFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "raw" => Self::Raw,
+            "jwk" => Self::Jwk,
+            "pkcs8" => Self::Pkcs8,
+            _ => return Err(err_msg!(Input, "Unsupported key format")),
+        })
+    }
+}
+
+impl Display for KeyFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Key derivation functions applied to the shared secret produced by key
+/// agreement
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyAgreementKdf {
+    /// The Concat KDF with SHA-256 (NIST SP 800-56A), with the `OtherInfo`
+    /// input formatted as for JWA ECDH-ES (RFC 7518 section 4.6.2)
+    ConcatKdf {
+        /// The algorithm identifier for the derived key
+        alg_id: String,
+        /// The party `U` (producer) information
+        apu: Vec<u8>,
+        /// The party `V` (recipient) information
+        apv: Vec<u8>,
+        /// The length of the derived key in bytes, at most
+        /// [`MAX_DERIVED_KEY_LENGTH`](crate::MAX_DERIVED_KEY_LENGTH)
+        key_len: usize,
+    },
+    /// HKDF with SHA-256 (RFC 5869)
+    Hkdf {
+        /// The optional salt value
+        salt: Vec<u8>,
+        /// The context and application specific information
+        info: Vec<u8>,
+        /// The length of the derived key in bytes, at most
+        /// [`MAX_DERIVED_KEY_LENGTH`](crate::MAX_DERIVED_KEY_LENGTH)
+        key_len: usize,
+    },
+}
+
 /// Categories of keys supported by the default KMS
 #[derive(Clone, Debug, PartialEq, Eq, Zeroize)]
 pub enum KeyCategory {
@@ -64,6 +188,8 @@ pub enum KeyCategory {
     PublicKey,
     /// A combination of a public and private key
     KeyPair,
+    /// A symmetric key
+    SecretKey,
     /// An unrecognized key category
     Other(String),
 }
@@ -74,6 +200,7 @@ impl KeyCategory {
         match self {
             Self::PublicKey => "public",
             Self::KeyPair => "keypair",
+            Self::SecretKey => "secret",
             Self::Other(other) => other.as_str(),
         }
     }
@@ -102,6 +229,7 @@ impl FromStr for KeyCategory {
         Ok(match s {
             "public" => Self::PublicKey,
             "keypair" => Self::KeyPair,
+            "secret" => Self::SecretKey,
             other => Self::Other(other.to_owned()),
         })
     }
@@ -144,9 +272,41 @@ pub struct KeyParams {
         with = "crate::serde_utils::as_base58"
     )]
     pub prv_key: Option<SecretBytes>,
+
+    /// Whether the private key may be exported from the store
+    ///
+    /// This is fixed when the key is created and is not affected by
+    /// updates to the key metadata.
+    #[serde(
+        default = "default_exportable",
+        rename = "exp",
+        skip_serializing_if = "is_exportable"
+    )]
+    pub exportable: bool,
+}
+
+#[inline]
+fn default_exportable() -> bool {
+    true
+}
+
+#[inline]
+fn is_exportable(exportable: &bool) -> bool {
+    *exportable
 }
 
 impl KeyParams {
+    /// Determine the initial export policy for a new key from its metadata
+    ///
+    /// A key is created as non-exportable when the metadata is a JSON object
+    /// containing `"exportable": false`.
+    pub(crate) fn metadata_exportable(metadata: Option<&str>) -> bool {
+        metadata
+            .and_then(|meta| serde_json::from_str::<serde_json::Value>(meta).ok())
+            .and_then(|meta| meta.get("exportable").and_then(serde_json::Value::as_bool))
+            .unwrap_or(true)
+    }
+
     pub(crate) fn to_vec(&self) -> Result<Vec<u8>, Error> {
         serde_json::to_vec(self)
             .map_err(|e| err_msg!(Unexpected, "Error serializing key params: {}", e))
@@ -174,7 +334,7 @@ impl Zeroize for KeyParams {
 /// A stored key entry
 #[derive(Clone, Debug, Eq)]
 pub struct KeyEntry {
-    /// The category of the key entry (public or public/private pair)
+    /// The category of the key entry (public, public/private pair or secret)
     pub category: KeyCategory,
     /// The key entry identifier
     pub ident: String,
@@ -202,6 +362,41 @@ impl KeyEntry {
         self.params.reference.is_none()
     }
 
+    /// Determine if the private key may be exported from the store
+    ///
+    /// Keys are exportable unless the key metadata contained
+    /// `"exportable": false` when the key was created.
+    pub fn is_exportable(&self) -> bool {
+        self.params.exportable
+    }
+
+    /// Encode the key as a JSON Web Key
+    ///
+    /// The private key is only included when `include_private` is set, in
+    /// which case the key must be exportable. Symmetric keys can only be
+    /// encoded with the private key.
+    pub fn to_jwk(&self, include_private: bool) -> Result<String, Error> {
+        let prv_key = if include_private {
+            if !self.is_exportable() {
+                return Err(err_msg!(Forbidden, "Key is not exportable"));
+            }
+            Some(
+                self.params
+                    .prv_key
+                    .as_ref()
+                    .ok_or_else(|| err_msg!(Input, "Undefined private key"))?
+                    .as_ref(),
+            )
+        } else {
+            None
+        };
+        super::jwk::encode_jwk(
+            &self.params.alg,
+            self.params.pub_key.as_ref().map(Vec::as_slice),
+            prv_key,
+        )
+    }
+
     /// Access the associated public key as an [`EncodedVerKey`]
     pub fn encoded_verkey(&self) -> Result<EncodedVerKey, Error> {
         Ok(self
@@ -350,9 +545,33 @@ mod tests {
             reference: None,
             pub_key: Some(vec![0, 0, 0, 0]),
             prv_key: Some(vec![1, 1, 1, 1].into()),
+            exportable: true,
         };
         let enc_params = params.to_vec().unwrap();
         let p2 = KeyParams::from_slice(&enc_params).unwrap();
         assert_eq!(p2, params);
     }
+
+    #[test]
+    fn key_params_exportable() {
+        assert!(KeyParams::metadata_exportable(None));
+        assert!(KeyParams::metadata_exportable(Some("meta")));
+        assert!(!KeyParams::metadata_exportable(Some(
+            r#"{"exportable": false}"#
+        )));
+
+        let params = KeyParams {
+            alg: KeyAlg::ED25519,
+            metadata: None,
+            reference: None,
+            pub_key: None,
+            prv_key: Some(vec![1, 1, 1, 1].into()),
+            exportable: false,
+        };
+        let enc_params = params.to_vec().unwrap();
+        let p2 = KeyParams::from_slice(&enc_params).unwrap();
+        assert!(!p2.exportable);
+        let p3 = KeyParams::from_slice(br#"{"alg":"ed25519"}"#).unwrap();
+        assert!(p3.exportable);
+    }
 }
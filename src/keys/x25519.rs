@@ -0,0 +1,178 @@
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha512};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroize;
+
+use crate::error::Result;
+use crate::types::SecretBytes;
+
+/// The length of an X25519 public or private key
+pub const KEY_LENGTH: usize = 32;
+
+/// Generate a new X25519 keypair, returning the public and private keys
+///
+/// A seeded private key is derived in the same manner as libsodium's
+/// `crypto_box_seed_keypair`.
+pub(crate) fn generate_keypair(seed: Option<&[u8]>) -> Result<(Vec<u8>, SecretBytes)> {
+    let sk = match seed {
+        None => StaticSecret::new(OsRng),
+        Some(seed) if seed.len() == KEY_LENGTH => hashed_secret(seed),
+        Some(_) => return Err(err_msg!(Input, "Invalid length for seed")),
+    };
+    Ok(keypair_bytes(sk))
+}
+
+/// Load an X25519 keypair from an existing private key
+pub(crate) fn import_keypair(secret: &[u8]) -> Result<(Vec<u8>, SecretBytes)> {
+    if secret.len() != KEY_LENGTH {
+        return Err(err_msg!(Input, "Invalid X25519 private key"));
+    }
+    let mut sk = [0u8; KEY_LENGTH];
+    sk.copy_from_slice(secret);
+    let secret = StaticSecret::from(sk);
+    sk.zeroize();
+    Ok(keypair_bytes(secret))
+}
+
+/// Convert an Ed25519 private key into an X25519 keypair
+///
+/// This is compatible with libsodium's `crypto_sign_ed25519_sk_to_curve25519`.
+pub(crate) fn convert_ed25519_keypair(ed_sk: &[u8]) -> Result<(Vec<u8>, SecretBytes)> {
+    if ed_sk.len() < KEY_LENGTH {
+        return Err(err_msg!(Input, "Invalid Ed25519 private key"));
+    }
+    Ok(keypair_bytes(hashed_secret(&ed_sk[..KEY_LENGTH])))
+}
+
+/// Convert an Ed25519 public key into an X25519 public key
+///
+/// This is compatible with libsodium's `crypto_sign_ed25519_pk_to_curve25519`.
+pub(crate) fn convert_ed25519_public_key(ed_pk: &[u8]) -> Result<Vec<u8>> {
+    if ed_pk.len() != KEY_LENGTH {
+        return Err(err_msg!(Input, "Invalid Ed25519 public key"));
+    }
+    let point = CompressedEdwardsY::from_slice(ed_pk)
+        .decompress()
+        .ok_or_else(|| err_msg!(Input, "Invalid Ed25519 public key"))?;
+    Ok(point.to_montgomery().to_bytes().to_vec())
+}
+
+/// Perform an X25519 key exchange between a private key and a peer public key
+pub(crate) fn diffie_hellman(prv_key: &[u8], pub_key: &[u8]) -> Result<SecretBytes> {
+    if prv_key.len() != KEY_LENGTH {
+        return Err(err_msg!(Input, "Invalid X25519 private key"));
+    }
+    validate_public_key(pub_key)?;
+    let mut sk = [0u8; KEY_LENGTH];
+    sk.copy_from_slice(prv_key);
+    let secret = StaticSecret::from(sk);
+    sk.zeroize();
+    let mut pk = [0u8; KEY_LENGTH];
+    pk.copy_from_slice(pub_key);
+    let shared = secret.diffie_hellman(&PublicKey::from(pk));
+    // a low-order public key produces an all-zero result
+    if shared.as_bytes().iter().all(|b| *b == 0) {
+        return Err(err_msg!(Input, "Invalid X25519 public key"));
+    }
+    Ok(SecretBytes::from(&shared.as_bytes()[..]))
+}
+
+/// Check that a public key is a valid X25519 point encoding
+pub(crate) fn validate_public_key(pub_key: &[u8]) -> Result<()> {
+    if pub_key.len() == KEY_LENGTH {
+        Ok(())
+    } else {
+        Err(err_msg!(Input, "Invalid X25519 public key"))
+    }
+}
+
+fn hashed_secret(input: &[u8]) -> StaticSecret {
+    let mut hash = Sha512::digest(input);
+    let mut sk = [0u8; KEY_LENGTH];
+    sk.copy_from_slice(&hash[..KEY_LENGTH]);
+    hash.as_mut_slice().zeroize();
+    let secret = StaticSecret::from(sk);
+    sk.zeroize();
+    secret
+}
+
+fn keypair_bytes(sk: StaticSecret) -> (Vec<u8>, SecretBytes) {
+    let pk = PublicKey::from(&sk);
+    (
+        pk.as_bytes().to_vec(),
+        SecretBytes::from(&sk.to_bytes()[..]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // from the libsodium ed25519_convert test
+    const ED25519_SEED: &'static str =
+        "421151a459faeade3d247115f94aedae42318124095afabe4d1451a559faedee";
+
+    #[test]
+    fn convert_ed25519_expected() {
+        let seed = hex::decode(ED25519_SEED).unwrap();
+        let (pk, sk) = convert_ed25519_keypair(&seed).unwrap();
+        assert_eq!(
+            hex::encode(pk),
+            "f1814f0e8ff1043d8a44d25babff3cedcae6c22c3edaa48f857ae70de2baae50"
+        );
+        assert_eq!(
+            hex::encode(&*sk),
+            "8052030376d47112be7f73ed7a019293dd12ad910b654455798b4667d73de166"
+        );
+    }
+
+    #[test]
+    fn convert_ed25519_public_expected() {
+        let ed_pk = hex::decode("b5076a8474a832daee4dd5b4040983b6623b5f344aca57d4d6ee4baf3f259e6e")
+            .unwrap();
+        let seed = hex::decode(ED25519_SEED).unwrap();
+        let (pk, _) = convert_ed25519_keypair(&seed).unwrap();
+        assert_eq!(convert_ed25519_public_key(&ed_pk).unwrap(), pk);
+        assert!(convert_ed25519_public_key(&ed_pk[1..]).is_err());
+    }
+
+    #[test]
+    fn import_expected() {
+        // RFC 7748 section 6.1
+        let sk = hex::decode("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a")
+            .unwrap();
+        let (pk, _) = import_keypair(&sk).unwrap();
+        assert_eq!(
+            hex::encode(pk),
+            "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"
+        );
+        assert!(import_keypair(&sk[1..]).is_err());
+    }
+
+    #[test]
+    fn diffie_hellman_expected() {
+        // RFC 7748 section 6.1
+        let alice_sk =
+            hex::decode("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a")
+                .unwrap();
+        let bob_pk =
+            hex::decode("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f")
+                .unwrap();
+        assert_eq!(
+            hex::encode(&*diffie_hellman(&alice_sk, &bob_pk).unwrap()),
+            "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742"
+        );
+        assert!(diffie_hellman(&alice_sk, &[0u8; KEY_LENGTH]).is_err());
+    }
+
+    #[test]
+    fn seeded_keypair_matches_conversion() {
+        let seed = hex::decode(ED25519_SEED).unwrap();
+        assert_eq!(
+            generate_keypair(Some(&seed)).unwrap(),
+            convert_ed25519_keypair(&seed).unwrap()
+        );
+        assert!(generate_keypair(Some(b"short")).is_err());
+    }
+}
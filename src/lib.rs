@@ -17,6 +17,15 @@ extern crate log;
 #[macro_use]
 extern crate serde;
 
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+mod audit;
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+mod cache;
+
+#[cfg(any(feature = "memory", feature = "sqlite"))]
+mod changes;
+
 #[cfg(any(feature = "postgres", feature = "sqlite"))]
 mod db_utils;
 
@@ -28,6 +37,11 @@ pub mod future;
 /// Indy wallet compatibility support
 pub mod indy_compat;
 
+#[cfg(feature = "memory")]
+#[cfg_attr(docsrs, doc(cfg(feature = "memory")))]
+/// In-memory store support
+pub mod memory;
+
 mod options;
 
 #[cfg(feature = "ffi")]
@@ -57,15 +71,24 @@ pub mod any;
 
 mod keys;
 pub use self::keys::{
-    derive_verkey, verify_signature,
+    derive_verkey, verify_jws, verify_signature,
     wrap::{generate_raw_wrap_key, WrapKeyMethod},
-    KeyAlg, KeyCategory, KeyEntry, KeyParams, PassKey,
+    JweEncryption, JwsSerialization, KeyAgreementKdf, KeyAlg, KeyCategory, KeyEntry, KeyFormat,
+    KeyParams, PassKey, SignatureFormat, MAX_DERIVED_KEY_LENGTH,
 };
 
+mod retry;
+pub use self::retry::RetryPolicy;
+
 mod store;
-pub use self::store::{Backend, ManageBackend, QueryBackend, Scan, Session, Store};
+pub use self::store::{
+    Backend, CancelHandle, ChangeStream, ManageBackend, QueryBackend, Scan, Session, Store,
+};
 
 mod types;
-pub use self::types::{Entry, EntryOperation, EntryTag, SecretBytes, TagFilter};
+pub use self::types::{
+    AuditOperation, AuditRecord, Entry, EntryCacheStats, EntryChange, EntryHistory, EntryOperation,
+    EntryTag, ProfileQuota, ProfileUsage, SecretBytes, SoftDelete, TagFilter,
+};
 
 mod wql;
@@ -0,0 +1,240 @@
+use crate::error::Result;
+use crate::keys::store::StoreKey;
+use crate::types::{EncEntryTag, TagFilter};
+use crate::wql::tags::{tag_query, CompareOp, ConjunctionOp, TagName, TagQueryEncoder};
+
+/// A tag filter clause which is evaluated against the encrypted tags of an entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum TagMatch {
+    Compare(CompareOp, Vec<u8>, Vec<u8>, bool),
+    In(Vec<u8>, Vec<Vec<u8>>, bool, bool),
+    Exist(Vec<u8>, bool, bool),
+    Conj(ConjunctionOp, Vec<TagMatch>),
+}
+
+impl TagMatch {
+    pub fn matches(&self, tags: &[EncEntryTag]) -> bool {
+        match self {
+            Self::Compare(op, name, value, plaintext) => tags.iter().any(|tag| {
+                tag.plaintext == *plaintext
+                    && &tag.name == name
+                    && compare_value(*op, &tag.value, value)
+            }),
+            Self::In(name, values, plaintext, negate) => tags.iter().any(|tag| {
+                tag.plaintext == *plaintext
+                    && &tag.name == name
+                    && values.contains(&tag.value) != *negate
+            }),
+            Self::Exist(name, plaintext, negate) => {
+                tags.iter()
+                    .any(|tag| tag.plaintext == *plaintext && &tag.name == name)
+                    != *negate
+            }
+            Self::Conj(ConjunctionOp::And, clauses) => clauses.iter().all(|c| c.matches(tags)),
+            Self::Conj(ConjunctionOp::Or, clauses) => clauses.iter().any(|c| c.matches(tags)),
+        }
+    }
+}
+
+fn compare_value(op: CompareOp, value: &[u8], target: &[u8]) -> bool {
+    match op {
+        CompareOp::Eq => value == target,
+        CompareOp::Neq => value != target,
+        CompareOp::Gt => value > target,
+        CompareOp::Gte => value >= target,
+        CompareOp::Lt => value < target,
+        CompareOp::Lte => value <= target,
+        CompareOp::Like => like_match(value, target),
+        CompareOp::NotLike => !like_match(value, target),
+    }
+}
+
+// follows the behaviour of the Sqlite LIKE operator, which is case-insensitive
+// for ASCII characters
+fn like_match(value: &[u8], pattern: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some((b'%', rest)) => (0..=value.len()).any(|idx| like_match(&value[idx..], rest)),
+        Some((b'_', rest)) => !value.is_empty() && like_match(&value[1..], rest),
+        Some((c, rest)) => match value.split_first() {
+            Some((v, value_rest)) => v.eq_ignore_ascii_case(c) && like_match(value_rest, rest),
+            None => false,
+        },
+    }
+}
+
+pub(crate) struct TagMatchEncoder<EN, EV> {
+    enc_name: EN,
+    enc_value: EV,
+}
+
+impl<EN, EV> TagMatchEncoder<EN, EV>
+where
+    EN: Fn(&str) -> Result<Vec<u8>>,
+    EV: Fn(&str) -> Result<Vec<u8>>,
+{
+    pub fn new(enc_name: EN, enc_value: EV) -> Self {
+        Self {
+            enc_name,
+            enc_value,
+        }
+    }
+}
+
+impl<EN, EV> TagQueryEncoder for TagMatchEncoder<EN, EV>
+where
+    EN: Fn(&str) -> Result<Vec<u8>>,
+    EV: Fn(&str) -> Result<Vec<u8>>,
+{
+    type Arg = Vec<u8>;
+    type Clause = TagMatch;
+
+    fn encode_name(&mut self, name: &TagName) -> Result<Self::Arg> {
+        Ok(match name {
+            TagName::Encrypted(name) | TagName::Plaintext(name) => (&self.enc_name)(name)?,
+        })
+    }
+
+    fn encode_value(&mut self, value: &String, is_plaintext: bool) -> Result<Self::Arg> {
+        Ok(if is_plaintext {
+            value.as_bytes().to_vec()
+        } else {
+            (&self.enc_value)(value)?
+        })
+    }
+
+    fn encode_op_clause(
+        &mut self,
+        op: CompareOp,
+        enc_name: Self::Arg,
+        enc_value: Self::Arg,
+        is_plaintext: bool,
+    ) -> Result<Option<Self::Clause>> {
+        Ok(Some(TagMatch::Compare(
+            op,
+            enc_name,
+            enc_value,
+            is_plaintext,
+        )))
+    }
+
+    fn encode_in_clause(
+        &mut self,
+        enc_name: Self::Arg,
+        enc_values: Vec<Self::Arg>,
+        is_plaintext: bool,
+        negate: bool,
+    ) -> Result<Option<Self::Clause>> {
+        Ok(Some(TagMatch::In(
+            enc_name,
+            enc_values,
+            is_plaintext,
+            negate,
+        )))
+    }
+
+    fn encode_exist_clause(
+        &mut self,
+        enc_name: Self::Arg,
+        is_plaintext: bool,
+        negate: bool,
+    ) -> Result<Option<Self::Clause>> {
+        Ok(Some(TagMatch::Exist(enc_name, is_plaintext, negate)))
+    }
+
+    fn encode_conj_clause(
+        &mut self,
+        op: ConjunctionOp,
+        clauses: Vec<Self::Clause>,
+    ) -> Result<Option<Self::Clause>> {
+        Ok(Some(TagMatch::Conj(op, clauses)))
+    }
+}
+
+pub(crate) fn encode_tag_match(
+    tag_filter: Option<TagFilter>,
+    key: &StoreKey,
+) -> Result<Option<TagMatch>> {
+    if let Some(tag_filter) = tag_filter {
+        let tag_query = tag_query(tag_filter.query)?;
+        let mut enc = TagMatchEncoder::new(
+            |name| Ok(key.encrypt_tag_name(StoreKey::prepare_input(name.as_bytes()))?),
+            |value| Ok(key.encrypt_tag_value(StoreKey::prepare_input(value.as_bytes()))?),
+        );
+        enc.encode_query(&tag_query)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wql::tags::TagQuery;
+
+    fn tag(name: &str, value: &str, plaintext: bool) -> EncEntryTag {
+        EncEntryTag {
+            name: name.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            plaintext,
+        }
+    }
+
+    fn encode(query: &TagQuery) -> TagMatch {
+        let mut enc = TagMatchEncoder::new(
+            |name: &str| Ok(name.as_bytes().to_vec()),
+            |value: &str| Ok(value.to_uppercase().into_bytes()),
+        );
+        enc.encode_query(query).unwrap().unwrap()
+    }
+
+    #[test]
+    fn tag_match_compare() {
+        let tags = vec![tag("enctag", "ENCVAL", false), tag("plaintag", "10", true)];
+        let query = TagQuery::And(vec![
+            TagQuery::Eq(
+                TagName::Encrypted("enctag".to_string()),
+                "encval".to_string(),
+            ),
+            TagQuery::Gt(TagName::Plaintext("plaintag".to_string()), "05".to_string()),
+        ]);
+        assert!(encode(&query).matches(&tags));
+
+        let query = TagQuery::Not(Box::new(TagQuery::Eq(
+            TagName::Encrypted("enctag".to_string()),
+            "encval".to_string(),
+        )));
+        assert!(!encode(&query).matches(&tags));
+
+        let query = TagQuery::Eq(
+            TagName::Plaintext("enctag".to_string()),
+            "ENCVAL".to_string(),
+        );
+        assert!(!encode(&query).matches(&tags));
+    }
+
+    #[test]
+    fn tag_match_in_exist() {
+        let tags = vec![tag("enctag", "ENCVAL", false)];
+        let query = TagQuery::In(
+            TagName::Encrypted("enctag".to_string()),
+            vec!["other".to_string(), "encval".to_string()],
+        );
+        assert!(encode(&query).matches(&tags));
+
+        let query = TagQuery::Not(Box::new(TagQuery::Exist(vec![TagName::Encrypted(
+            "enctag".to_string(),
+        )])));
+        assert!(!encode(&query).matches(&tags));
+        assert!(encode(&query).matches(&[]));
+    }
+
+    #[test]
+    fn tag_match_like() {
+        assert!(like_match(b"plainval", b"plain%"));
+        assert!(like_match(b"plainval", b"%VAL"));
+        assert!(like_match(b"plainval", b"pl_inval"));
+        assert!(!like_match(b"plainval", b"plain"));
+        assert!(!like_match(b"plainval", b"_plainval"));
+    }
+}
@@ -0,0 +1,1163 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_mutex::{Mutex, MutexGuard};
+use futures_lite::stream::{self, StreamExt};
+
+use super::changes::ChangeBroadcast;
+use super::error::Result;
+use super::future::{unblock, BoxFuture};
+use super::keys::{store::StoreKey, wrap::WrapKeyMethod, EntryEncryptor, KeyCache, PassKey};
+use super::store::{Backend, ChangeStream, QueryBackend, Scan};
+use super::types::{
+    AuditRecord, EncEntryTag, Entry, EntryCacheStats, EntryChange, EntryHistory, EntryKind,
+    EntryOperation, EntryTag, Expiry, ProfileId, ProfileQuota, ProfileUsage, SoftDelete, TagFilter,
+};
+
+mod filter;
+use self::filter::{encode_tag_match, TagMatch};
+
+mod provision;
+pub use self::provision::MemoryStoreOptions;
+
+const PAGE_SIZE: usize = 32;
+
+/// Items are indexed by profile, kind, encrypted category and encrypted name
+type ItemKey = (ProfileId, i16, Vec<u8>, Vec<u8>);
+
+#[derive(Clone, Debug)]
+struct MemoryItem {
+    id: i64,
+    value: Vec<u8>,
+    tags: Vec<EncEntryTag>,
+    expiry: Option<Expiry>,
+    deleted: Option<Expiry>,
+}
+
+impl MemoryItem {
+    #[inline]
+    fn is_expired(&self, now: &Expiry) -> bool {
+        matches!(&self.expiry, Some(expiry) if expiry <= now)
+    }
+
+    #[inline]
+    fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    /// Identify the stored state of the item, which changes whenever the
+    /// item is replaced, deleted or restored
+    #[inline]
+    fn version(&self) -> (i64, bool) {
+        (self.id, self.is_deleted())
+    }
+}
+
+/// The contents of an in-memory store
+#[derive(Debug, Default)]
+pub(crate) struct MemoryData {
+    wrap_key_ref: String,
+    default_profile: String,
+    profiles: BTreeMap<String, (ProfileId, Vec<u8>)>,
+    items: BTreeMap<ItemKey, MemoryItem>,
+    history: BTreeMap<ItemKey, Vec<MemoryItem>>,
+    next_profile_id: ProfileId,
+    next_item_id: i64,
+    quotas: BTreeMap<ProfileId, ProfileQuota>,
+    changes: ChangeBroadcast,
+}
+
+impl MemoryData {
+    fn add_profile(&mut self, name: String, enc_key: Vec<u8>) -> Option<ProfileId> {
+        if self.profiles.contains_key(&name) {
+            None
+        } else {
+            self.next_profile_id += 1;
+            let pid = self.next_profile_id;
+            self.profiles.insert(name, (pid, enc_key));
+            Some(pid)
+        }
+    }
+
+    fn remove_profile(&mut self, name: &str) -> bool {
+        if let Some((pid, _)) = self.profiles.remove(name) {
+            self.items.retain(|(item_pid, ..), _| *item_pid != pid);
+            self.history.retain(|(item_pid, ..), _| *item_pid != pid);
+            self.quotas.remove(&pid);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Get the number and total value size of the items in a profile
+    fn usage(&self, profile_id: ProfileId, now: &Expiry) -> (i64, i64) {
+        let start: ItemKey = (profile_id, 0, Vec::new(), Vec::new());
+        self.items
+            .range(start..)
+            .take_while(|((pid, ..), _)| *pid == profile_id)
+            .filter(|(_, item)| !item.is_deleted() && !item.is_expired(now))
+            .fold((0, 0), |(items, bytes), (_, item)| {
+                (items + 1, bytes + item.value.len() as i64)
+            })
+    }
+}
+
+/// An in-memory store
+///
+/// Entries are encrypted in the same manner as the database backends, but
+/// are only retained for the lifetime of the process.
+pub struct MemoryStore {
+    data: Arc<Mutex<MemoryData>>,
+    default_profile: String,
+    key_cache: Arc<KeyCache>,
+    name: Option<String>,
+    soft_delete: RwLock<Arc<SoftDelete>>,
+    history: RwLock<Arc<EntryHistory>>,
+}
+
+impl MemoryStore {
+    pub(crate) fn new(
+        data: Arc<Mutex<MemoryData>>,
+        default_profile: String,
+        key_cache: KeyCache,
+        name: Option<String>,
+    ) -> Self {
+        Self {
+            data,
+            default_profile,
+            key_cache: Arc::new(key_cache),
+            name,
+            soft_delete: RwLock::new(Arc::new(SoftDelete::default())),
+            history: RwLock::new(Arc::new(EntryHistory::default())),
+        }
+    }
+}
+
+impl Debug for MemoryStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore")
+            .field("default_profile", &self.default_profile)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl Backend for MemoryStore {
+    type Session = MemorySession;
+
+    fn create_profile(&self, name: Option<String>) -> BoxFuture<'_, Result<String>> {
+        let name = name.unwrap_or_else(provision::random_profile_name);
+        Box::pin(async move {
+            let key = StoreKey::new()?;
+            let enc_key = provision::encode_store_key(&key, &self.key_cache.wrap_key)?;
+            let pid = self
+                .data
+                .lock()
+                .await
+                .add_profile(name.clone(), enc_key)
+                .ok_or_else(|| err_msg!(Duplicate, "Duplicate profile name"))?;
+            self.key_cache
+                .add_profile(name.clone(), pid, Arc::new(key))
+                .await;
+            Ok(name)
+        })
+    }
+
+    fn get_profile_name(&self) -> &str {
+        self.default_profile.as_str()
+    }
+
+    fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool>> {
+        Box::pin(async move { Ok(self.data.lock().await.remove_profile(&name)) })
+    }
+
+    fn set_profile_quota(
+        &self,
+        profile: Option<String>,
+        quota: ProfileQuota,
+    ) -> BoxFuture<'_, Result<()>> {
+        let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+        Box::pin(async move {
+            let mut data = self.data.lock().await;
+            let (pid, _) = *data
+                .profiles
+                .get(&profile)
+                .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+            if quota == ProfileQuota::default() {
+                data.quotas.remove(&pid);
+            } else {
+                data.quotas.insert(pid, quota);
+            }
+            Ok(())
+        })
+    }
+
+    fn profile_usage(&self, profile: Option<String>) -> BoxFuture<'_, Result<ProfileUsage>> {
+        let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+        Box::pin(async move {
+            let data = self.data.lock().await;
+            let (pid, _) = data
+                .profiles
+                .get(&profile)
+                .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+            let (items, bytes) = data.usage(*pid, &chrono::Utc::now());
+            Ok(ProfileUsage {
+                items,
+                bytes,
+                quota: data.quotas.get(pid).copied().unwrap_or_default(),
+            })
+        })
+    }
+
+    fn rekey_backend(
+        &mut self,
+        method: WrapKeyMethod,
+        pass_key: PassKey<'_>,
+    ) -> BoxFuture<'_, Result<()>> {
+        let pass_key = pass_key.into_owned();
+        Box::pin(async move {
+            let (wrap_key, wrap_key_ref) = unblock(move || method.resolve(pass_key)).await?;
+            let wrap_key = Arc::new(wrap_key);
+            let mut data = self.data.lock().await;
+            let mut upd_keys = BTreeMap::<String, Vec<u8>>::new();
+            for (name, (_, enc_key)) in data.profiles.iter() {
+                let store_key = self.key_cache.load_key(enc_key.clone()).await?;
+                let upd_key = unblock({
+                    let wrap_key = wrap_key.clone();
+                    move || provision::encode_store_key(&store_key, &wrap_key)
+                })
+                .await?;
+                upd_keys.insert(name.clone(), upd_key);
+            }
+            for (name, key) in upd_keys {
+                if let Some((_, enc_key)) = data.profiles.get_mut(&name) {
+                    *enc_key = key;
+                }
+            }
+            data.wrap_key_ref = wrap_key_ref.into_uri();
+            self.key_cache = Arc::new(KeyCache::new(wrap_key));
+            Ok(())
+        })
+    }
+
+    fn scan(
+        &self,
+        profile: Option<String>,
+        kind: EntryKind,
+        category: String,
+        tag_filter: Option<TagFilter>,
+        offset: Option<i64>,
+        limit: Option<i64>,
+    ) -> BoxFuture<'_, Result<Scan<'static, Entry>>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let enc_rows = session
+                .find_entries(kind, &category, tag_filter, offset, limit, false)
+                .await?;
+            let key = session.profile_key().await?.1;
+            let batches = enc_rows
+                .chunks(PAGE_SIZE)
+                .map(|rows| rows.to_vec())
+                .collect::<Vec<_>>();
+            let stream = stream::iter(batches).then(move |enc_rows| {
+                let category = category.clone();
+                let key = key.clone();
+                unblock(move || decrypt_entries(category, enc_rows, &key))
+            });
+            Ok(Scan::new(stream, PAGE_SIZE))
+        })
+    }
+
+    fn session(&self, profile: Option<String>, transaction: bool) -> Result<Self::Session> {
+        Ok(MemorySession {
+            data: self.data.clone(),
+            key_cache: self.key_cache.clone(),
+            profile: profile.unwrap_or_else(|| self.default_profile.clone()),
+            profile_key: None,
+            is_transaction: transaction,
+            transaction: None,
+            soft_delete: self.soft_delete.read().unwrap().clone(),
+            history: self.history.read().unwrap().clone(),
+        })
+    }
+
+    fn backup_to(&self, _path: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            Err(err_msg!(
+                Unsupported,
+                "Backups are not supported for in-memory stores"
+            ))
+        })
+    }
+
+    fn entry_cache_stats(&self) -> Option<EntryCacheStats> {
+        None
+    }
+
+    fn default_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn subscribe(
+        &self,
+        profile: Option<String>,
+        category: Option<String>,
+    ) -> BoxFuture<'_, Result<ChangeStream>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let (profile_id, _) = session.profile_key().await?;
+            Ok(self
+                .data
+                .lock()
+                .await
+                .changes
+                .subscribe(profile_id, category))
+        })
+    }
+
+    fn scan_audit(
+        &self,
+        _start: Option<i64>,
+        _end: Option<i64>,
+    ) -> BoxFuture<'_, Result<Vec<AuditRecord>>> {
+        Box::pin(async move {
+            Err(err_msg!(
+                Unsupported,
+                "Audit logging is not supported for in-memory stores"
+            ))
+        })
+    }
+
+    fn set_audit_actor(&self, _actor: Option<String>) {}
+
+    fn set_soft_delete(&self, policy: SoftDelete) -> Result<()> {
+        *self.soft_delete.write().unwrap() = Arc::new(policy);
+        Ok(())
+    }
+
+    fn purge_deleted(
+        &self,
+        profile: Option<String>,
+        older_than: Duration,
+    ) -> BoxFuture<'_, Result<i64>> {
+        Box::pin(async move {
+            let cutoff = chrono::Utc::now()
+                .checked_sub_signed(
+                    chrono::Duration::from_std(older_than)
+                        .map_err(err_map!(Input, "Invalid retention period"))?,
+                )
+                .ok_or_else(|| err_msg!(Input, "Invalid retention period"))?;
+            let mut session = self.session(profile, false)?;
+            let (profile_id, key) = session.profile_key().await?;
+            let purged = {
+                let mut data = self.data.lock().await;
+                let purged = data
+                    .items
+                    .iter()
+                    .filter(|(item_key, item)| {
+                        item_key.0 == profile_id
+                            && matches!(&item.deleted, Some(deleted) if *deleted <= cutoff)
+                    })
+                    .map(|(item_key, _)| item_key.clone())
+                    .collect::<Vec<_>>();
+                for item_key in purged.iter() {
+                    data.items.remove(item_key);
+                }
+                purged
+            };
+            let count = purged.len() as i64;
+            let enc_categories = purged
+                .into_iter()
+                .filter(|(_, kind, ..)| *kind == EntryKind::Item as i16)
+                .map(|(_, _, enc_category, _)| enc_category)
+                .collect::<BTreeSet<_>>();
+            let categories = unblock(move || {
+                enc_categories
+                    .into_iter()
+                    .map(|category| key.decrypt_entry_category(category))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .await?;
+            let data = self.data.lock().await;
+            for category in categories {
+                data.changes.publish(
+                    profile_id,
+                    &EntryChange {
+                        operation: EntryOperation::Remove,
+                        category,
+                        name: None,
+                    },
+                );
+            }
+            Ok(count)
+        })
+    }
+
+    fn set_entry_history(&self, policy: EntryHistory) -> Result<()> {
+        *self.history.write().unwrap() = Arc::new(policy);
+        Ok(())
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move { Ok(()) })
+    }
+}
+
+/// The pending changes of a transaction on an in-memory store
+///
+/// Writes are buffered until the transaction is committed, so that the store
+/// is only locked for the duration of each operation. A removed item is
+/// recorded as `None`. The committed version of each item that is written or
+/// fetched for update is recorded when it is first accessed, and the commit
+/// fails if another session has changed the item in the meantime.
+#[derive(Debug, Default)]
+struct MemoryTransaction {
+    items: BTreeMap<ItemKey, Option<MemoryItem>>,
+    history: BTreeMap<ItemKey, Vec<MemoryItem>>,
+    versions: BTreeMap<ItemKey, Option<(i64, bool)>>,
+    changes: Vec<(ProfileId, EntryChange)>,
+}
+
+impl MemoryTransaction {
+    /// Check for conflicting changes by other sessions, then apply the
+    /// pending changes to the store
+    fn commit(self, data: &mut MemoryData) -> Result<()> {
+        for (key, version) in self.versions.iter() {
+            let current = data.items.get(key).map(MemoryItem::version);
+            if current != *version {
+                return Err(match (version, current) {
+                    // another session inserted an item which this
+                    // transaction also inserted
+                    (None, Some((_, false))) | (Some((_, true)), Some((_, false)))
+                        if self.items.contains_key(key) =>
+                    {
+                        err_msg!(Duplicate, "Duplicate row")
+                    }
+                    _ => err_msg!(Busy, "Entry was modified by another session"),
+                });
+            }
+        }
+
+        // the profile usage may have been increased by other sessions
+        let now = chrono::Utc::now();
+        let active = |item: &&MemoryItem| !item.is_deleted() && !item.is_expired(&now);
+        let mut deltas = BTreeMap::<ProfileId, (i64, i64)>::new();
+        for (key, pending) in self.items.iter() {
+            let delta = deltas.entry(key.0).or_default();
+            if let Some(item) = data.items.get(key).filter(active) {
+                delta.0 -= 1;
+                delta.1 -= item.value.len() as i64;
+            }
+            if let Some(item) = pending.as_ref().filter(active) {
+                delta.0 += 1;
+                delta.1 += item.value.len() as i64;
+            }
+        }
+        for (profile_id, (items, bytes)) in deltas {
+            if items <= 0 && bytes <= 0 {
+                continue;
+            }
+            if let Some(quota) = data.quotas.get(&profile_id) {
+                let (used_items, used_bytes) = data.usage(profile_id, &now);
+                quota.check(used_items + items, used_bytes + bytes)?;
+            }
+        }
+
+        for (key, item) in self.items {
+            if let Some(item) = item {
+                data.items.insert(key, item);
+            } else {
+                data.items.remove(&key);
+            }
+        }
+        for (key, versions) in self.history {
+            data.history.insert(key, versions);
+        }
+        for (profile_id, change) in self.changes {
+            data.changes.publish(profile_id, &change);
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn in_category(key: &ItemKey, profile_id: ProfileId, kind: EntryKind, enc_category: &[u8]) -> bool {
+    key.0 == profile_id && key.1 == kind as i16 && key.2.as_slice() == enc_category
+}
+
+/// A view of the store contents for a single operation, including the
+/// pending changes of the current transaction
+struct MemoryDataRef<'a> {
+    data: MutexGuard<'a, MemoryData>,
+    txn: Option<&'a mut MemoryTransaction>,
+}
+
+impl MemoryDataRef<'_> {
+    fn get(&self) -> &MemoryData {
+        &self.data
+    }
+
+    fn item(&self, key: &ItemKey) -> Option<&MemoryItem> {
+        match self.txn.as_deref().and_then(|txn| txn.items.get(key)) {
+            Some(pending) => pending.as_ref(),
+            None => self.data.items.get(key),
+        }
+    }
+
+    /// Record the committed version of an item when it is first accessed for
+    /// update by the current transaction
+    fn track(&mut self, key: &ItemKey) {
+        if let Some(txn) = self.txn.as_deref_mut() {
+            if !txn.versions.contains_key(key) {
+                let version = self.data.items.get(key).map(MemoryItem::version);
+                txn.versions.insert(key.clone(), version);
+            }
+        }
+    }
+
+    fn versions(&self, key: &ItemKey) -> Option<&Vec<MemoryItem>> {
+        match self.txn.as_deref().and_then(|txn| txn.history.get(key)) {
+            Some(versions) => Some(versions),
+            None => self.data.history.get(key),
+        }
+    }
+
+    /// Find matching items in insertion order, excluding deleted items
+    fn find_items(
+        &self,
+        profile_id: ProfileId,
+        kind: EntryKind,
+        enc_category: &[u8],
+        tag_match: Option<&TagMatch>,
+        active_only: bool,
+    ) -> Vec<(&ItemKey, &MemoryItem)> {
+        let now = chrono::Utc::now();
+        let start: ItemKey = (profile_id, kind as i16, enc_category.to_vec(), Vec::new());
+        let txn = self.txn.as_deref();
+        let committed = self
+            .data
+            .items
+            .range(start.clone()..)
+            .take_while(|(key, _)| in_category(key, profile_id, kind, enc_category))
+            .filter(|(key, _)| !txn.map(|txn| txn.items.contains_key(*key)).unwrap_or(false));
+        let pending = txn.into_iter().flat_map(|txn| {
+            txn.items
+                .range(start.clone()..)
+                .take_while(move |(key, _)| in_category(key, profile_id, kind, enc_category))
+                .filter_map(|(key, item)| item.as_ref().map(|item| (key, item)))
+        });
+        let mut found = committed
+            .chain(pending)
+            .filter(|(_, item)| !item.is_deleted())
+            .filter(|(_, item)| !active_only || !item.is_expired(&now))
+            .filter(|(_, item)| tag_match.map(|m| m.matches(&item.tags)).unwrap_or(true))
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(_, item)| item.id);
+        found
+    }
+
+    /// Get the number and total value size of the items in a profile
+    fn usage(&self, profile_id: ProfileId) -> (i64, i64) {
+        let now = chrono::Utc::now();
+        let active = |item: &&MemoryItem| !item.is_deleted() && !item.is_expired(&now);
+        let (mut items, mut bytes) = self.data.usage(profile_id, &now);
+        if let Some(txn) = self.txn.as_deref() {
+            for (key, pending) in txn.items.iter().filter(|(key, _)| key.0 == profile_id) {
+                if let Some(item) = self.data.items.get(key).filter(active) {
+                    items -= 1;
+                    bytes -= item.value.len() as i64;
+                }
+                if let Some(item) = pending.as_ref().filter(active) {
+                    items += 1;
+                    bytes += item.value.len() as i64;
+                }
+            }
+        }
+        (items, bytes)
+    }
+
+    /// Check that an item may be stored without exceeding the profile quota,
+    /// given the size of any item being replaced
+    fn check_quota(
+        &self,
+        profile_id: ProfileId,
+        value_len: usize,
+        replaced: Option<usize>,
+    ) -> Result<()> {
+        if let Some(quota) = self.data.quotas.get(&profile_id) {
+            let (mut items, mut bytes) = self.usage(profile_id);
+            if let Some(prev_len) = replaced {
+                items -= 1;
+                bytes -= prev_len as i64;
+            }
+            quota.check(items + 1, bytes + value_len as i64)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn next_item_id(&mut self) -> i64 {
+        self.data.next_item_id += 1;
+        self.data.next_item_id
+    }
+
+    fn insert_item(&mut self, key: ItemKey, item: MemoryItem) {
+        self.track(&key);
+        if let Some(txn) = self.txn.as_deref_mut() {
+            txn.items.insert(key, Some(item));
+        } else {
+            self.data.items.insert(key, item);
+        }
+    }
+
+    /// Notify subscribers of a change, or defer the notification until the
+    /// current transaction is committed
+    fn publish_change(&mut self, profile_id: ProfileId, change: EntryChange) {
+        if let Some(txn) = self.txn.as_deref_mut() {
+            txn.changes.push((profile_id, change));
+        } else {
+            self.data.changes.publish(profile_id, &change);
+        }
+    }
+
+    fn remove_item(&mut self, key: &ItemKey) -> bool {
+        match self.item(key) {
+            Some(item) if !item.is_deleted() => (),
+            _ => return false,
+        }
+        self.track(key);
+        if let Some(txn) = self.txn.as_deref_mut() {
+            txn.items.insert(key.clone(), None);
+        } else {
+            self.data.items.remove(key);
+        }
+        true
+    }
+
+    /// Mark an item as deleted, retaining it until it is restored or purged
+    fn mark_deleted(&mut self, key: &ItemKey) -> bool {
+        match self.item(key) {
+            Some(item) if !item.is_deleted() => {
+                let mut item = item.clone();
+                item.deleted = Some(chrono::Utc::now());
+                self.insert_item(key.clone(), item);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Add the current version of an item to its history, discarding the
+    /// oldest versions beyond the retention limit
+    fn record_history(&mut self, key: &ItemKey, max_versions: Option<usize>) {
+        let item = match self.item(key) {
+            Some(item) if !item.is_deleted() => item.clone(),
+            _ => return,
+        };
+        let mut versions = self.versions(key).cloned().unwrap_or_default();
+        versions.push(item);
+        if let Some(max_versions) = max_versions {
+            let excess = versions.len().saturating_sub(max_versions);
+            versions.drain(..excess);
+        }
+        self.track(key);
+        if let Some(txn) = self.txn.as_deref_mut() {
+            txn.history.insert(key.clone(), versions);
+        } else {
+            self.data.history.insert(key.clone(), versions);
+        }
+    }
+
+    /// Restore a deleted item
+    fn restore_item(&mut self, key: &ItemKey) {
+        if let Some(item) = self.item(key).filter(|item| item.is_deleted()) {
+            let mut item = item.clone();
+            item.deleted = None;
+            self.insert_item(key.clone(), item);
+        }
+    }
+}
+
+/// A session on an in-memory store
+pub struct MemorySession {
+    data: Arc<Mutex<MemoryData>>,
+    key_cache: Arc<KeyCache>,
+    profile: String,
+    profile_key: Option<(ProfileId, Arc<StoreKey>)>,
+    is_transaction: bool,
+    transaction: Option<MemoryTransaction>,
+    soft_delete: Arc<SoftDelete>,
+    history: Arc<EntryHistory>,
+}
+
+impl MemorySession {
+    async fn data(&mut self) -> MemoryDataRef<'_> {
+        let data = self.data.lock().await;
+        let txn = if self.is_transaction {
+            if self.transaction.is_none() {
+                info!("Start transaction");
+            }
+            Some(
+                self.transaction
+                    .get_or_insert_with(MemoryTransaction::default),
+            )
+        } else {
+            None
+        };
+        MemoryDataRef { data, txn }
+    }
+
+    async fn profile_key(&mut self) -> Result<(ProfileId, Arc<StoreKey>)> {
+        if let Some(ret) = self.profile_key.as_ref() {
+            return Ok(ret.clone());
+        }
+        let ret = if let Some(ret) = self.key_cache.get_profile(&self.profile).await {
+            ret
+        } else {
+            let profile = self.profile.clone();
+            let (pid, enc_key) = self
+                .data()
+                .await
+                .get()
+                .profiles
+                .get(&profile)
+                .cloned()
+                .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+            let key = Arc::new(self.key_cache.load_key(enc_key).await?);
+            self.key_cache.add_profile(profile, pid, key.clone()).await;
+            (pid, key)
+        };
+        self.profile_key.replace(ret.clone());
+        Ok(ret)
+    }
+
+    async fn encrypt_category(
+        &mut self,
+        category: &str,
+        tag_filter: Option<TagFilter>,
+    ) -> Result<(ProfileId, Vec<u8>, Option<TagMatch>)> {
+        let (profile_id, key) = self.profile_key().await?;
+        let category = StoreKey::prepare_input(category.as_bytes());
+        let (enc_category, tag_match) = unblock(move || {
+            Result::Ok((
+                key.encrypt_entry_category(category)?,
+                encode_tag_match(tag_filter, &key)?,
+            ))
+        })
+        .await?;
+        Ok((profile_id, enc_category, tag_match))
+    }
+
+    async fn find_entries(
+        &mut self,
+        kind: EntryKind,
+        category: &str,
+        tag_filter: Option<TagFilter>,
+        offset: Option<i64>,
+        limit: Option<i64>,
+        for_update: bool,
+    ) -> Result<Vec<(Vec<u8>, MemoryItem)>> {
+        let (profile_id, enc_category, tag_match) =
+            self.encrypt_category(category, tag_filter).await?;
+        let mut data = self.data().await;
+        let found = data
+            .find_items(profile_id, kind, &enc_category, tag_match.as_ref(), true)
+            .into_iter()
+            .skip(offset.unwrap_or(0).max(0) as usize)
+            .take(limit.map(|l| l.max(0) as usize).unwrap_or(usize::MAX))
+            .map(|(key, item)| (key.clone(), item.clone()))
+            .collect::<Vec<_>>();
+        if for_update {
+            for (key, _) in found.iter() {
+                data.track(key);
+            }
+        }
+        Ok(found.into_iter().map(|(key, item)| (key.3, item)).collect())
+    }
+}
+
+impl Debug for MemorySession {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySession")
+            .field("profile", &self.profile)
+            .field("is_transaction", &self.is_transaction)
+            .field("active", &self.transaction.is_some())
+            .finish()
+    }
+}
+
+impl QueryBackend for MemorySession {
+    fn count<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+    ) -> BoxFuture<'q, Result<i64>> {
+        Box::pin(async move {
+            let (profile_id, enc_category, tag_match) =
+                self.encrypt_category(category, tag_filter).await?;
+            let data = self.data().await;
+            let count = data
+                .find_items(profile_id, kind, &enc_category, tag_match.as_ref(), true)
+                .len();
+            Ok(count as i64)
+        })
+    }
+
+    fn fetch(
+        &mut self,
+        kind: EntryKind,
+        category: &str,
+        name: &str,
+        for_update: bool,
+    ) -> BoxFuture<'_, Result<Option<Entry>>> {
+        let category = category.to_string();
+        let name = name.to_string();
+
+        Box::pin(async move {
+            let (profile_id, key) = self.profile_key().await?;
+            let (enc_category, enc_name) = unblock({
+                let key = key.clone();
+                let category = StoreKey::prepare_input(category.as_bytes());
+                let name = StoreKey::prepare_input(name.as_bytes());
+                move || {
+                    Result::Ok((
+                        key.encrypt_entry_category(category)?,
+                        key.encrypt_entry_name(name)?,
+                    ))
+                }
+            })
+            .await?;
+            let item_key = (profile_id, kind as i16, enc_category, enc_name);
+            let mut data = self.data().await;
+            if for_update {
+                data.track(&item_key);
+            }
+            let found = data
+                .item(&item_key)
+                .filter(|item| !item.is_deleted() && !item.is_expired(&chrono::Utc::now()))
+                .cloned();
+            drop(data);
+            if let Some(item) = found {
+                let (value, tags) = unblock(move || {
+                    let value = key.decrypt_entry_value(item.value)?;
+                    let tags = if item.tags.is_empty() {
+                        None
+                    } else {
+                        Some(key.decrypt_entry_tags(item.tags)?)
+                    };
+                    Result::Ok((value, tags))
+                })
+                .await?;
+                Ok(Some(Entry::new(category, name, value, tags)))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    fn fetch_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+        limit: Option<i64>,
+        for_update: bool,
+    ) -> BoxFuture<'q, Result<Vec<Entry>>> {
+        let category = category.to_string();
+        Box::pin(async move {
+            let enc_rows = self
+                .find_entries(kind, &category, tag_filter, None, limit, for_update)
+                .await?;
+            let key = self.profile_key().await?.1;
+            unblock(move || decrypt_entries(category, enc_rows, &key)).await
+        })
+    }
+
+    fn remove_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+    ) -> BoxFuture<'q, Result<i64>> {
+        Box::pin(async move {
+            let (profile_id, enc_category, tag_match) =
+                self.encrypt_category(category, tag_filter).await?;
+            let soft_delete = self.soft_delete.applies(category);
+            let history = self.history.clone();
+            let mut data = self.data().await;
+            let keys = data
+                .find_items(profile_id, kind, &enc_category, tag_match.as_ref(), false)
+                .into_iter()
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+            for key in keys.iter() {
+                if history.applies(category) {
+                    data.record_history(key, history.max_versions);
+                }
+                if soft_delete {
+                    data.mark_deleted(key);
+                } else {
+                    data.remove_item(key);
+                }
+            }
+            if !keys.is_empty() && kind == EntryKind::Item {
+                data.publish_change(
+                    profile_id,
+                    EntryChange {
+                        operation: EntryOperation::Remove,
+                        category: category.to_string(),
+                        name: None,
+                    },
+                );
+            }
+            Ok(keys.len() as i64)
+        })
+    }
+
+    fn fetch_history<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<Entry>>> {
+        let category = category.to_string();
+        let name = name.to_string();
+
+        Box::pin(async move {
+            let (profile_id, key) = self.profile_key().await?;
+            let (enc_category, enc_name) = unblock({
+                let key = key.clone();
+                let category = StoreKey::prepare_input(category.as_bytes());
+                let name = StoreKey::prepare_input(name.as_bytes());
+                move || {
+                    Result::Ok((
+                        key.encrypt_entry_category(category)?,
+                        key.encrypt_entry_name(name)?,
+                    ))
+                }
+            })
+            .await?;
+            let item_key = (profile_id, kind as i16, enc_category, enc_name);
+            let enc_rows = self
+                .data()
+                .await
+                .versions(&item_key)
+                .map(|versions| {
+                    versions
+                        .iter()
+                        .rev()
+                        .map(|item| (item_key.3.clone(), item.clone()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            unblock(move || decrypt_entries(category, enc_rows, &key)).await
+        })
+    }
+
+    fn undelete<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+    ) -> BoxFuture<'q, Result<()>> {
+        let change = EntryChange {
+            operation: EntryOperation::Insert,
+            category: category.to_string(),
+            name: Some(name.to_string()),
+        };
+        let category = StoreKey::prepare_input(category.as_bytes());
+        let name = StoreKey::prepare_input(name.as_bytes());
+
+        Box::pin(async move {
+            let (profile_id, key) = self.profile_key().await?;
+            let (enc_category, enc_name) = unblock(move || {
+                Result::Ok((
+                    key.encrypt_entry_category(category)?,
+                    key.encrypt_entry_name(name)?,
+                ))
+            })
+            .await?;
+            let item_key = (profile_id, kind as i16, enc_category, enc_name);
+            let mut data = self.data().await;
+            let value_len = match data.item(&item_key) {
+                Some(item) if item.is_deleted() => item.value.len(),
+                _ => return Err(err_msg!(NotFound, "Deleted entry not found")),
+            };
+            // the restored entry counts toward the profile quota again
+            data.check_quota(profile_id, value_len, None)?;
+            trace!("Restore entry");
+            data.restore_item(&item_key);
+            if kind == EntryKind::Item {
+                data.publish_change(profile_id, change);
+            }
+            Ok(())
+        })
+    }
+
+    fn update<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        operation: EntryOperation,
+        category: &'q str,
+        name: &'q str,
+        value: Option<&'q [u8]>,
+        tags: Option<&'q [EntryTag]>,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<()>> {
+        let change = move |operation| EntryChange {
+            operation,
+            category: category.to_string(),
+            name: Some(name.to_string()),
+        };
+        let soft_delete = self.soft_delete.applies(category);
+        let history = Some(self.history.max_versions).filter(|_| self.history.applies(category));
+        let category = StoreKey::prepare_input(category.as_bytes());
+        let name = StoreKey::prepare_input(name.as_bytes());
+
+        match operation {
+            op @ EntryOperation::Insert | op @ EntryOperation::Replace => {
+                let value = StoreKey::prepare_input(value.unwrap());
+                let tags = tags.map(|t| t.to_vec());
+                Box::pin(async move {
+                    let (profile_id, key) = self.profile_key().await?;
+                    let (enc_category, enc_name, enc_value, enc_tags) = unblock(move || {
+                        Result::Ok((
+                            key.encrypt_entry_category(category)?,
+                            key.encrypt_entry_name(name)?,
+                            key.encrypt_entry_value(value)?,
+                            tags.map(|t| key.encrypt_entry_tags(t)).transpose()?,
+                        ))
+                    })
+                    .await?;
+                    let expiry = expiry_ms.map(expiry_timestamp).transpose()?;
+                    let item_key = (profile_id, kind as i16, enc_category, enc_name);
+                    let mut data = self.data().await;
+                    // an expired or deleted item is overwritten by an insert,
+                    // and does not count toward the profile quota
+                    let now = chrono::Utc::now();
+                    let existing = data.item(&item_key);
+                    let replaced = match existing.filter(|item| !item.is_deleted()) {
+                        Some(item) if item.is_expired(&now) => None,
+                        Some(item) if op == EntryOperation::Replace => Some(item.value.len()),
+                        Some(_) => {
+                            return Err(err_msg!(Duplicate, "Duplicate row"));
+                        }
+                        None if op == EntryOperation::Replace => {
+                            return Err(err_msg!(NotFound, "Entry not found"));
+                        }
+                        None => None,
+                    };
+                    data.check_quota(profile_id, enc_value.len(), replaced)?;
+                    if let (EntryOperation::Replace, Some(max_versions)) = (op, history) {
+                        data.record_history(&item_key, max_versions);
+                    }
+                    let id = data.next_item_id();
+                    trace!("Insert entry");
+                    data.insert_item(
+                        item_key,
+                        MemoryItem {
+                            id,
+                            value: enc_value,
+                            tags: enc_tags.unwrap_or_default(),
+                            expiry,
+                            deleted: None,
+                        },
+                    );
+                    if kind == EntryKind::Item {
+                        data.publish_change(profile_id, change(op));
+                    }
+                    Ok(())
+                })
+            }
+
+            EntryOperation::Remove => Box::pin(async move {
+                let (profile_id, key) = self.profile_key().await?;
+                let (enc_category, enc_name) = unblock(move || {
+                    Result::Ok((
+                        key.encrypt_entry_category(category)?,
+                        key.encrypt_entry_name(name)?,
+                    ))
+                })
+                .await?;
+                trace!("Remove entry");
+                let item_key = (profile_id, kind as i16, enc_category, enc_name);
+                let mut data = self.data().await;
+                if let Some(max_versions) = history {
+                    data.record_history(&item_key, max_versions);
+                }
+                let removed = if soft_delete {
+                    data.mark_deleted(&item_key)
+                } else {
+                    data.remove_item(&item_key)
+                };
+                if removed {
+                    if kind == EntryKind::Item {
+                        data.publish_change(profile_id, change(EntryOperation::Remove));
+                    }
+                    Ok(())
+                } else {
+                    Err(err_msg!(NotFound, "Entry not found"))
+                }
+            }),
+        }
+    }
+
+    fn set_audit_actor(&mut self, _actor: Option<String>) {}
+
+    fn set_profile(&mut self, profile: String) -> Result<()> {
+        self.profile = profile;
+        self.profile_key = None;
+        Ok(())
+    }
+
+    fn close(mut self, commit: bool) -> BoxFuture<'static, Result<()>> {
+        Box::pin(async move {
+            if let Some(txn) = self.transaction.take() {
+                if commit {
+                    info!("Commit transaction on close");
+                    txn.commit(&mut *self.data.lock().await)?;
+                } else {
+                    info!("Roll-back transaction on close");
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Drop for MemorySession {
+    fn drop(&mut self) {
+        if self.transaction.take().is_some() {
+            info!("Dropped transaction: roll-back");
+        }
+    }
+}
+
+fn expiry_timestamp(expire_ms: i64) -> Result<Expiry> {
+    chrono::Utc::now()
+        .checked_add_signed(chrono::Duration::milliseconds(expire_ms))
+        .ok_or_else(|| err_msg!(Unexpected, "Invalid expiry timestamp"))
+}
+
+fn decrypt_entries(
+    category: String,
+    enc_rows: Vec<(Vec<u8>, MemoryItem)>,
+    key: &StoreKey,
+) -> Result<Vec<Entry>> {
+    let mut batch = Vec::with_capacity(enc_rows.len());
+    for (enc_name, item) in enc_rows {
+        let name = key.decrypt_entry_name(enc_name)?;
+        let value = key.decrypt_entry_value(item.value)?;
+        let tags = if item.tags.is_empty() {
+            None
+        } else {
+            Some(key.decrypt_entry_tags(item.tags)?)
+        };
+        batch.push(Entry::new(category.clone(), name, value, tags));
+    }
+    Ok(batch)
+}
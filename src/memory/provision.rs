@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_mutex::Mutex;
+use once_cell::sync::Lazy;
+
+use super::{MemoryData, MemoryStore};
+use crate::error::Result;
+use crate::future::{unblock, BoxFuture};
+use crate::keys::{
+    store::StoreKey,
+    wrap::{WrapKey, WrapKeyMethod, WrapKeyReference},
+    KeyCache, PassKey,
+};
+use crate::options::{IntoOptions, Options};
+use crate::store::{ManageBackend, Store};
+
+/// Named in-memory stores, which may be re-opened until they are removed
+static STORES: Lazy<Mutex<HashMap<String, Arc<Mutex<MemoryData>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Configuration options for in-memory stores
+#[derive(Debug)]
+pub struct MemoryStoreOptions {
+    pub(crate) name: Option<String>,
+}
+
+impl MemoryStoreOptions {
+    /// Initialize `MemoryStoreOptions` from a generic set of options
+    ///
+    /// A store provisioned with a name (as in `memory://name`) remains available to be
+    /// opened by other callers in the same process until it is removed. A store without
+    /// a name is dropped along with the last reference to it.
+    pub fn new<'a>(options: impl IntoOptions<'a>) -> Result<Self> {
+        let opts = options.into_options()?;
+        let mut name = opts.host.to_string();
+        name.push_str(&*opts.path);
+        Ok(Self {
+            name: if name.is_empty() { None } else { Some(name) },
+        })
+    }
+
+    /// Provision a new in-memory store from these configuration options
+    pub async fn provision(
+        self,
+        method: WrapKeyMethod,
+        pass_key: PassKey<'_>,
+        profile: Option<&'_ str>,
+        recreate: bool,
+    ) -> Result<Store<MemoryStore>> {
+        if let Some(name) = self.name.as_ref() {
+            if !recreate && STORES.lock().await.contains_key(name) {
+                return self.open(Some(method), pass_key, profile).await;
+            }
+        }
+
+        let default_profile = profile
+            .map(str::to_string)
+            .unwrap_or_else(random_profile_name);
+        let (store_key, enc_store_key, wrap_key, wrap_key_ref) = unblock({
+            let pass_key = pass_key.into_owned();
+            move || init_keys(method, pass_key)
+        })
+        .await?;
+        let mut data = MemoryData {
+            wrap_key_ref,
+            default_profile: default_profile.clone(),
+            ..Default::default()
+        };
+        let profile_id = data
+            .add_profile(default_profile.clone(), enc_store_key)
+            .unwrap();
+        let data = Arc::new(Mutex::new(data));
+        if let Some(name) = self.name.as_ref() {
+            STORES.lock().await.insert(name.clone(), data.clone());
+        }
+
+        let mut key_cache = KeyCache::new(wrap_key);
+        key_cache.add_profile_mut(default_profile.clone(), profile_id, store_key);
+
+        Ok(Store::new(MemoryStore::new(
+            data,
+            default_profile,
+            key_cache,
+            self.name,
+        )))
+    }
+
+    /// Open an existing in-memory store from this set of configuration options
+    pub async fn open(
+        self,
+        method: Option<WrapKeyMethod>,
+        pass_key: PassKey<'_>,
+        profile: Option<&'_ str>,
+    ) -> Result<Store<MemoryStore>> {
+        let data = match self.name.as_ref() {
+            Some(name) => STORES.lock().await.get(name).cloned(),
+            None => None,
+        }
+        .ok_or_else(|| err_msg!(NotFound, "The requested store was not found"))?;
+
+        let (wrap_key_ref, profile, profile_key) = {
+            let data = data.lock().await;
+            let profile = profile
+                .map(str::to_string)
+                .unwrap_or_else(|| data.default_profile.clone());
+            let profile_key = data
+                .profiles
+                .get(&profile)
+                .cloned()
+                .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+            (data.wrap_key_ref.clone(), profile, profile_key)
+        };
+        let wrap_ref = WrapKeyReference::parse_uri(&wrap_key_ref)?;
+        if let Some(method) = method {
+            if !wrap_ref.compare_method(&method) {
+                return Err(err_msg!(Input, "Store key wrap method mismatch"));
+            }
+        }
+        let wrap_key = unblock({
+            let pass_key = pass_key.into_owned();
+            move || wrap_ref.resolve(pass_key)
+        })
+        .await?;
+        let mut key_cache = KeyCache::new(wrap_key);
+        let (profile_id, enc_key) = profile_key;
+        let store_key = key_cache.load_key(enc_key).await?;
+        key_cache.add_profile_mut(profile.clone(), profile_id, store_key);
+
+        Ok(Store::new(MemoryStore::new(
+            data, profile, key_cache, self.name,
+        )))
+    }
+
+    /// Remove the in-memory store defined by these configuration options
+    pub async fn remove(self) -> Result<bool> {
+        if let Some(name) = self.name {
+            Ok(STORES.lock().await.remove(&name).is_some())
+        } else {
+            Ok(true)
+        }
+    }
+
+    /// Default options for an unnamed in-memory store
+    pub fn in_memory() -> Self {
+        Self::new(Options::default()).unwrap()
+    }
+}
+
+impl<'a> ManageBackend<'a> for MemoryStoreOptions {
+    type Store = Store<MemoryStore>;
+
+    fn open_backend(
+        self,
+        method: Option<WrapKeyMethod>,
+        pass_key: PassKey<'a>,
+        profile: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Store<MemoryStore>>> {
+        Box::pin(self.open(method, pass_key, profile))
+    }
+
+    fn provision_backend(
+        self,
+        method: WrapKeyMethod,
+        pass_key: PassKey<'a>,
+        profile: Option<&'a str>,
+        recreate: bool,
+    ) -> BoxFuture<'a, Result<Store<MemoryStore>>> {
+        Box::pin(self.provision(method, pass_key, profile, recreate))
+    }
+
+    fn remove_backend(self) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(self.remove())
+    }
+}
+
+fn init_keys(
+    method: WrapKeyMethod,
+    pass_key: PassKey<'_>,
+) -> Result<(StoreKey, Vec<u8>, WrapKey, String)> {
+    let (wrap_key, wrap_key_ref) = method.resolve(pass_key)?;
+    let store_key = StoreKey::new()?;
+    let enc_store_key = encode_store_key(&store_key, &wrap_key)?;
+    Ok((store_key, enc_store_key, wrap_key, wrap_key_ref.into_uri()))
+}
+
+pub(super) fn encode_store_key(store_key: &StoreKey, wrap_key: &WrapKey) -> Result<Vec<u8>> {
+    let enc_store_key = store_key.to_string()?;
+    let result = wrap_key.wrap_data(enc_store_key.into())?;
+    Ok(result)
+}
+
+#[inline]
+pub(super) fn random_profile_name() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 use std::fmt::{self, Debug, Formatter};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use async_stream::try_stream;
 
@@ -11,10 +12,12 @@ use futures_lite::{
 
 use sqlx::{
     pool::PoolConnection,
-    postgres::{PgPool, Postgres},
+    postgres::{PgConnection, PgListener, PgPool, Postgres},
     Row,
 };
 
+use super::audit::{AuditEvent, AuditLog, AUDIT_KEY_CONFIG};
+use super::cache::{CacheKey, CacheTarget, EntryCache};
 use super::db_utils::{
     decode_tags, decrypt_scan_batch, encode_store_key, encode_tag_filter, expiry_timestamp,
     extend_query, prepare_tags, random_profile_name, replace_arg_placeholders, DbSession,
@@ -22,29 +25,46 @@ use super::db_utils::{
 };
 use super::error::Result;
 use super::future::{unblock, BoxFuture};
-use super::keys::{store::StoreKey, wrap::WrapKeyMethod, EntryEncryptor, KeyCache, PassKey};
-use super::store::{Backend, QueryBackend, Scan};
-use super::types::{EncEntryTag, Entry, EntryKind, EntryOperation, EntryTag, ProfileId, TagFilter};
+use super::keys::{
+    store::StoreKey,
+    wrap::{WrapKey, WrapKeyMethod},
+    EntryEncryptor, KeyCache, PassKey,
+};
+use super::store::{Backend, ChangeStream, QueryBackend, Scan};
+use super::types::{
+    AuditOperation, AuditRecord, EncEntryTag, Entry, EntryCacheStats, EntryChange, EntryHistory,
+    EntryKind, EntryOperation, EntryTag, ProfileId, ProfileQuota, ProfileUsage, SoftDelete,
+    TagFilter,
+};
 
 const COUNT_QUERY: &'static str = "SELECT COUNT(*) FROM items i
     WHERE profile_id = $1 AND kind = $2 AND category = $3
-    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
+    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
 const DELETE_QUERY: &'static str = "DELETE FROM items
-    WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4";
+    WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = items.id)";
+const DELETE_DELETED_QUERY: &'static str = "DELETE FROM items
+    WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
+    AND EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = items.id)";
 const FETCH_QUERY: &'static str = "SELECT id, value,
     (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
         || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
-        FROM items_tags it WHERE it.item_id = i.id) tags
+        FROM items_tags it WHERE it.item_id = i.id) tags,
+    expiry IS NULL AS cacheable
     FROM items i
     WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
-    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
+    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
 const FETCH_QUERY_UPDATE: &'static str = "SELECT id, value,
     (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
         || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
-        FROM items_tags it WHERE it.item_id = i.id) tags
+        FROM items_tags it WHERE it.item_id = i.id) tags,
+    expiry IS NULL AS cacheable
     FROM items i
     WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
-    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP) FOR UPDATE";
+    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id) FOR UPDATE";
 const INSERT_QUERY: &'static str =
     "INSERT INTO items (profile_id, kind, category, name, value, expiry)
     VALUES ($1, $2, $3, $4, $5, $6)
@@ -54,11 +74,125 @@ const SCAN_QUERY: &'static str = "SELECT id, name, value,
         || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
         FROM items_tags it WHERE it.item_id = i.id) tags
     FROM items i WHERE profile_id = $1 AND kind = $2 AND category = $3
-    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
+    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
 const DELETE_ALL_QUERY: &'static str = "DELETE FROM items i
-    WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3";
+    WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const SOFT_DELETE_QUERY: &'static str = "INSERT INTO items_deleted (item_id, deleted)
+    SELECT i.id, CURRENT_TIMESTAMP FROM items i
+    WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3 AND i.name = $4
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const SOFT_DELETE_ALL_QUERY: &'static str = "INSERT INTO items_deleted (item_id, deleted)
+    SELECT i.id, CURRENT_TIMESTAMP FROM items i
+    WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const UNDELETE_QUERY: &'static str = "DELETE FROM items_deleted
+    WHERE item_id IN (SELECT id FROM items
+        WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4)";
+const DELETED_SIZE_QUERY: &'static str = "SELECT OCTET_LENGTH(i.value)::BIGINT FROM items i
+    JOIN items_deleted d ON d.item_id = i.id
+    WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3 AND i.name = $4";
+const PURGE_CATEGORIES_QUERY: &'static str = "SELECT DISTINCT i.category FROM items i
+    JOIN items_deleted d ON d.item_id = i.id
+    WHERE i.profile_id = $1 AND i.kind = $2
+    AND d.deleted <= CURRENT_TIMESTAMP - $3 * INTERVAL '1 second'";
+const PURGE_DELETED_QUERY: &'static str = "DELETE FROM items WHERE profile_id = $1
+    AND id IN (SELECT item_id FROM items_deleted
+        WHERE deleted <= CURRENT_TIMESTAMP - $2 * INTERVAL '1 second')";
+const CHANGES_CHANNEL: &'static str = "askar_changes";
+// listeners hold a shared advisory lock on the key 0x61736b61 for the lifetime of
+// their connection, and changes are only recorded while such a lock is held.
+// notifications carry the profile and change identifiers, as the payload size
+// is limited
+const LISTENER_LOCK_QUERY: &'static str = "SELECT pg_advisory_lock_shared(1634954081)";
+const NOTIFY_QUERY: &'static str = "WITH listening AS (
+        SELECT 1 FROM pg_locks
+        WHERE locktype = 'advisory' AND classid = 0 AND objid = 1634954081 AND objsubid = 1
+        AND database = (SELECT oid FROM pg_database WHERE datname = CURRENT_DATABASE())
+        LIMIT 1
+    ), pruned AS (
+        DELETE FROM items_changes WHERE created < CURRENT_TIMESTAMP - INTERVAL '5 minutes'
+        AND EXISTS (SELECT 1 FROM listening)
+    ), change AS (
+        INSERT INTO items_changes (profile_id, operation, category, name, created)
+        SELECT $1, $2, $3, $4, CURRENT_TIMESTAMP FROM listening
+        RETURNING id
+    )
+    SELECT pg_notify('askar_changes', $1::text || ':' || id::text) FROM change";
+const CHANGE_FETCH_QUERY: &'static str = "SELECT operation, category, name
+    FROM items_changes WHERE id = $1";
+const CHANGE_PRUNE_QUERY: &'static str = "DELETE FROM items_changes
+    WHERE created < CURRENT_TIMESTAMP - INTERVAL '5 minutes'";
 const TAG_INSERT_QUERY: &'static str = "INSERT INTO items_tags
     (item_id, name, value, plaintext) VALUES ($1, $2, $3, $4)";
+const HISTORY_INSERT_QUERY: &'static str = "INSERT INTO items_history
+    (profile_id, kind, category, name, value, tags, created)
+    SELECT i.profile_id, i.kind, i.category, i.name, i.value,
+    (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
+        || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
+        FROM items_tags it WHERE it.item_id = i.id),
+    CURRENT_TIMESTAMP
+    FROM items i WHERE i.profile_id = $1 AND i.kind = $2
+    AND i.category = $3 AND i.name = $4
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const HISTORY_INSERT_ALL_QUERY: &'static str = "INSERT INTO items_history
+    (profile_id, kind, category, name, value, tags, created)
+    SELECT i.profile_id, i.kind, i.category, i.name, i.value,
+    (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
+        || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
+        FROM items_tags it WHERE it.item_id = i.id),
+    CURRENT_TIMESTAMP
+    FROM items i WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const HISTORY_TRIM_QUERY: &'static str = "DELETE FROM items_history
+    WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
+    AND id <= (SELECT id FROM items_history
+        WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
+        ORDER BY id DESC LIMIT 1 OFFSET $5)";
+const HISTORY_TRIM_ALL_QUERY: &'static str = "DELETE FROM items_history
+    WHERE id IN (SELECT id FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY name ORDER BY id DESC) AS version
+        FROM items_history WHERE profile_id = $1 AND kind = $2 AND category = $3
+    ) AS h WHERE h.version > $4)";
+const HISTORY_FETCH_QUERY: &'static str = "SELECT name, value, tags FROM items_history
+    WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
+    ORDER BY id DESC";
+const QUOTA_QUERY: &'static str = "SELECT max_items, max_bytes, used_items, used_bytes
+    FROM profiles_quotas WHERE profile_id = $1 FOR UPDATE";
+const QUOTA_UPSERT_QUERY: &'static str =
+    "INSERT INTO profiles_quotas (profile_id, max_items, max_bytes, used_items, used_bytes)
+    SELECT p.id, $2, $3,
+        (SELECT COUNT(*) FROM items i WHERE i.profile_id = p.id
+            AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)),
+        (SELECT COALESCE(SUM(OCTET_LENGTH(i.value)), 0) FROM items i WHERE i.profile_id = p.id
+            AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id))
+    FROM profiles p WHERE p.name = $1
+    ON CONFLICT (profile_id) DO UPDATE
+    SET max_items = excluded.max_items, max_bytes = excluded.max_bytes";
+const USAGE_QUERY: &'static str = "SELECT used_items, used_bytes FROM profiles_quotas
+    WHERE profile_id = $1";
+const EXPIRED_PURGE_QUERY: &'static str = "DELETE FROM items
+    WHERE profile_id = $1 AND expiry <= CURRENT_TIMESTAMP";
+const PROFILE_USAGE_QUERY: &'static str = "SELECT
+    (SELECT COUNT(*) FROM items i WHERE profile_id = p.id
+        AND (i.expiry IS NULL OR i.expiry > CURRENT_TIMESTAMP)
+        AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)),
+    (SELECT COALESCE(SUM(OCTET_LENGTH(value)), 0) FROM items i WHERE profile_id = p.id
+        AND (i.expiry IS NULL OR i.expiry > CURRENT_TIMESTAMP)
+        AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)),
+    q.max_items, q.max_bytes
+    FROM profiles p LEFT JOIN profiles_quotas q ON q.profile_id = p.id
+    WHERE p.name = $1";
+/// Advisory lock key used to serialize appends to the audit log
+const AUDIT_LOCK_ID: i64 = 0x6173_6b61_7261_7564;
+const AUDIT_LOCK_QUERY: &'static str = "SELECT pg_advisory_xact_lock($1)";
+const AUDIT_LAST_QUERY: &'static str = "SELECT id, mac FROM audit_log ORDER BY id DESC LIMIT 1";
+const AUDIT_INSERT_QUERY: &'static str =
+    "INSERT INTO audit_log (id, data, mac) VALUES ($1, $2, $3)";
+const AUDIT_MAC_QUERY: &'static str = "SELECT mac FROM audit_log WHERE id = $1";
+const AUDIT_SCAN_QUERY: &'static str = "SELECT id, data, mac FROM audit_log
+    WHERE id >= $1 AND id < $2 ORDER BY id";
 
 mod provision;
 pub use provision::PostgresStoreOptions;
@@ -73,6 +207,11 @@ pub struct PostgresStore {
     key_cache: Arc<KeyCache>,
     host: String,
     name: String,
+    timeout: Option<Duration>,
+    entry_cache: Option<Arc<EntryCache>>,
+    audit_log: Option<Arc<AuditLog>>,
+    soft_delete: RwLock<Arc<SoftDelete>>,
+    history: RwLock<Arc<EntryHistory>>,
 }
 
 impl PostgresStore {
@@ -82,6 +221,9 @@ impl PostgresStore {
         key_cache: KeyCache,
         host: String,
         name: String,
+        timeout: Option<Duration>,
+        entry_cache_size: usize,
+        audit_log: Option<AuditLog>,
     ) -> Self {
         Self {
             conn_pool,
@@ -89,8 +231,28 @@ impl PostgresStore {
             key_cache: Arc::new(key_cache),
             host,
             name,
+            timeout,
+            entry_cache: EntryCache::new(entry_cache_size).map(Arc::new),
+            audit_log: audit_log.map(Arc::new),
+            soft_delete: RwLock::new(Arc::new(SoftDelete::default())),
+            history: RwLock::new(Arc::new(EntryHistory::default())),
         }
     }
+
+    /// Prepare an audit log event for a store-level operation, if audit
+    /// logging is enabled
+    fn audit_event(
+        &self,
+        operation: AuditOperation,
+        profile: Option<&str>,
+    ) -> Option<(Arc<AuditLog>, AuditEvent)> {
+        self.audit_log.as_ref().map(|log| {
+            (
+                log.clone(),
+                AuditEvent::new(operation, profile, log.actor()),
+            )
+        })
+    }
 }
 
 impl Backend for PostgresStore {
@@ -101,16 +263,22 @@ impl Backend for PostgresStore {
         Box::pin(async move {
             let key = StoreKey::new()?;
             let enc_key = key.to_string()?;
-            let mut conn = self.conn_pool.acquire().await?;
+            let mut txn = self.conn_pool.begin().await?;
             if let Some(pid) = sqlx::query_scalar(
                 "INSERT INTO profiles (name, store_key) VALUES ($1, $2) 
                 ON CONFLICT DO NOTHING RETURNING id",
             )
             .bind(&name)
             .bind(enc_key.as_bytes())
-            .fetch_optional(&mut conn)
+            .fetch_optional(&mut txn)
             .await?
             {
+                if let Some((log, event)) =
+                    self.audit_event(AuditOperation::CreateProfile, Some(&name))
+                {
+                    append_audit(&mut txn, &log, event).await?;
+                }
+                txn.commit().await?;
                 self.key_cache
                     .add_profile(name.clone(), pid, Arc::new(key))
                     .await;
@@ -127,13 +295,69 @@ impl Backend for PostgresStore {
 
     fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool>> {
         Box::pin(async move {
-            let mut conn = self.conn_pool.acquire().await?;
-            Ok(sqlx::query("DELETE FROM profiles WHERE name=$1")
+            let mut txn = self.conn_pool.begin().await?;
+            let removed = sqlx::query("DELETE FROM profiles WHERE name=$1")
                 .bind(&name)
+                .execute(&mut txn)
+                .await?
+                .rows_affected()
+                != 0;
+            if let (true, Some((log, event))) = (
+                removed,
+                self.audit_event(AuditOperation::RemoveProfile, Some(&name)),
+            ) {
+                append_audit(&mut txn, &log, event).await?;
+            }
+            txn.commit().await?;
+            if removed {
+                if let Some(cache) = self.entry_cache.as_ref() {
+                    cache.clear();
+                }
+            }
+            Ok(removed)
+        })
+    }
+
+    fn set_profile_quota(
+        &self,
+        profile: Option<String>,
+        quota: ProfileQuota,
+    ) -> BoxFuture<'_, Result<()>> {
+        let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+        Box::pin(async move {
+            let mut conn = self.conn_pool.acquire().await?;
+            if sqlx::query(QUOTA_UPSERT_QUERY)
+                .bind(&profile)
+                .bind(quota.max_items)
+                .bind(quota.max_bytes)
                 .execute(&mut conn)
                 .await?
                 .rows_affected()
-                != 0)
+                == 0
+            {
+                return Err(err_msg!(NotFound, "Profile not found"));
+            }
+            Ok(())
+        })
+    }
+
+    fn profile_usage(&self, profile: Option<String>) -> BoxFuture<'_, Result<ProfileUsage>> {
+        let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+        Box::pin(async move {
+            let mut conn = self.conn_pool.acquire().await?;
+            let row = sqlx::query(PROFILE_USAGE_QUERY)
+                .bind(&profile)
+                .fetch_optional(&mut conn)
+                .await?
+                .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+            Ok(ProfileUsage {
+                items: row.try_get(0)?,
+                bytes: row.try_get(1)?,
+                quota: ProfileQuota {
+                    max_items: row.try_get(2)?,
+                    max_bytes: row.try_get(3)?,
+                },
+            })
         })
     }
 
@@ -183,6 +407,29 @@ impl Backend for PostgresStore {
             {
                 return Err(err_msg!(Backend, "Error updating wrap key"));
             }
+            // the audit key is re-wrapped even when audit logging is not enabled
+            // for this instance, so that it remains readable
+            if let Some(enc_key) =
+                sqlx::query_scalar::<_, String>("SELECT value FROM config WHERE name = $1")
+                    .bind(AUDIT_KEY_CONFIG)
+                    .fetch_optional(&mut txn)
+                    .await?
+            {
+                let upd_key = unblock({
+                    let old_key = self.key_cache.wrap_key.clone();
+                    let wrap_key = wrap_key.clone();
+                    move || rewrap_audit_key(&enc_key, &old_key, &wrap_key)
+                })
+                .await?;
+                sqlx::query("UPDATE config SET value = $1 WHERE name = $2")
+                    .bind(upd_key)
+                    .bind(AUDIT_KEY_CONFIG)
+                    .execute(&mut txn)
+                    .await?;
+            }
+            if let Some((log, event)) = self.audit_event(AuditOperation::Rekey, None) {
+                append_audit(&mut txn, &log, event).await?;
+            }
             txn.commit().await?;
             self.key_cache = Arc::new(KeyCache::new(wrap_key));
             Ok(())
@@ -228,9 +475,135 @@ impl Backend for PostgresStore {
             self.key_cache.clone(),
             profile.unwrap_or_else(|| self.default_profile.clone()),
             transaction,
+            self.entry_cache.clone(),
+            self.audit_log.clone(),
+            self.soft_delete.read().unwrap().clone(),
+            self.history.read().unwrap().clone(),
         ))
     }
 
+    fn backup_to(&self, _path: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            Err(err_msg!(
+                Unsupported,
+                "Backups are not supported for PostgreSQL stores"
+            ))
+        })
+    }
+
+    fn entry_cache_stats(&self) -> Option<EntryCacheStats> {
+        self.entry_cache.as_ref().map(|cache| cache.stats())
+    }
+
+    fn default_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn subscribe(
+        &self,
+        profile: Option<String>,
+        category: Option<String>,
+    ) -> BoxFuture<'_, Result<ChangeStream>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let (profile_id, key) = acquire_key(&mut session).await?;
+            drop(session);
+            let listener = change_listener(&self.conn_pool).await?;
+            sqlx::query(CHANGE_PRUNE_QUERY)
+                .execute(&self.conn_pool)
+                .await?;
+            Ok(listen_changes(self.conn_pool.clone(), listener, profile_id, key, category).boxed())
+        })
+    }
+
+    fn scan_audit(
+        &self,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> BoxFuture<'_, Result<Vec<AuditRecord>>> {
+        Box::pin(async move {
+            let log = self
+                .audit_log
+                .clone()
+                .ok_or_else(|| err_msg!(Unsupported, "Audit logging is not enabled"))?;
+            let start = start.unwrap_or(1).max(1);
+            // read the anchor and the range from a single snapshot
+            let mut txn = self.conn_pool.begin().await?;
+            sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+                .execute(&mut txn)
+                .await?;
+            let prev_mac = if start > 1 {
+                sqlx::query_scalar(AUDIT_MAC_QUERY)
+                    .bind(start - 1)
+                    .fetch_optional(&mut txn)
+                    .await?
+            } else {
+                None
+            };
+            let rows = sqlx::query(AUDIT_SCAN_QUERY)
+                .bind(start)
+                .bind(end.unwrap_or(i64::MAX))
+                .fetch_all(&mut txn)
+                .await?
+                .into_iter()
+                .map(|row| Result::Ok((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?)))
+                .collect::<Result<Vec<_>>>()?;
+            txn.commit().await?;
+            unblock(move || log.open_rows(start, prev_mac, rows)).await
+        })
+    }
+
+    fn set_audit_actor(&self, actor: Option<String>) {
+        if let Some(log) = self.audit_log.as_ref() {
+            log.set_actor(actor);
+        }
+    }
+
+    fn set_soft_delete(&self, policy: SoftDelete) -> Result<()> {
+        *self.soft_delete.write().unwrap() = Arc::new(policy);
+        Ok(())
+    }
+
+    fn purge_deleted(
+        &self,
+        profile: Option<String>,
+        older_than: Duration,
+    ) -> BoxFuture<'_, Result<i64>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let (profile_id, _) = acquire_key(&mut session).await?;
+            let audit = session.audit_event(AuditOperation::PurgeDeleted);
+            let cutoff = older_than.as_secs() as i64;
+            let mut active = acquire_session(&mut session).await?;
+            let mut txn = active.as_transaction().await?;
+            let enc_categories: Vec<Vec<u8>> = sqlx::query_scalar(PURGE_CATEGORIES_QUERY)
+                .bind(profile_id)
+                .bind(EntryKind::Item as i16)
+                .bind(cutoff)
+                .fetch_all(txn.connection_mut())
+                .await?;
+            let purged = sqlx::query(PURGE_DELETED_QUERY)
+                .bind(profile_id)
+                .bind(cutoff)
+                .execute(txn.connection_mut())
+                .await?
+                .rows_affected();
+            for enc_category in enc_categories {
+                notify_change(&mut txn, EntryOperation::Remove, &enc_category, None).await?;
+            }
+            if let (true, Some((log, event))) = (purged > 0, audit) {
+                append_audit(txn.connection_mut(), &log, event).await?;
+            }
+            txn.commit().await?;
+            Ok(purged as i64)
+        })
+    }
+
+    fn set_entry_history(&self, policy: EntryHistory) -> Result<()> {
+        *self.history.write().unwrap() = Arc::new(policy);
+        Ok(())
+    }
+
     fn close(&self) -> BoxFuture<'_, Result<()>> {
         Box::pin(async move {
             self.conn_pool.close().await;
@@ -296,6 +669,16 @@ impl QueryBackend for DbSession<Postgres> {
 
         Box::pin(async move {
             let (profile_id, key) = acquire_key(&mut *self).await?;
+            let cached = match self.entry_cache() {
+                Some(cache) if !for_update => {
+                    let cache_key = CacheKey::new(profile_id, kind, &category, &name);
+                    if let Some(entry) = cache.get(&cache_key) {
+                        return Ok(Some(entry));
+                    }
+                    Some((cache.clone(), cache_key, cache.generation()))
+                }
+                _ => None,
+            };
             let (enc_category, enc_name) = unblock({
                 let key = key.clone();
                 let category = StoreKey::prepare_input(category.as_bytes());
@@ -323,6 +706,7 @@ impl QueryBackend for DbSession<Postgres> {
             {
                 let value = row.try_get(1)?;
                 let tags = row.try_get::<Option<String>, _>(2)?.map(String::into_bytes);
+                let cacheable = row.try_get::<bool, _>(3)?;
                 let (value, tags) = unblock(move || {
                     let value = key.decrypt_entry_value(value)?;
                     let tags = if let Some(enc_tags) = tags {
@@ -338,7 +722,12 @@ impl QueryBackend for DbSession<Postgres> {
                     Result::Ok((value, tags))
                 })
                 .await?;
-                Ok(Some(Entry::new(category, name, value, tags)))
+                let entry = Entry::new(category, name, value, tags);
+                if let (Some((cache, cache_key, generation)), true) = (cached, cacheable) {
+                    // entries with an expiry time are not cached
+                    cache.insert(cache_key, entry.clone(), generation);
+                }
+                Ok(Some(entry))
             } else {
                 Ok(None)
             }
@@ -388,6 +777,17 @@ impl QueryBackend for DbSession<Postgres> {
         category: &'q str,
         tag_filter: Option<TagFilter>,
     ) -> BoxFuture<'q, Result<i64>> {
+        let cache_target =
+            move |profile_id| CacheTarget::Category(profile_id, kind, category.to_string());
+        let audit = self
+            .audit_event(AuditOperation::RemoveAll)
+            .map(|(log, event)| (log, event.entry(category, None)));
+        let delete_query = if self.soft_delete(category) {
+            SOFT_DELETE_ALL_QUERY
+        } else {
+            DELETE_ALL_QUERY
+        };
+        let history = self.entry_history(category);
         let category = StoreKey::prepare_input(category.as_bytes());
 
         Box::pin(async move {
@@ -405,24 +805,165 @@ impl QueryBackend for DbSession<Postgres> {
                 }
             })
             .await?;
+            let notify_category = enc_category.clone();
+            let history = if let Some(max_versions) = history {
+                let mut params = QueryParams::new();
+                params.push(profile_id);
+                params.push(kind as i16);
+                params.push(enc_category.clone());
+                let query = extend_query::<PostgresStore>(
+                    HISTORY_INSERT_ALL_QUERY,
+                    &mut params,
+                    tag_filter.clone(),
+                    None,
+                    None,
+                )?;
+                Some((query, params, max_versions))
+            } else {
+                None
+            };
             params.push(enc_category);
-            let query = extend_query::<PostgresStore>(
-                DELETE_ALL_QUERY,
-                &mut params,
-                tag_filter,
-                None,
-                None,
-            )?;
+            let query =
+                extend_query::<PostgresStore>(delete_query, &mut params, tag_filter, None, None)?;
 
             let mut active = acquire_session(&mut *self).await?;
+            let mut txn = active.as_transaction().await?;
+            if let Some((history_query, history_params, max_versions)) = history {
+                trace!("Record entry history");
+                sqlx::query_with(history_query.as_str(), history_params)
+                    .execute(txn.connection_mut())
+                    .await?;
+                trim_history(&mut txn, kind, &notify_category, None, max_versions).await?;
+            }
             let removed = sqlx::query_with(query.as_str(), params)
-                .execute(active.connection_mut())
+                .execute(txn.connection_mut())
                 .await?
                 .rows_affected();
+            if removed > 0 && kind == EntryKind::Item {
+                notify_change(&mut txn, EntryOperation::Remove, &notify_category, None).await?;
+            }
+            if let (true, Some((log, event))) = (removed > 0, audit) {
+                append_audit(txn.connection_mut(), &log, event).await?;
+            }
+            txn.commit().await?;
+            drop(active);
+            self.invalidate_cache(cache_target(profile_id));
             Ok(removed as i64)
         })
     }
 
+    fn fetch_history<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<Entry>>> {
+        let category = category.to_string();
+        let name = name.to_string();
+
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let (enc_category, enc_name) = unblock({
+                let key = key.clone();
+                let category = StoreKey::prepare_input(category.as_bytes());
+                let name = StoreKey::prepare_input(name.as_bytes());
+                move || {
+                    Result::Ok((
+                        key.encrypt_entry_category(category)?,
+                        key.encrypt_entry_name(name)?,
+                    ))
+                }
+            })
+            .await?;
+            let mut active = acquire_session(&mut *self).await?;
+            let enc_rows = sqlx::query(HISTORY_FETCH_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+                .bind(enc_name)
+                .fetch_all(active.connection_mut())
+                .await?
+                .into_iter()
+                .map(|row| {
+                    let tags: Option<String> = row.try_get(2)?;
+                    Result::Ok(EncScanEntry {
+                        name: row.try_get(0)?,
+                        value: row.try_get(1)?,
+                        tags: tags.map(String::into_bytes),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            drop(active);
+            unblock(move || decrypt_scan_batch(category, enc_rows, &key)).await
+        })
+    }
+
+    fn undelete<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+    ) -> BoxFuture<'q, Result<()>> {
+        let cache_target =
+            move |profile_id| CacheTarget::Entry(CacheKey::new(profile_id, kind, category, name));
+        let audit = self
+            .audit_event(AuditOperation::Undelete)
+            .map(|(log, event)| (log, event.entry(category, Some(name))));
+        let category = StoreKey::prepare_input(category.as_bytes());
+        let name = StoreKey::prepare_input(name.as_bytes());
+
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let (enc_category, enc_name) = unblock(move || {
+                Result::Ok((
+                    key.encrypt_entry_category(category)?,
+                    key.encrypt_entry_name(name)?,
+                ))
+            })
+            .await?;
+            let mut active = acquire_session(&mut *self).await?;
+            let mut txn = active.as_transaction().await?;
+            let value_len: i64 = sqlx::query_scalar(DELETED_SIZE_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .bind(&enc_category)
+                .bind(&enc_name)
+                .fetch_optional(txn.connection_mut())
+                .await?
+                .ok_or_else(|| err_msg!(NotFound, "Deleted entry not found"))?;
+            // the restored entry counts toward the profile quota again
+            check_quota(&mut txn, value_len as usize).await?;
+            trace!("Restore entry");
+            let done = sqlx::query(UNDELETE_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .bind(&enc_category)
+                .bind(&enc_name)
+                .execute(txn.connection_mut())
+                .await?;
+            if done.rows_affected() == 0 {
+                // the entry had expired and was removed by the quota check
+                return Err(err_msg!(NotFound, "Deleted entry not found"));
+            }
+            if kind == EntryKind::Item {
+                notify_change(
+                    &mut txn,
+                    EntryOperation::Insert,
+                    &enc_category,
+                    Some(&enc_name),
+                )
+                .await?;
+            }
+            if let Some((log, event)) = audit {
+                append_audit(txn.connection_mut(), &log, event).await?;
+            }
+            txn.commit().await?;
+            drop(active);
+            self.invalidate_cache(cache_target(profile_id));
+            Ok(())
+        })
+    }
+
     fn update<'q>(
         &'q mut self,
         kind: EntryKind,
@@ -433,6 +974,13 @@ impl QueryBackend for DbSession<Postgres> {
         tags: Option<&'q [EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> BoxFuture<'q, Result<()>> {
+        let cache_target =
+            move |profile_id| CacheTarget::Entry(CacheKey::new(profile_id, kind, category, name));
+        let audit = self
+            .audit_event(operation.into())
+            .map(|(log, event)| (log, event.entry(category, Some(name))));
+        let soft_delete = self.soft_delete(category);
+        let history = self.entry_history(category);
         let category = StoreKey::prepare_input(category.as_bytes());
         let name = StoreKey::prepare_input(name.as_bytes());
 
@@ -441,7 +989,7 @@ impl QueryBackend for DbSession<Postgres> {
                 let value = StoreKey::prepare_input(value.unwrap());
                 let tags = tags.map(prepare_tags);
                 Box::pin(async move {
-                    let (_, key) = acquire_key(&mut *self).await?;
+                    let (profile_id, key) = acquire_key(&mut *self).await?;
                     let (enc_category, enc_name, enc_value, enc_tags) = unblock(move || {
                         Result::Ok((
                             key.encrypt_entry_category(category)?,
@@ -453,6 +1001,8 @@ impl QueryBackend for DbSession<Postgres> {
                     .await?;
                     let mut active = acquire_session(&mut *self).await?;
                     let mut txn = active.as_transaction().await?;
+                    // a deleted entry with the same name is erased by an insert
+                    perform_purge(&mut txn, kind, &enc_category, &enc_name).await?;
                     perform_insert(
                         &mut txn,
                         kind,
@@ -463,7 +1013,21 @@ impl QueryBackend for DbSession<Postgres> {
                         expiry_ms,
                     )
                     .await?;
+                    if kind == EntryKind::Item {
+                        notify_change(
+                            &mut txn,
+                            EntryOperation::Insert,
+                            &enc_category,
+                            Some(&enc_name),
+                        )
+                        .await?;
+                    }
+                    if let Some((log, event)) = audit {
+                        append_audit(txn.connection_mut(), &log, event).await?;
+                    }
                     txn.commit().await?;
+                    drop(active);
+                    self.invalidate_cache(cache_target(profile_id));
                     Ok(())
                 })
             }
@@ -471,7 +1035,7 @@ impl QueryBackend for DbSession<Postgres> {
                 let value = StoreKey::prepare_input(value.unwrap());
                 let tags = tags.map(prepare_tags);
                 Box::pin(async move {
-                    let (_, key) = acquire_key(&mut *self).await?;
+                    let (profile_id, key) = acquire_key(&mut *self).await?;
                     let (enc_category, enc_name, enc_value, enc_tags) = unblock(move || {
                         Result::Ok((
                             key.encrypt_entry_category(category)?,
@@ -484,6 +1048,10 @@ impl QueryBackend for DbSession<Postgres> {
 
                     let mut active = acquire_session(&mut *self).await?;
                     let mut txn = active.as_transaction().await?;
+                    if let Some(max_versions) = history {
+                        record_history(&mut txn, kind, &enc_category, &enc_name, max_versions)
+                            .await?;
+                    }
                     perform_remove(&mut txn, kind, &enc_category, &enc_name, false).await?;
                     perform_insert(
                         &mut txn,
@@ -495,13 +1063,27 @@ impl QueryBackend for DbSession<Postgres> {
                         expiry_ms,
                     )
                     .await?;
+                    if kind == EntryKind::Item {
+                        notify_change(
+                            &mut txn,
+                            EntryOperation::Replace,
+                            &enc_category,
+                            Some(&enc_name),
+                        )
+                        .await?;
+                    }
+                    if let Some((log, event)) = audit {
+                        append_audit(txn.connection_mut(), &log, event).await?;
+                    }
                     txn.commit().await?;
+                    drop(active);
+                    self.invalidate_cache(cache_target(profile_id));
                     Ok(())
                 })
             }
 
             EntryOperation::Remove => Box::pin(async move {
-                let (_, key) = acquire_key(&mut *self).await?;
+                let (profile_id, key) = acquire_key(&mut *self).await?;
                 let (enc_category, enc_name) = unblock(move || {
                     Result::Ok((
                         key.encrypt_entry_category(category)?,
@@ -510,13 +1092,46 @@ impl QueryBackend for DbSession<Postgres> {
                 })
                 .await?;
                 let mut active = acquire_session(&mut *self).await?;
-                Ok(perform_remove(&mut active, kind, &enc_category, &enc_name, false).await?)
+                let mut txn = active.as_transaction().await?;
+                if let Some(max_versions) = history {
+                    record_history(&mut txn, kind, &enc_category, &enc_name, max_versions).await?;
+                }
+                if soft_delete {
+                    perform_soft_delete(&mut txn, kind, &enc_category, &enc_name).await?;
+                } else {
+                    perform_remove(&mut txn, kind, &enc_category, &enc_name, false).await?;
+                }
+                if kind == EntryKind::Item {
+                    notify_change(
+                        &mut txn,
+                        EntryOperation::Remove,
+                        &enc_category,
+                        Some(&enc_name),
+                    )
+                    .await?;
+                }
+                if let Some((log, event)) = audit {
+                    append_audit(txn.connection_mut(), &log, event).await?;
+                }
+                txn.commit().await?;
+                drop(active);
+                self.invalidate_cache(cache_target(profile_id));
+                Ok(())
             }),
         }
     }
 
-    fn close(self, commit: bool) -> BoxFuture<'static, Result<()>> {
-        Box::pin(DbSession::close(self, commit))
+    fn set_audit_actor(&mut self, actor: Option<String>) {
+        DbSession::set_audit_actor(self, actor);
+    }
+
+    fn set_profile(&mut self, profile: String) -> Result<()> {
+        DbSession::set_profile(self, profile);
+        Ok(())
+    }
+
+    fn close(mut self, commit: bool) -> BoxFuture<'static, Result<()>> {
+        Box::pin(async move { DbSession::close(&mut self, commit).await })
     }
 }
 
@@ -587,6 +1202,71 @@ async fn resolve_profile_key(
     }
 }
 
+async fn append_audit(conn: &mut PgConnection, log: &AuditLog, event: AuditEvent) -> Result<()> {
+    // serialize appends so that each row is chained to the last committed row
+    sqlx::query(AUDIT_LOCK_QUERY)
+        .bind(AUDIT_LOCK_ID)
+        .execute(&mut *conn)
+        .await?;
+    let last = sqlx::query(AUDIT_LAST_QUERY)
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|row| Result::Ok((row.try_get(0)?, row.try_get(1)?)))
+        .transpose()?;
+    let (id, data, mac) = log.seal(last, event)?;
+    sqlx::query(AUDIT_INSERT_QUERY)
+        .bind(id)
+        .bind(data)
+        .bind(mac)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+fn rewrap_audit_key(enc_key: &str, old_key: &WrapKey, new_key: &WrapKey) -> Result<String> {
+    AuditLog::from_wrapped(enc_key, old_key)?.to_wrapped(new_key)
+}
+
+/// Check that an entry may be inserted without exceeding the profile quota
+///
+/// The quota row is locked for the remainder of the transaction, so that
+/// concurrent inserts into the same profile are checked in turn. Any entry
+/// being replaced must already have been removed within the transaction.
+async fn check_quota<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    value_len: usize,
+) -> Result<()> {
+    if let Some(row) = sqlx::query(QUOTA_QUERY)
+        .bind(active.profile_id)
+        .fetch_optional(active.connection_mut())
+        .await?
+    {
+        let quota = ProfileQuota {
+            max_items: row.try_get(0)?,
+            max_bytes: row.try_get(1)?,
+        };
+        let (items, bytes): (i64, i64) = (row.try_get(2)?, row.try_get(3)?);
+        if quota.check(items + 1, bytes + value_len as i64).is_err() {
+            // expired entries still hold their share of the quota until they
+            // are removed, so clear them out before rejecting the insert
+            let purged = sqlx::query(EXPIRED_PURGE_QUERY)
+                .bind(active.profile_id)
+                .execute(active.connection_mut())
+                .await?
+                .rows_affected();
+            if purged == 0 {
+                return quota.check(items + 1, bytes + value_len as i64);
+            }
+            let (items, bytes): (i64, i64) = sqlx::query_as(USAGE_QUERY)
+                .bind(active.profile_id)
+                .fetch_one(active.connection_mut())
+                .await?;
+            quota.check(items + 1, bytes + value_len as i64)?;
+        }
+    }
+    Ok(())
+}
+
 async fn perform_insert<'q>(
     active: &mut DbSessionActive<'q, Postgres>,
     kind: EntryKind,
@@ -596,6 +1276,7 @@ async fn perform_insert<'q>(
     enc_tags: Option<Vec<EncEntryTag>>,
     expiry_ms: Option<i64>,
 ) -> Result<()> {
+    check_quota(active, enc_value.len()).await?;
     trace!("Insert entry");
     let row_id: i64 = sqlx::query_scalar(INSERT_QUERY)
         .bind(active.profile_id)
@@ -643,6 +1324,204 @@ async fn perform_remove<'q>(
     }
 }
 
+/// Add the current version of an entry to its history
+async fn record_history<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+    max_versions: Option<usize>,
+) -> Result<()> {
+    trace!("Record entry history");
+    sqlx::query(HISTORY_INSERT_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .execute(active.connection_mut())
+        .await?;
+    trim_history(active, kind, enc_category, Some(enc_name), max_versions).await
+}
+
+/// Discard the oldest versions of an entry beyond the retention limit, or
+/// those of every entry in the category when no name is given
+async fn trim_history<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: Option<&[u8]>,
+    max_versions: Option<usize>,
+) -> Result<()> {
+    if let Some(max_versions) = max_versions {
+        let query = if let Some(enc_name) = enc_name {
+            sqlx::query(HISTORY_TRIM_QUERY)
+                .bind(active.profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+                .bind(enc_name)
+        } else {
+            sqlx::query(HISTORY_TRIM_ALL_QUERY)
+                .bind(active.profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+        };
+        query
+            .bind(max_versions as i64)
+            .execute(active.connection_mut())
+            .await?;
+    }
+    Ok(())
+}
+
+async fn perform_soft_delete<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+) -> Result<()> {
+    trace!("Mark entry deleted");
+    let done = sqlx::query(SOFT_DELETE_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .execute(active.connection_mut())
+        .await?;
+    if done.rows_affected() == 0 {
+        Err(err_msg!(NotFound, "Entry not found"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Erase a deleted entry, if any, so that its name may be reused
+async fn perform_purge<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+) -> Result<()> {
+    sqlx::query(DELETE_DELETED_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .execute(active.connection_mut())
+        .await?;
+    Ok(())
+}
+
+async fn notify_change<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    operation: EntryOperation,
+    enc_category: &[u8],
+    enc_name: Option<&[u8]>,
+) -> Result<()> {
+    // notifications sent within a transaction are only delivered upon commit.
+    // the encrypted category and name are looked up and decrypted by each
+    // listener using the profile key
+    let op: i16 = match operation {
+        EntryOperation::Insert => 0,
+        EntryOperation::Replace => 1,
+        EntryOperation::Remove => 2,
+    };
+    sqlx::query(NOTIFY_QUERY)
+        .bind(active.profile_id)
+        .bind(op)
+        .bind(enc_category)
+        .bind(enc_name)
+        .execute(active.connection_mut())
+        .await?;
+    Ok(())
+}
+
+/// Open a connection listening for change notifications
+async fn change_listener(pool: &PgPool) -> Result<PgListener> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANGES_CHANNEL).await?;
+    sqlx::query(LISTENER_LOCK_QUERY)
+        .execute(&mut listener)
+        .await?;
+    Ok(listener)
+}
+
+/// Decode the identifier of a change from a notification payload, if it
+/// applies to the given profile
+fn decode_change_id(payload: &str, profile_id: ProfileId) -> Result<Option<i64>> {
+    let mut parts = payload.splitn(2, ':');
+    match parts.next().map(str::parse::<ProfileId>) {
+        Some(Ok(pid)) if pid == profile_id => (),
+        Some(Ok(_)) => return Ok(None),
+        _ => return Err(err_msg!(Unexpected, "Invalid change notification")),
+    }
+    parts
+        .next()
+        .and_then(|id| id.parse().ok())
+        .map(Some)
+        .ok_or_else(|| err_msg!(Unexpected, "Invalid change notification"))
+}
+
+async fn fetch_change(
+    pool: &PgPool,
+    change_id: i64,
+    key: &StoreKey,
+) -> Result<Option<EntryChange>> {
+    // the change may already have been pruned if the listener has fallen behind
+    let row = match sqlx::query(CHANGE_FETCH_QUERY)
+        .bind(change_id)
+        .fetch_optional(pool)
+        .await?
+    {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let operation = match row.try_get::<i16, _>(0)? {
+        0 => EntryOperation::Insert,
+        1 => EntryOperation::Replace,
+        2 => EntryOperation::Remove,
+        _ => return Err(err_msg!(Unexpected, "Invalid change operation")),
+    };
+    let category = key.decrypt_entry_category(row.try_get(1)?)?;
+    let name = row
+        .try_get::<Option<Vec<u8>>, _>(2)?
+        .map(|name| key.decrypt_entry_name(name))
+        .transpose()?;
+    Ok(Some(EntryChange {
+        operation,
+        category,
+        name,
+    }))
+}
+
+fn listen_changes(
+    pool: PgPool,
+    mut listener: PgListener,
+    profile_id: ProfileId,
+    key: Arc<StoreKey>,
+    category: Option<String>,
+) -> impl Stream<Item = Result<EntryChange>> {
+    try_stream! {
+        loop {
+            let notification = match listener.try_recv().await? {
+                Some(notification) => notification,
+                None => {
+                    // the connection was lost, along with the listener lock.
+                    // any changes made in the meantime are not delivered
+                    listener = change_listener(&pool).await?;
+                    continue;
+                }
+            };
+            if let Some(change_id) = decode_change_id(notification.payload(), profile_id)? {
+                if let Some(change) = fetch_change(&pool, change_id, &key).await? {
+                    if category.as_ref().map(|cat| cat == &change.category).unwrap_or(true) {
+                        yield change;
+                    }
+                }
+            }
+        }
+    }
+}
+
 fn perform_scan<'q>(
     mut active: DbSessionRef<'q, Postgres>,
     profile_id: ProfileId,
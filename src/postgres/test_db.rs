@@ -69,6 +69,9 @@ impl TestDB {
             key_cache,
             opts.host,
             opts.name,
+            opts.timeout,
+            opts.entry_cache_size,
+            None,
         ));
 
         Ok(TestDB {
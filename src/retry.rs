@@ -0,0 +1,125 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::error::{Error, Result};
+
+/// A policy for repeating operations which fail due to transient backend errors
+///
+/// Failures due to a busy or locked database, serialization failures and
+/// deadlocks, and timeouts acquiring a pooled connection are retried. The delay
+/// between attempts doubles from `base_delay` up to `max_delay`, and is
+/// randomized in the upper half of this range to avoid synchronized retries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the initial attempt
+    pub max_attempts: usize,
+    /// The delay before the first retry
+    pub base_delay: Duration,
+    /// The maximum delay between attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(20),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run an operation, repeating it while it fails with a transient error
+    pub(crate) async fn run<F, Fut, T>(&self, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Err(err) if self.should_retry(attempt, &err) => {
+                    self.backoff(attempt, &err).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Check whether a failed attempt may be repeated
+    pub(crate) fn should_retry(&self, attempt: usize, err: &Error) -> bool {
+        attempt < self.max_attempts && err.is_transient()
+    }
+
+    /// Wait before repeating a failed attempt
+    pub(crate) async fn backoff(&self, attempt: usize, err: &Error) {
+        let delay = self.delay(attempt);
+        warn!(
+            "Retrying operation after transient error (attempt {} of {}, delay {:?}): {}",
+            attempt, self.max_attempts, delay, err
+        );
+        async_std::task::sleep(delay).await;
+    }
+
+    fn delay(&self, attempt: usize) -> Duration {
+        let exp = (attempt.max(1) - 1).min(16) as u32;
+        let limit = self
+            .base_delay
+            .checked_mul(1 << exp)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        let limit_ms = limit.as_millis() as u64;
+        if limit_ms < 2 {
+            return limit;
+        }
+        Duration::from_millis(rand::thread_rng().gen_range(limit_ms / 2, limit_ms + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::block_on;
+
+    #[test]
+    fn retry_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+        let first = policy.delay(1);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+        let third = policy.delay(3);
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+        let last = policy.delay(9);
+        assert!(last >= Duration::from_millis(250) && last <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn retry_transient() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        };
+        let mut count = 0;
+        let result: Result<()> = block_on(policy.run(|| {
+            count += 1;
+            async { Err(err_msg!(Busy, "Busy")) }
+        }));
+        assert!(result.is_err());
+        assert_eq!(count, 3);
+
+        let mut count = 0;
+        let result: Result<()> = block_on(policy.run(|| {
+            count += 1;
+            async { Err(err_msg!(Input, "Not transient")) }
+        }));
+        assert!(result.is_err());
+        assert_eq!(count, 1);
+    }
+}
@@ -1,6 +1,8 @@
 use std::collections::BTreeMap;
 use std::fmt::{self, Debug, Formatter};
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 use async_stream::try_stream;
 use futures_lite::{
@@ -10,35 +12,53 @@ use futures_lite::{
 
 use sqlx::{
     pool::PoolConnection,
-    sqlite::{Sqlite, SqlitePool},
+    sqlite::{Sqlite, SqliteConnection, SqlitePool},
     Database, Error as SqlxError, Row, TransactionManager,
 };
 
+use super::audit::{AuditEvent, AuditLog, AUDIT_KEY_CONFIG};
+use super::cache::{CacheKey, CacheTarget, EntryCache};
+use super::changes::ChangeBroadcast;
 use super::db_utils::{
     decode_tags, decrypt_scan_batch, encode_store_key, encode_tag_filter, expiry_timestamp,
     extend_query, prepare_tags, random_profile_name, DbSession, DbSessionActive, DbSessionRef,
     EncScanEntry, ExtDatabase, QueryParams, QueryPrepare, PAGE_SIZE,
 };
 use super::error::Result;
-use super::future::{unblock, BoxFuture};
-use super::keys::{store::StoreKey, wrap::WrapKeyMethod, EntryEncryptor, KeyCache, PassKey};
-use super::store::{Backend, QueryBackend, Scan};
-use super::types::{EncEntryTag, Entry, EntryKind, EntryOperation, EntryTag, ProfileId, TagFilter};
+use super::future::{spawn_ok, unblock, BoxFuture};
+use super::keys::{
+    store::StoreKey,
+    wrap::{WrapKey, WrapKeyMethod},
+    EntryEncryptor, KeyCache, PassKey,
+};
+use super::store::{Backend, ChangeStream, QueryBackend, Scan};
+use super::types::{
+    AuditOperation, AuditRecord, EncEntryTag, Entry, EntryCacheStats, EntryChange, EntryHistory,
+    EntryKind, EntryOperation, EntryTag, ProfileId, ProfileQuota, ProfileUsage, SoftDelete,
+    TagFilter,
+};
 
 mod provision;
 pub use provision::SqliteStoreOptions;
 
 const COUNT_QUERY: &'static str = "SELECT COUNT(*) FROM items i
     WHERE profile_id = ?1 AND kind = ?2 AND category = ?3
-    AND (expiry IS NULL OR expiry > DATETIME('now'))";
+    AND (expiry IS NULL OR expiry > DATETIME('now'))
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
 const DELETE_QUERY: &'static str = "DELETE FROM items
-    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4";
+    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = items.id)";
+const DELETE_DELETED_QUERY: &'static str = "DELETE FROM items
+    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4
+    AND EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = items.id)";
 const FETCH_QUERY: &'static str = "SELECT i.id, i.value,
     (SELECT GROUP_CONCAT(it.plaintext || ':' || HEX(it.name) || ':' || HEX(it.value))
-        FROM items_tags it WHERE it.item_id = i.id) AS tags
+        FROM items_tags it WHERE it.item_id = i.id) AS tags,
+    i.expiry IS NULL AS cacheable
     FROM items i WHERE i.profile_id = ?1 AND i.kind = ?2
     AND i.category = ?3 AND i.name = ?4
-    AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))";
+    AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
 const INSERT_QUERY: &'static str =
     "INSERT OR IGNORE INTO items (profile_id, kind, category, name, value, expiry)
     VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
@@ -46,11 +66,109 @@ const SCAN_QUERY: &'static str = "SELECT i.id, i.name, i.value,
     (SELECT GROUP_CONCAT(it.plaintext || ':' || HEX(it.name) || ':' || HEX(it.value))
         FROM items_tags it WHERE it.item_id = i.id) AS tags
     FROM items i WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3
-    AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))";
+    AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
 const DELETE_ALL_QUERY: &'static str = "DELETE FROM items AS i
-    WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3";
+    WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const SOFT_DELETE_QUERY: &'static str = "INSERT INTO items_deleted (item_id, deleted)
+    SELECT i.id, DATETIME('now') FROM items i
+    WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3 AND i.name = ?4
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const SOFT_DELETE_ALL_QUERY: &'static str = "INSERT INTO items_deleted (item_id, deleted)
+    SELECT i.id, DATETIME('now') FROM items i
+    WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const UNDELETE_QUERY: &'static str = "DELETE FROM items_deleted
+    WHERE item_id IN (SELECT id FROM items
+        WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4)";
+const DELETED_SIZE_QUERY: &'static str = "SELECT LENGTH(i.value) FROM items i
+    JOIN items_deleted d ON d.item_id = i.id
+    WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3 AND i.name = ?4";
+const PURGE_CATEGORIES_QUERY: &'static str = "SELECT DISTINCT i.category FROM items i
+    JOIN items_deleted d ON d.item_id = i.id
+    WHERE i.profile_id = ?1 AND i.kind = ?2 AND d.deleted <= DATETIME('now', ?3)";
+const PURGE_DELETED_QUERY: &'static str = "DELETE FROM items WHERE profile_id = ?1
+    AND id IN (SELECT item_id FROM items_deleted WHERE deleted <= DATETIME('now', ?2))";
 const TAG_INSERT_QUERY: &'static str = "INSERT INTO items_tags
     (item_id, name, value, plaintext) VALUES (?1, ?2, ?3, ?4)";
+const LOCK_ITEM_QUERY: &'static str = "SELECT id FROM items
+    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4";
+const LOCK_ACQUIRE_QUERY: &'static str = "INSERT INTO items_locks (id, expiry)
+    VALUES (?1, DATETIME('now', ?2))
+    ON CONFLICT (id) DO UPDATE SET expiry = excluded.expiry
+    WHERE items_locks.expiry <= DATETIME('now')";
+const LOCK_SCAN_QUERY: &'static str = "SELECT i.id FROM items i
+    WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3
+    AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const LOCK_RELEASE_QUERY: &'static str = "DELETE FROM items_locks WHERE id = ?1";
+const HISTORY_INSERT_QUERY: &'static str = "INSERT INTO items_history
+    (profile_id, kind, category, name, value, tags, created)
+    SELECT i.profile_id, i.kind, i.category, i.name, i.value,
+    (SELECT GROUP_CONCAT(it.plaintext || ':' || HEX(it.name) || ':' || HEX(it.value))
+        FROM items_tags it WHERE it.item_id = i.id),
+    DATETIME('now')
+    FROM items i WHERE i.profile_id = ?1 AND i.kind = ?2
+    AND i.category = ?3 AND i.name = ?4
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const HISTORY_INSERT_ALL_QUERY: &'static str = "INSERT INTO items_history
+    (profile_id, kind, category, name, value, tags, created)
+    SELECT i.profile_id, i.kind, i.category, i.name, i.value,
+    (SELECT GROUP_CONCAT(it.plaintext || ':' || HEX(it.name) || ':' || HEX(it.value))
+        FROM items_tags it WHERE it.item_id = i.id),
+    DATETIME('now')
+    FROM items i WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3
+    AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)";
+const HISTORY_TRIM_QUERY: &'static str = "DELETE FROM items_history
+    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4
+    AND id <= (SELECT id FROM items_history
+        WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4
+        ORDER BY id DESC LIMIT 1 OFFSET ?5)";
+const HISTORY_TRIM_ALL_QUERY: &'static str = "DELETE FROM items_history
+    WHERE id IN (SELECT id FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY name ORDER BY id DESC) AS version
+        FROM items_history WHERE profile_id = ?1 AND kind = ?2 AND category = ?3
+    ) AS h WHERE h.version > ?4)";
+const HISTORY_FETCH_QUERY: &'static str = "SELECT name, value, tags FROM items_history
+    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4
+    ORDER BY id DESC";
+const QUOTA_QUERY: &'static str = "SELECT max_items, max_bytes, used_items, used_bytes
+    FROM profiles_quotas WHERE profile_id = ?1";
+const QUOTA_UPSERT_QUERY: &'static str =
+    "INSERT INTO profiles_quotas (profile_id, max_items, max_bytes, used_items, used_bytes)
+    SELECT p.id, ?2, ?3,
+        (SELECT COUNT(*) FROM items i WHERE i.profile_id = p.id
+            AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)),
+        (SELECT COALESCE(SUM(LENGTH(i.value)), 0) FROM items i WHERE i.profile_id = p.id
+            AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id))
+    FROM profiles p WHERE p.name = ?1
+    ON CONFLICT (profile_id) DO UPDATE
+    SET max_items = excluded.max_items, max_bytes = excluded.max_bytes";
+const USAGE_QUERY: &'static str = "SELECT used_items, used_bytes FROM profiles_quotas
+    WHERE profile_id = ?1";
+const EXPIRED_PURGE_QUERY: &'static str = "DELETE FROM items
+    WHERE profile_id = ?1 AND expiry <= DATETIME('now')";
+const PROFILE_USAGE_QUERY: &'static str = "SELECT
+    (SELECT COUNT(*) FROM items i WHERE profile_id = p.id
+        AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))
+        AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)),
+    (SELECT COALESCE(SUM(LENGTH(value)), 0) FROM items i WHERE profile_id = p.id
+        AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))
+        AND NOT EXISTS (SELECT 1 FROM items_deleted d WHERE d.item_id = i.id)),
+    q.max_items, q.max_bytes
+    FROM profiles p LEFT JOIN profiles_quotas q ON q.profile_id = p.id
+    WHERE p.name = ?1";
+const AUDIT_LAST_QUERY: &'static str = "SELECT id, mac FROM audit_log ORDER BY id DESC LIMIT 1";
+const AUDIT_INSERT_QUERY: &'static str =
+    "INSERT INTO audit_log (id, data, mac) VALUES (?1, ?2, ?3)";
+const AUDIT_MAC_QUERY: &'static str = "SELECT mac FROM audit_log WHERE id = ?1";
+const AUDIT_SCAN_QUERY: &'static str = "SELECT id, data, mac FROM audit_log
+    WHERE id >= ?1 AND id < ?2 ORDER BY id";
+
+/// Interval between checks when waiting on a record lock held by another session
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const MIN_LOCK_LEASE: Duration = Duration::from_secs(1);
 
 /// A Sqlite database store
 pub struct SqliteStore {
@@ -58,6 +176,13 @@ pub struct SqliteStore {
     default_profile: String,
     key_cache: Arc<KeyCache>,
     path: String,
+    lock_timeout: Duration,
+    timeout: Option<Duration>,
+    entry_cache: Option<Arc<EntryCache>>,
+    changes: Arc<ChangeBroadcast>,
+    audit_log: Option<Arc<AuditLog>>,
+    soft_delete: RwLock<Arc<SoftDelete>>,
+    history: RwLock<Arc<EntryHistory>>,
 }
 
 impl SqliteStore {
@@ -66,14 +191,40 @@ impl SqliteStore {
         default_profile: String,
         key_cache: KeyCache,
         path: String,
+        lock_timeout: Duration,
+        timeout: Option<Duration>,
+        entry_cache_size: usize,
+        audit_log: Option<AuditLog>,
     ) -> Self {
         Self {
             conn_pool,
             default_profile,
             key_cache: Arc::new(key_cache),
             path,
+            lock_timeout,
+            timeout,
+            entry_cache: EntryCache::new(entry_cache_size).map(Arc::new),
+            changes: Arc::new(ChangeBroadcast::default()),
+            audit_log: audit_log.map(Arc::new),
+            soft_delete: RwLock::new(Arc::new(SoftDelete::default())),
+            history: RwLock::new(Arc::new(EntryHistory::default())),
         }
     }
+
+    /// Prepare an audit log event for a store-level operation, if audit
+    /// logging is enabled
+    fn audit_event(
+        &self,
+        operation: AuditOperation,
+        profile: Option<&str>,
+    ) -> Option<(Arc<AuditLog>, AuditEvent)> {
+        self.audit_log.as_ref().map(|log| {
+            (
+                log.clone(),
+                AuditEvent::new(operation, profile, log.actor()),
+            )
+        })
+    }
 }
 
 impl Debug for SqliteStore {
@@ -89,24 +240,62 @@ impl QueryPrepare for SqliteStore {
     type DB = Sqlite;
 }
 
+/// A session on a Sqlite database store
+///
+/// Sqlite has no row-level locking, so records fetched for update within a
+/// transaction are tracked as leases in the `items_locks` table. A lease is
+/// taken before the transaction acquires the database write lock, and a
+/// session fetching the same record for update waits until the lease is
+/// released or expires. Writers are not checked against the leases: every
+/// write runs in an immediate transaction, so it already waits (up to the
+/// lock timeout) for the lease holder to commit or roll back.
+///
+/// Leases are released when the session is closed or dropped.
+#[derive(Debug)]
+pub struct SqliteSession {
+    inner: DbSession<Sqlite>,
+    conn_pool: SqlitePool,
+    item_locks: Vec<i64>,
+    lock_timeout: Duration,
+    changes: Arc<ChangeBroadcast>,
+    pending_changes: Vec<(ProfileId, EntryChange)>,
+}
+
+impl SqliteSession {
+    /// Notify subscribers of a change, or defer the notification until the
+    /// current transaction is committed
+    fn publish_change(&mut self, profile_id: ProfileId, change: EntryChange) {
+        if self.inner.is_transaction() {
+            self.pending_changes.push((profile_id, change));
+        } else {
+            self.changes.publish(profile_id, &change);
+        }
+    }
+}
+
 impl Backend for SqliteStore {
-    type Session = DbSession<Sqlite>;
+    type Session = SqliteSession;
 
     fn create_profile(&self, name: Option<String>) -> BoxFuture<'_, Result<String>> {
         let name = name.unwrap_or_else(random_profile_name);
         Box::pin(async move {
             let key = StoreKey::new()?;
             let enc_key = key.to_string()?;
-            let mut conn = self.conn_pool.acquire().await?;
+            let mut txn = self.conn_pool.begin().await?;
             let done =
                 sqlx::query("INSERT OR IGNORE INTO profiles (name, store_key) VALUES (?1, ?2)")
                     .bind(&name)
                     .bind(enc_key)
-                    .execute(&mut conn)
+                    .execute(&mut txn)
                     .await?;
             if done.rows_affected() == 0 {
                 return Err(err_msg!(Duplicate, "Duplicate profile name"));
             }
+            if let Some((log, event)) = self.audit_event(AuditOperation::CreateProfile, Some(&name))
+            {
+                append_audit(&mut txn, &log, event).await?;
+            }
+            txn.commit().await?;
             self.key_cache
                 .add_profile(name.clone(), done.last_insert_rowid(), Arc::new(key))
                 .await;
@@ -120,13 +309,69 @@ impl Backend for SqliteStore {
 
     fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool>> {
         Box::pin(async move {
-            let mut conn = self.conn_pool.acquire().await?;
-            Ok(sqlx::query("DELETE FROM profiles WHERE name=?")
+            let mut txn = self.conn_pool.begin().await?;
+            let removed = sqlx::query("DELETE FROM profiles WHERE name=?")
                 .bind(&name)
+                .execute(&mut txn)
+                .await?
+                .rows_affected()
+                != 0;
+            if let (true, Some((log, event))) = (
+                removed,
+                self.audit_event(AuditOperation::RemoveProfile, Some(&name)),
+            ) {
+                append_audit(&mut txn, &log, event).await?;
+            }
+            txn.commit().await?;
+            if removed {
+                if let Some(cache) = self.entry_cache.as_ref() {
+                    cache.clear();
+                }
+            }
+            Ok(removed)
+        })
+    }
+
+    fn set_profile_quota(
+        &self,
+        profile: Option<String>,
+        quota: ProfileQuota,
+    ) -> BoxFuture<'_, Result<()>> {
+        let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+        Box::pin(async move {
+            let mut conn = self.conn_pool.acquire().await?;
+            if sqlx::query(QUOTA_UPSERT_QUERY)
+                .bind(&profile)
+                .bind(quota.max_items)
+                .bind(quota.max_bytes)
                 .execute(&mut conn)
                 .await?
                 .rows_affected()
-                != 0)
+                == 0
+            {
+                return Err(err_msg!(NotFound, "Profile not found"));
+            }
+            Ok(())
+        })
+    }
+
+    fn profile_usage(&self, profile: Option<String>) -> BoxFuture<'_, Result<ProfileUsage>> {
+        let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+        Box::pin(async move {
+            let mut conn = self.conn_pool.acquire().await?;
+            let row = sqlx::query(PROFILE_USAGE_QUERY)
+                .bind(&profile)
+                .fetch_optional(&mut conn)
+                .await?
+                .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+            Ok(ProfileUsage {
+                items: row.try_get(0)?,
+                bytes: row.try_get(1)?,
+                quota: ProfileQuota {
+                    max_items: row.try_get(2)?,
+                    max_bytes: row.try_get(3)?,
+                },
+            })
         })
     }
 
@@ -176,6 +421,29 @@ impl Backend for SqliteStore {
             {
                 return Err(err_msg!(Backend, "Error updating wrap key"));
             }
+            // the audit key is re-wrapped even when audit logging is not enabled
+            // for this instance, so that it remains readable
+            if let Some(enc_key) =
+                sqlx::query_scalar::<_, String>("SELECT value FROM config WHERE name = ?1")
+                    .bind(AUDIT_KEY_CONFIG)
+                    .fetch_optional(&mut txn)
+                    .await?
+            {
+                let upd_key = unblock({
+                    let old_key = self.key_cache.wrap_key.clone();
+                    let wrap_key = wrap_key.clone();
+                    move || rewrap_audit_key(&enc_key, &old_key, &wrap_key)
+                })
+                .await?;
+                sqlx::query("UPDATE config SET value = ?1 WHERE name = ?2")
+                    .bind(upd_key)
+                    .bind(AUDIT_KEY_CONFIG)
+                    .execute(&mut txn)
+                    .await?;
+            }
+            if let Some((log, event)) = self.audit_event(AuditOperation::Rekey, None) {
+                append_audit(&mut txn, &log, event).await?;
+            }
             txn.commit().await?;
             self.key_cache = Arc::new(KeyCache::new(wrap_key));
             Ok(())
@@ -193,7 +461,7 @@ impl Backend for SqliteStore {
     ) -> BoxFuture<'_, Result<Scan<'static, Entry>>> {
         Box::pin(async move {
             let session = self.session(profile, false)?;
-            let mut active = session.owned_ref();
+            let mut active = session.inner.owned_ref();
             let (profile_id, key) = acquire_key(&mut *active).await?;
             let scan = perform_scan(
                 active,
@@ -215,12 +483,165 @@ impl Backend for SqliteStore {
     }
 
     fn session(&self, profile: Option<String>, transaction: bool) -> Result<Self::Session> {
-        Ok(DbSession::new(
-            self.conn_pool.clone(),
-            self.key_cache.clone(),
-            profile.unwrap_or_else(|| self.default_profile.clone()),
-            transaction,
-        ))
+        Ok(SqliteSession {
+            inner: DbSession::new(
+                self.conn_pool.clone(),
+                self.key_cache.clone(),
+                profile.unwrap_or_else(|| self.default_profile.clone()),
+                transaction,
+                self.entry_cache.clone(),
+                self.audit_log.clone(),
+                self.soft_delete.read().unwrap().clone(),
+                self.history.read().unwrap().clone(),
+            ),
+            conn_pool: self.conn_pool.clone(),
+            item_locks: Vec::new(),
+            lock_timeout: self.lock_timeout,
+            changes: self.changes.clone(),
+            pending_changes: Vec::new(),
+        })
+    }
+
+    fn backup_to(&self, path: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            // VACUUM INTO writes a snapshot of the database from a single read
+            // transaction, so it is safe to run while the store is in use
+            if unblock({
+                let path = path.clone();
+                move || Path::new(&path).exists()
+            })
+            .await
+            {
+                return Err(err_msg!(Duplicate, "Backup path already exists"));
+            }
+            let mut conn = self.conn_pool.acquire().await?;
+            sqlx::query("VACUUM INTO ?1")
+                .bind(path)
+                .execute(&mut conn)
+                .await
+                .map_err(err_map!(Backend, "Error creating backup"))?;
+            Ok(())
+        })
+    }
+
+    fn entry_cache_stats(&self) -> Option<EntryCacheStats> {
+        self.entry_cache.as_ref().map(|cache| cache.stats())
+    }
+
+    fn default_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn subscribe(
+        &self,
+        profile: Option<String>,
+        category: Option<String>,
+    ) -> BoxFuture<'_, Result<ChangeStream>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let (profile_id, _) = acquire_key(&mut session.inner).await?;
+            Ok(self.changes.subscribe(profile_id, category))
+        })
+    }
+
+    fn scan_audit(
+        &self,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> BoxFuture<'_, Result<Vec<AuditRecord>>> {
+        Box::pin(async move {
+            let log = self
+                .audit_log
+                .clone()
+                .ok_or_else(|| err_msg!(Unsupported, "Audit logging is not enabled"))?;
+            let start = start.unwrap_or(1).max(1);
+            // read the anchor and the range from a single snapshot
+            let mut txn = self.conn_pool.begin().await?;
+            let prev_mac = if start > 1 {
+                sqlx::query_scalar(AUDIT_MAC_QUERY)
+                    .bind(start - 1)
+                    .fetch_optional(&mut txn)
+                    .await?
+            } else {
+                None
+            };
+            let rows = sqlx::query(AUDIT_SCAN_QUERY)
+                .bind(start)
+                .bind(end.unwrap_or(i64::MAX))
+                .fetch_all(&mut txn)
+                .await?
+                .into_iter()
+                .map(|row| Result::Ok((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?)))
+                .collect::<Result<Vec<_>>>()?;
+            txn.commit().await?;
+            unblock(move || log.open_rows(start, prev_mac, rows)).await
+        })
+    }
+
+    fn set_audit_actor(&self, actor: Option<String>) {
+        if let Some(log) = self.audit_log.as_ref() {
+            log.set_actor(actor);
+        }
+    }
+
+    fn set_soft_delete(&self, policy: SoftDelete) -> Result<()> {
+        *self.soft_delete.write().unwrap() = Arc::new(policy);
+        Ok(())
+    }
+
+    fn purge_deleted(
+        &self,
+        profile: Option<String>,
+        older_than: Duration,
+    ) -> BoxFuture<'_, Result<i64>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let (profile_id, key) = acquire_key(&mut session.inner).await?;
+            let audit = session.inner.audit_event(AuditOperation::PurgeDeleted);
+            let cutoff = format!("-{} seconds", older_than.as_secs());
+            let mut active = acquire_session(&mut session.inner).await?;
+            let mut txn = active.as_transaction().await?;
+            let enc_categories: Vec<Vec<u8>> = sqlx::query_scalar(PURGE_CATEGORIES_QUERY)
+                .bind(profile_id)
+                .bind(EntryKind::Item as i16)
+                .bind(&cutoff)
+                .fetch_all(txn.connection_mut())
+                .await?;
+            let purged = sqlx::query(PURGE_DELETED_QUERY)
+                .bind(profile_id)
+                .bind(&cutoff)
+                .execute(txn.connection_mut())
+                .await?
+                .rows_affected();
+            if let (true, Some((log, event))) = (purged > 0, audit) {
+                append_audit(txn.connection_mut(), &log, event).await?;
+            }
+            txn.commit().await?;
+            drop(active);
+            let categories = unblock(move || {
+                enc_categories
+                    .into_iter()
+                    .map(|category| key.decrypt_entry_category(category))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .await?;
+            for category in categories {
+                self.changes.publish(
+                    profile_id,
+                    &EntryChange {
+                        operation: EntryOperation::Remove,
+                        category,
+                        name: None,
+                    },
+                );
+            }
+            Ok(purged as i64)
+        })
+    }
+
+    fn set_entry_history(&self, policy: EntryHistory) -> Result<()> {
+        *self.history.write().unwrap() = Arc::new(policy);
+        Ok(())
     }
 
     fn close(&self) -> BoxFuture<'_, Result<()>> {
@@ -231,7 +652,7 @@ impl Backend for SqliteStore {
     }
 }
 
-impl QueryBackend for DbSession<Sqlite> {
+impl QueryBackend for SqliteSession {
     fn count<'q>(
         &'q mut self,
         kind: EntryKind,
@@ -241,7 +662,7 @@ impl QueryBackend for DbSession<Sqlite> {
         let category = StoreKey::prepare_input(category.as_bytes());
 
         Box::pin(async move {
-            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let (profile_id, key) = acquire_key(&mut self.inner).await?;
             let mut params = QueryParams::new();
             params.push(profile_id);
             params.push(kind as i16);
@@ -258,7 +679,7 @@ impl QueryBackend for DbSession<Sqlite> {
             params.push(enc_category);
             let query =
                 extend_query::<SqliteStore>(COUNT_QUERY, &mut params, tag_filter, None, None)?;
-            let mut active = acquire_session(&mut *self).await?;
+            let mut active = acquire_session(&mut self.inner).await?;
             let count = sqlx::query_scalar_with(query.as_str(), params)
                 .fetch_one(active.connection_mut())
                 .await?;
@@ -271,13 +692,33 @@ impl QueryBackend for DbSession<Sqlite> {
         kind: EntryKind,
         category: &str,
         name: &str,
-        _for_update: bool,
+        for_update: bool,
     ) -> BoxFuture<'_, Result<Option<Entry>>> {
         let category = category.to_string();
         let name = name.to_string();
 
         Box::pin(async move {
-            let (profile_id, key) = acquire_key(&mut *self).await?;
+            if for_update && self.inner.is_transaction() && !self.inner.is_active() {
+                // once the transaction is active it holds the database write lock,
+                // so the lease only needs to be taken before that point
+                if let Some(item_id) =
+                    acquire_item_lock(&self.inner, kind, &category, &name, self.lock_timeout)
+                        .await?
+                {
+                    self.item_locks.push(item_id);
+                }
+            }
+            let (profile_id, key) = acquire_key(&mut self.inner).await?;
+            let cached = match self.inner.entry_cache() {
+                Some(cache) if !for_update => {
+                    let cache_key = CacheKey::new(profile_id, kind, &category, &name);
+                    if let Some(entry) = cache.get(&cache_key) {
+                        return Ok(Some(entry));
+                    }
+                    Some((cache.clone(), cache_key, cache.generation()))
+                }
+                _ => None,
+            };
             let (enc_category, enc_name) = unblock({
                 let key = key.clone();
                 let category = StoreKey::prepare_input(category.as_bytes());
@@ -290,7 +731,7 @@ impl QueryBackend for DbSession<Sqlite> {
                 }
             })
             .await?;
-            let mut active = acquire_session(&mut *self).await?;
+            let mut active = acquire_session(&mut self.inner).await?;
             if let Some(row) = sqlx::query(FETCH_QUERY)
                 .bind(profile_id)
                 .bind(kind as i16)
@@ -301,6 +742,7 @@ impl QueryBackend for DbSession<Sqlite> {
             {
                 let value = row.try_get(1)?;
                 let tags = row.try_get(2)?;
+                let cacheable = row.try_get::<bool, _>(3)?;
                 let (value, tags) = unblock(move || {
                     let value = key.decrypt_entry_value(value)?;
                     let enc_tags = decode_tags(tags)
@@ -309,7 +751,12 @@ impl QueryBackend for DbSession<Sqlite> {
                     Result::Ok((value, tags))
                 })
                 .await?;
-                Ok(Some(Entry::new(category, name, value, tags)))
+                let entry = Entry::new(category, name, value, tags);
+                if let (Some((cache, cache_key, generation)), true) = (cached, cacheable) {
+                    // entries with an expiry time are not cached
+                    cache.insert(cache_key, entry.clone(), generation);
+                }
+                Ok(Some(entry))
             } else {
                 Ok(None)
             }
@@ -322,11 +769,23 @@ impl QueryBackend for DbSession<Sqlite> {
         category: &'q str,
         tag_filter: Option<TagFilter>,
         limit: Option<i64>,
-        _for_update: bool,
+        for_update: bool,
     ) -> BoxFuture<'q, Result<Vec<Entry>>> {
         let category = category.to_string();
         Box::pin(async move {
-            let mut active = self.borrow_mut();
+            if for_update && self.inner.is_transaction() && !self.inner.is_active() {
+                acquire_scan_locks(
+                    &self.inner,
+                    kind,
+                    &category,
+                    tag_filter.clone(),
+                    limit,
+                    self.lock_timeout,
+                    &mut self.item_locks,
+                )
+                .await?;
+            }
+            let mut active = self.inner.borrow_mut();
             let (profile_id, key) = acquire_key(&mut *active).await?;
             let scan = perform_scan(
                 active,
@@ -357,10 +816,27 @@ impl QueryBackend for DbSession<Sqlite> {
         category: &'q str,
         tag_filter: Option<TagFilter>,
     ) -> BoxFuture<'q, Result<i64>> {
+        let cache_target =
+            move |profile_id| CacheTarget::Category(profile_id, kind, category.to_string());
+        let change = move || EntryChange {
+            operation: EntryOperation::Remove,
+            category: category.to_string(),
+            name: None,
+        };
+        let audit = self
+            .inner
+            .audit_event(AuditOperation::RemoveAll)
+            .map(|(log, event)| (log, event.entry(category, None)));
+        let delete_query = if self.inner.soft_delete(category) {
+            SOFT_DELETE_ALL_QUERY
+        } else {
+            DELETE_ALL_QUERY
+        };
+        let history = self.inner.entry_history(category);
         let category = StoreKey::prepare_input(category.as_bytes());
 
         Box::pin(async move {
-            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let (profile_id, key) = acquire_key(&mut self.inner).await?;
             let mut params = QueryParams::new();
             params.push(profile_id);
             params.push(kind as i16);
@@ -374,19 +850,164 @@ impl QueryBackend for DbSession<Sqlite> {
                 }
             })
             .await?;
+            let history_category = enc_category.clone();
+            let history = if let Some(max_versions) = history {
+                let mut params = QueryParams::new();
+                params.push(profile_id);
+                params.push(kind as i16);
+                params.push(enc_category.clone());
+                let query = extend_query::<SqliteStore>(
+                    HISTORY_INSERT_ALL_QUERY,
+                    &mut params,
+                    tag_filter.clone(),
+                    None,
+                    None,
+                )?;
+                Some((query, params, max_versions))
+            } else {
+                None
+            };
             params.push(enc_category);
             let query =
-                extend_query::<SqliteStore>(DELETE_ALL_QUERY, &mut params, tag_filter, None, None)?;
+                extend_query::<SqliteStore>(delete_query, &mut params, tag_filter, None, None)?;
 
-            let mut active = acquire_session(&mut *self).await?;
+            let mut active = acquire_session(&mut self.inner).await?;
+            let mut txn = active.as_transaction().await?;
+            if let Some((history_query, history_params, max_versions)) = history {
+                trace!("Record entry history");
+                sqlx::query_with(history_query.as_str(), history_params)
+                    .execute(txn.connection_mut())
+                    .await?;
+                trim_history(&mut txn, kind, &history_category, None, max_versions).await?;
+            }
             let removed = sqlx::query_with(query.as_str(), params)
-                .execute(active.connection_mut())
+                .execute(txn.connection_mut())
                 .await?
                 .rows_affected();
+            if let (true, Some((log, event))) = (removed > 0, audit) {
+                append_audit(txn.connection_mut(), &log, event).await?;
+            }
+            txn.commit().await?;
+            drop(active);
+            self.inner.invalidate_cache(cache_target(profile_id));
+            if removed > 0 && kind == EntryKind::Item {
+                self.publish_change(profile_id, change());
+            }
             Ok(removed as i64)
         })
     }
 
+    fn fetch_history<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<Entry>>> {
+        let category = category.to_string();
+        let name = name.to_string();
+
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut self.inner).await?;
+            let (enc_category, enc_name) = unblock({
+                let key = key.clone();
+                let category = StoreKey::prepare_input(category.as_bytes());
+                let name = StoreKey::prepare_input(name.as_bytes());
+                move || {
+                    Result::Ok((
+                        key.encrypt_entry_category(category)?,
+                        key.encrypt_entry_name(name)?,
+                    ))
+                }
+            })
+            .await?;
+            let mut active = acquire_session(&mut self.inner).await?;
+            let enc_rows = sqlx::query(HISTORY_FETCH_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+                .bind(enc_name)
+                .fetch_all(active.connection_mut())
+                .await?
+                .into_iter()
+                .map(|row| {
+                    Result::Ok(EncScanEntry {
+                        name: row.try_get(0)?,
+                        value: row.try_get(1)?,
+                        tags: row.try_get(2)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            drop(active);
+            unblock(move || decrypt_scan_batch(category, enc_rows, &key)).await
+        })
+    }
+
+    fn undelete<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+    ) -> BoxFuture<'q, Result<()>> {
+        let cache_target =
+            move |profile_id| CacheTarget::Entry(CacheKey::new(profile_id, kind, category, name));
+        let change = EntryChange {
+            operation: EntryOperation::Insert,
+            category: category.to_string(),
+            name: Some(name.to_string()),
+        };
+        let audit = self
+            .inner
+            .audit_event(AuditOperation::Undelete)
+            .map(|(log, event)| (log, event.entry(category, Some(name))));
+        let category = StoreKey::prepare_input(category.as_bytes());
+        let name = StoreKey::prepare_input(name.as_bytes());
+
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut self.inner).await?;
+            let (enc_category, enc_name) = unblock(move || {
+                Result::Ok((
+                    key.encrypt_entry_category(category)?,
+                    key.encrypt_entry_name(name)?,
+                ))
+            })
+            .await?;
+            let mut active = acquire_session(&mut self.inner).await?;
+            let mut txn = active.as_transaction().await?;
+            let value_len: i64 = sqlx::query_scalar(DELETED_SIZE_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .bind(&enc_category)
+                .bind(&enc_name)
+                .fetch_optional(txn.connection_mut())
+                .await?
+                .ok_or_else(|| err_msg!(NotFound, "Deleted entry not found"))?;
+            // the restored entry counts toward the profile quota again
+            check_quota(&mut txn, value_len as usize).await?;
+            trace!("Restore entry");
+            let done = sqlx::query(UNDELETE_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+                .bind(enc_name)
+                .execute(txn.connection_mut())
+                .await?;
+            if done.rows_affected() == 0 {
+                // the entry had expired and was removed by the quota check
+                return Err(err_msg!(NotFound, "Deleted entry not found"));
+            }
+            if let Some((log, event)) = audit {
+                append_audit(txn.connection_mut(), &log, event).await?;
+            }
+            txn.commit().await?;
+            drop(active);
+            self.inner.invalidate_cache(cache_target(profile_id));
+            if kind == EntryKind::Item {
+                self.publish_change(profile_id, change);
+            }
+            Ok(())
+        })
+    }
+
     fn update<'q>(
         &'q mut self,
         kind: EntryKind,
@@ -397,6 +1018,19 @@ impl QueryBackend for DbSession<Sqlite> {
         tags: Option<&'q [EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> BoxFuture<'q, Result<()>> {
+        let cache_target =
+            move |profile_id| CacheTarget::Entry(CacheKey::new(profile_id, kind, category, name));
+        let change = move |operation| EntryChange {
+            operation,
+            category: category.to_string(),
+            name: Some(name.to_string()),
+        };
+        let audit = self
+            .inner
+            .audit_event(operation.into())
+            .map(|(log, event)| (log, event.entry(category, Some(name))));
+        let soft_delete = self.inner.soft_delete(category);
+        let history = self.inner.entry_history(category);
         let category = StoreKey::prepare_input(category.as_bytes());
         let name = StoreKey::prepare_input(name.as_bytes());
 
@@ -405,7 +1039,7 @@ impl QueryBackend for DbSession<Sqlite> {
                 let value = StoreKey::prepare_input(value.unwrap());
                 let tags = tags.map(prepare_tags);
                 Box::pin(async move {
-                    let (_, key) = acquire_key(&mut *self).await?;
+                    let (profile_id, key) = acquire_key(&mut self.inner).await?;
                     let (enc_category, enc_name, enc_value, enc_tags) = unblock(move || {
                         Result::Ok((
                             key.encrypt_entry_category(category)?,
@@ -415,10 +1049,17 @@ impl QueryBackend for DbSession<Sqlite> {
                         ))
                     })
                     .await?;
-                    let mut active = acquire_session(&mut *self).await?;
+                    let mut active = acquire_session(&mut self.inner).await?;
                     let mut txn = active.as_transaction().await?;
                     if op == EntryOperation::Replace {
+                        if let Some(max_versions) = history {
+                            record_history(&mut txn, kind, &enc_category, &enc_name, max_versions)
+                                .await?;
+                        }
                         perform_remove(&mut txn, kind, &enc_category, &enc_name, false).await?;
+                    } else {
+                        // a deleted entry with the same name is erased by an insert
+                        perform_purge(&mut txn, kind, &enc_category, &enc_name).await?;
                     }
                     perform_insert(
                         &mut txn,
@@ -430,13 +1071,21 @@ impl QueryBackend for DbSession<Sqlite> {
                         expiry_ms,
                     )
                     .await?;
+                    if let Some((log, event)) = audit {
+                        append_audit(txn.connection_mut(), &log, event).await?;
+                    }
                     txn.commit().await?;
+                    drop(active);
+                    self.inner.invalidate_cache(cache_target(profile_id));
+                    if kind == EntryKind::Item {
+                        self.publish_change(profile_id, change(op));
+                    }
                     Ok(())
                 })
             }
 
             EntryOperation::Remove => Box::pin(async move {
-                let (_, key) = acquire_key(&mut *self).await?;
+                let (profile_id, key) = acquire_key(&mut self.inner).await?;
                 let (enc_category, enc_name) = unblock(move || {
                     Result::Ok((
                         key.encrypt_entry_category(category)?,
@@ -444,14 +1093,71 @@ impl QueryBackend for DbSession<Sqlite> {
                     ))
                 })
                 .await?;
-                let mut active = acquire_session(&mut *self).await?;
-                Ok(perform_remove(&mut active, kind, &enc_category, &enc_name, false).await?)
+                let mut active = acquire_session(&mut self.inner).await?;
+                let mut txn = active.as_transaction().await?;
+                if let Some(max_versions) = history {
+                    record_history(&mut txn, kind, &enc_category, &enc_name, max_versions).await?;
+                }
+                if soft_delete {
+                    perform_soft_delete(&mut txn, kind, &enc_category, &enc_name).await?;
+                } else {
+                    perform_remove(&mut txn, kind, &enc_category, &enc_name, false).await?;
+                }
+                if let Some((log, event)) = audit {
+                    append_audit(txn.connection_mut(), &log, event).await?;
+                }
+                txn.commit().await?;
+                drop(active);
+                self.inner.invalidate_cache(cache_target(profile_id));
+                if kind == EntryKind::Item {
+                    self.publish_change(profile_id, change(EntryOperation::Remove));
+                }
+                Ok(())
             }),
         }
     }
 
-    fn close(self, commit: bool) -> BoxFuture<'static, Result<()>> {
-        Box::pin(DbSession::close(self, commit))
+    fn set_audit_actor(&mut self, actor: Option<String>) {
+        self.inner.set_audit_actor(actor);
+    }
+
+    fn set_profile(&mut self, profile: String) -> Result<()> {
+        self.inner.set_profile(profile);
+        Ok(())
+    }
+
+    fn close(mut self, commit: bool) -> BoxFuture<'static, Result<()>> {
+        Box::pin(async move {
+            let result = DbSession::close(&mut self.inner, commit).await;
+            if commit && result.is_ok() {
+                for (profile_id, change) in self.pending_changes.drain(..) {
+                    self.changes.publish(profile_id, &change);
+                }
+            }
+            let item_locks = std::mem::take(&mut self.item_locks);
+            if !item_locks.is_empty() {
+                if let Err(err) = release_item_locks(&self.conn_pool, &item_locks).await {
+                    warn!("Error releasing record locks: {}", err);
+                }
+            }
+            result
+        })
+    }
+}
+
+impl Drop for SqliteSession {
+    fn drop(&mut self) {
+        let item_locks = std::mem::take(&mut self.item_locks);
+        if !item_locks.is_empty() {
+            // the transaction has been rolled back, so the leases must not be
+            // left to block other sessions until they expire
+            let pool = self.conn_pool.clone();
+            spawn_ok(async move {
+                if let Err(err) = release_item_locks(&pool, &item_locks).await {
+                    warn!("Error releasing record locks: {}", err);
+                }
+            });
+        }
     }
 }
 
@@ -474,6 +1180,30 @@ impl ExtDatabase for Sqlite {
     }
 }
 
+async fn append_audit(
+    conn: &mut SqliteConnection,
+    log: &AuditLog,
+    event: AuditEvent,
+) -> Result<()> {
+    let last = sqlx::query(AUDIT_LAST_QUERY)
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|row| Result::Ok((row.try_get(0)?, row.try_get(1)?)))
+        .transpose()?;
+    let (id, data, mac) = log.seal(last, event)?;
+    sqlx::query(AUDIT_INSERT_QUERY)
+        .bind(id)
+        .bind(data)
+        .bind(mac)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+fn rewrap_audit_key(enc_key: &str, old_key: &WrapKey, new_key: &WrapKey) -> Result<String> {
+    AuditLog::from_wrapped(enc_key, old_key)?.to_wrapped(new_key)
+}
+
 async fn acquire_key(session: &mut DbSession<Sqlite>) -> Result<(ProfileId, Arc<StoreKey>)> {
     if let Some(ret) = session.profile_and_key() {
         Ok(ret)
@@ -512,6 +1242,165 @@ async fn resolve_profile_key(
     }
 }
 
+async fn acquire_item_lock(
+    session: &DbSession<Sqlite>,
+    kind: EntryKind,
+    category: &str,
+    name: &str,
+    lock_timeout: Duration,
+) -> Result<Option<i64>> {
+    let (pool, (cache, profile)) = match (session.pool(), session.pending_profile()) {
+        (Some(pool), Some(pending)) => (pool, pending),
+        _ => return Ok(None),
+    };
+    // the lease is committed on a separate connection so that it is visible
+    // to other sessions before this transaction is started
+    let mut conn = pool.acquire().await?;
+    let (profile_id, key) = resolve_profile_key(&mut conn, cache, profile).await?;
+    let (enc_category, enc_name) = unblock({
+        let category = StoreKey::prepare_input(category.as_bytes());
+        let name = StoreKey::prepare_input(name.as_bytes());
+        move || {
+            Result::Ok((
+                key.encrypt_entry_category(category)?,
+                key.encrypt_entry_name(name)?,
+            ))
+        }
+    })
+    .await?;
+    let item_id: i64 = match sqlx::query_scalar(LOCK_ITEM_QUERY)
+        .bind(profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .fetch_optional(&mut conn)
+        .await?
+    {
+        Some(item_id) => item_id,
+        None => return Ok(None),
+    };
+    lease_item(&mut conn, item_id, lock_timeout).await?;
+    Ok(Some(item_id))
+}
+
+async fn acquire_scan_locks(
+    session: &DbSession<Sqlite>,
+    kind: EntryKind,
+    category: &str,
+    tag_filter: Option<TagFilter>,
+    limit: Option<i64>,
+    lock_timeout: Duration,
+    item_locks: &mut Vec<i64>,
+) -> Result<()> {
+    let (pool, (cache, profile)) = match (session.pool(), session.pending_profile()) {
+        (Some(pool), Some(pending)) => (pool, pending),
+        _ => return Ok(()),
+    };
+    let mut conn = pool.acquire().await?;
+    let (profile_id, key) = resolve_profile_key(&mut conn, cache, profile).await?;
+    let mut params = QueryParams::new();
+    params.push(profile_id);
+    params.push(kind as i16);
+    let (enc_category, tag_filter) = unblock({
+        let category = StoreKey::prepare_input(category.as_bytes());
+        let params_len = params.len() + 1; // plus category
+        move || {
+            Result::Ok((
+                key.encrypt_entry_category(category)?,
+                encode_tag_filter::<SqliteStore>(tag_filter, &key, params_len)?,
+            ))
+        }
+    })
+    .await?;
+    params.push(enc_category);
+    let query = extend_query::<SqliteStore>(LOCK_SCAN_QUERY, &mut params, tag_filter, None, limit)?;
+    let rows = sqlx::query_with(query.as_str(), params)
+        .fetch_all(&mut conn)
+        .await?;
+    for row in rows {
+        let item_id: i64 = row.try_get(0)?;
+        lease_item(&mut conn, item_id, lock_timeout).await?;
+        // leases are recorded as they are taken so that all of them are
+        // released with the session, even if a later one times out
+        item_locks.push(item_id);
+    }
+    Ok(())
+}
+
+async fn lease_item(
+    conn: &mut PoolConnection<Sqlite>,
+    item_id: i64,
+    lock_timeout: Duration,
+) -> Result<()> {
+    // a zero lease would expire immediately and never exclude other sessions
+    let lease = lock_timeout.max(MIN_LOCK_LEASE);
+    let expiry = format!("+{} seconds", lease.as_secs());
+    let deadline = Instant::now() + lock_timeout;
+    loop {
+        trace!("Acquire record lock");
+        let done = sqlx::query(LOCK_ACQUIRE_QUERY)
+            .bind(item_id)
+            .bind(&expiry)
+            .execute(&mut *conn)
+            .await?;
+        if done.rows_affected() != 0 {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(err_msg!(Busy, "Timed out waiting for record lock"));
+        }
+        async_std::task::sleep(LOCK_POLL_INTERVAL).await;
+    }
+}
+
+async fn release_item_locks(pool: &SqlitePool, item_locks: &[i64]) -> Result<()> {
+    trace!("Release record locks");
+    let mut conn = pool.acquire().await?;
+    for item_id in item_locks {
+        sqlx::query(LOCK_RELEASE_QUERY)
+            .bind(item_id)
+            .execute(&mut conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Check that an entry may be inserted without exceeding the profile quota
+///
+/// Any entry being replaced must already have been removed within the
+/// current transaction.
+async fn check_quota<'q>(active: &mut DbSessionActive<'q, Sqlite>, value_len: usize) -> Result<()> {
+    if let Some(row) = sqlx::query(QUOTA_QUERY)
+        .bind(active.profile_id)
+        .fetch_optional(active.connection_mut())
+        .await?
+    {
+        let quota = ProfileQuota {
+            max_items: row.try_get(0)?,
+            max_bytes: row.try_get(1)?,
+        };
+        let (items, bytes): (i64, i64) = (row.try_get(2)?, row.try_get(3)?);
+        if quota.check(items + 1, bytes + value_len as i64).is_err() {
+            // expired entries still hold their share of the quota until they
+            // are removed, so clear them out before rejecting the insert
+            let purged = sqlx::query(EXPIRED_PURGE_QUERY)
+                .bind(active.profile_id)
+                .execute(active.connection_mut())
+                .await?
+                .rows_affected();
+            if purged == 0 {
+                return quota.check(items + 1, bytes + value_len as i64);
+            }
+            let (items, bytes): (i64, i64) = sqlx::query_as(USAGE_QUERY)
+                .bind(active.profile_id)
+                .fetch_one(active.connection_mut())
+                .await?;
+            quota.check(items + 1, bytes + value_len as i64)?;
+        }
+    }
+    Ok(())
+}
+
 async fn perform_insert<'q>(
     active: &mut DbSessionActive<'q, Sqlite>,
     kind: EntryKind,
@@ -521,6 +1410,7 @@ async fn perform_insert<'q>(
     enc_tags: Option<Vec<EncEntryTag>>,
     expiry_ms: Option<i64>,
 ) -> Result<()> {
+    check_quota(active, enc_value.len()).await?;
     trace!("Insert entry");
     let done = sqlx::query(INSERT_QUERY)
         .bind(active.profile_id)
@@ -571,6 +1461,93 @@ async fn perform_remove<'q>(
     }
 }
 
+/// Add the current version of an entry to its history
+async fn record_history<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+    max_versions: Option<usize>,
+) -> Result<()> {
+    trace!("Record entry history");
+    sqlx::query(HISTORY_INSERT_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .execute(active.connection_mut())
+        .await?;
+    trim_history(active, kind, enc_category, Some(enc_name), max_versions).await
+}
+
+/// Discard the oldest versions of an entry beyond the retention limit, or
+/// those of every entry in the category when no name is given
+async fn trim_history<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: Option<&[u8]>,
+    max_versions: Option<usize>,
+) -> Result<()> {
+    if let Some(max_versions) = max_versions {
+        let query = if let Some(enc_name) = enc_name {
+            sqlx::query(HISTORY_TRIM_QUERY)
+                .bind(active.profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+                .bind(enc_name)
+        } else {
+            sqlx::query(HISTORY_TRIM_ALL_QUERY)
+                .bind(active.profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+        };
+        query
+            .bind(max_versions as i64)
+            .execute(active.connection_mut())
+            .await?;
+    }
+    Ok(())
+}
+
+async fn perform_soft_delete<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+) -> Result<()> {
+    trace!("Mark entry deleted");
+    let done = sqlx::query(SOFT_DELETE_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .execute(active.connection_mut())
+        .await?;
+    if done.rows_affected() == 0 {
+        Err(err_msg!(NotFound, "Entry not found"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Erase a deleted entry, if any, so that its name may be reused
+async fn perform_purge<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+) -> Result<()> {
+    sqlx::query(DELETE_DELETED_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .execute(active.connection_mut())
+        .await?;
+    Ok(())
+}
+
 fn perform_scan<'q>(
     mut active: DbSessionRef<'q, Sqlite>,
     profile_id: ProfileId,
@@ -651,6 +1628,79 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn sqlite_fetch_for_update_lock() {
+        block_on(async {
+            let key = generate_raw_wrap_key(None)?;
+            let db = SqliteStoreOptions::in_memory()
+                .provision(WrapKeyMethod::RawKey, key, None, false)
+                .await?;
+            let mut conn = db.session(None).await?;
+            conn.insert("category", "name", b"value", None, None)
+                .await?;
+            conn.commit().await?;
+
+            let mut txn = db.transaction(None).await?;
+            txn.fetch("category", "name", true)
+                .await?
+                .expect("Expected row");
+            let locks: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM items_locks WHERE expiry > DATETIME('now')",
+            )
+            .fetch_one(&db.inner().conn_pool)
+            .await?;
+            assert_eq!(locks, 1);
+
+            txn.commit().await?;
+            let locks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM items_locks")
+                .fetch_one(&db.inner().conn_pool)
+                .await?;
+            assert_eq!(locks, 0);
+            Result::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_dropped_transaction_releases_lock() {
+        block_on(async {
+            let key = generate_raw_wrap_key(None)?;
+            let db = SqliteStoreOptions::in_memory()
+                .provision(WrapKeyMethod::RawKey, key, None, false)
+                .await?;
+            let mut conn = db.session(None).await?;
+            conn.insert("category", "name", b"value", None, None)
+                .await?;
+            conn.commit().await?;
+
+            let mut txn = db.transaction(None).await?;
+            txn.fetch("category", "name", true)
+                .await?
+                .expect("Expected row");
+            drop(txn);
+
+            // the lease is released in the background
+            let mut locks: i64 = 1;
+            for _ in 0..50 {
+                locks = sqlx::query_scalar("SELECT COUNT(*) FROM items_locks")
+                    .fetch_one(&db.inner().conn_pool)
+                    .await?;
+                if locks == 0 {
+                    break;
+                }
+                async_std::task::sleep(LOCK_POLL_INTERVAL).await;
+            }
+            assert_eq!(locks, 0);
+
+            let mut txn = db.transaction(None).await?;
+            txn.replace("category", "name", b"value2", None, None)
+                .await?;
+            txn.commit().await?;
+            Result::Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn sqlite_query_placeholders() {
         assert_eq!(
@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::fs::remove_file;
 use std::io::ErrorKind as IoErrorKind;
 use std::str::FromStr;
+use std::time::Duration;
 
 use sqlx::{
     sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
@@ -9,22 +10,103 @@ use sqlx::{
 };
 
 use super::SqliteStore;
+use crate::audit::{AuditLog, AUDIT_KEY_CONFIG};
 use crate::db_utils::{init_keys, random_profile_name};
 use crate::error::Result;
 use crate::future::{unblock, BoxFuture};
 use crate::keys::{
-    wrap::{WrapKeyMethod, WrapKeyReference},
+    wrap::{WrapKey, WrapKeyMethod, WrapKeyReference},
     KeyCache, PassKey,
 };
 use crate::options::{IntoOptions, Options};
 use crate::store::{ManageBackend, Store};
 
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+// profile quotas were added after the initial schema, so the table is
+// also created when opening an existing store. The limits are kept in a table
+// keyed by profile (like the other per-profile state) rather than in a new
+// column of the profiles table, which would require migrating existing stores.
+// The usage counters are only maintained while a quota row exists.
+const CREATE_QUOTAS_TABLE: &'static str = "CREATE TABLE IF NOT EXISTS profiles_quotas (
+    profile_id INTEGER NOT NULL,
+    max_items INTEGER NULL,
+    max_bytes INTEGER NULL,
+    used_items INTEGER NOT NULL DEFAULT 0,
+    used_bytes INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (profile_id),
+    FOREIGN KEY (profile_id) REFERENCES profiles (id)
+        ON DELETE CASCADE ON UPDATE CASCADE
+)";
+
+// soft-deleted entries were added after the initial schema, so the table is
+// also created when opening an existing store
+const CREATE_DELETED_TABLE: &'static str = "CREATE TABLE IF NOT EXISTS items_deleted (
+    item_id INTEGER NOT NULL,
+    deleted DATETIME NOT NULL,
+    PRIMARY KEY (item_id),
+    FOREIGN KEY (item_id) REFERENCES items (id)
+        ON DELETE CASCADE ON UPDATE CASCADE
+)";
+
+// keep the profile usage counters up to date as entries are added, removed,
+// soft-deleted and restored. Soft-deleted entries are not counted.
+const CREATE_QUOTA_TRIGGERS: &'static str = "
+CREATE TRIGGER IF NOT EXISTS tr_items_quota_insert AFTER INSERT ON items
+BEGIN
+    UPDATE profiles_quotas SET used_items = used_items + 1,
+        used_bytes = used_bytes + LENGTH(NEW.value)
+    WHERE profile_id = NEW.profile_id;
+END;
+CREATE TRIGGER IF NOT EXISTS tr_items_quota_remove BEFORE DELETE ON items
+    WHEN NOT EXISTS (SELECT 1 FROM items_deleted WHERE item_id = OLD.id)
+BEGIN
+    UPDATE profiles_quotas SET used_items = used_items - 1,
+        used_bytes = used_bytes - LENGTH(OLD.value)
+    WHERE profile_id = OLD.profile_id;
+END;
+CREATE TRIGGER IF NOT EXISTS tr_items_deleted_quota_insert AFTER INSERT ON items_deleted
+BEGIN
+    UPDATE profiles_quotas SET used_items = used_items - 1,
+        used_bytes = used_bytes - (SELECT LENGTH(value) FROM items WHERE id = NEW.item_id)
+    WHERE profile_id = (SELECT profile_id FROM items WHERE id = NEW.item_id);
+END;
+CREATE TRIGGER IF NOT EXISTS tr_items_deleted_quota_remove AFTER DELETE ON items_deleted
+    WHEN EXISTS (SELECT 1 FROM items WHERE id = OLD.item_id)
+BEGIN
+    UPDATE profiles_quotas SET used_items = used_items + 1,
+        used_bytes = used_bytes + (SELECT LENGTH(value) FROM items WHERE id = OLD.item_id)
+    WHERE profile_id = (SELECT profile_id FROM items WHERE id = OLD.item_id);
+END";
+
+// entry history was added after the initial schema, so the table is
+// also created when opening an existing store
+const CREATE_HISTORY_TABLE: &'static str = "CREATE TABLE IF NOT EXISTS items_history (
+    id INTEGER NOT NULL,
+    profile_id INTEGER NOT NULL,
+    kind INTEGER NOT NULL,
+    category BLOB NOT NULL,
+    name BLOB NOT NULL,
+    value BLOB NOT NULL,
+    tags TEXT NULL,
+    created DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    FOREIGN KEY (profile_id) REFERENCES profiles (id)
+        ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX IF NOT EXISTS ix_items_history_item
+    ON items_history (profile_id, kind, category, name)";
+
 /// Configuration options for Sqlite stores
 #[derive(Debug)]
 pub struct SqliteStoreOptions {
     pub(crate) in_memory: bool,
     pub(crate) path: String,
     pub(crate) max_connections: u32,
+    pub(crate) lock_timeout: Duration,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) entry_cache_size: usize,
+    pub(crate) audit: bool,
 }
 
 impl SqliteStoreOptions {
@@ -38,18 +120,57 @@ impl SqliteStoreOptions {
         } else {
             num_cpus::get() as u32
         };
+        let lock_timeout = if let Some(timeout) = opts.query.remove("lock_timeout") {
+            Duration::from_secs(
+                timeout
+                    .parse()
+                    .map_err(err_map!(Input, "Error parsing 'lock_timeout' parameter"))?,
+            )
+        } else {
+            DEFAULT_LOCK_TIMEOUT
+        };
+        let timeout = if let Some(timeout) = opts.query.remove("timeout") {
+            Some(Duration::from_secs(timeout.parse().map_err(err_map!(
+                Input,
+                "Error parsing 'timeout' parameter"
+            ))?))
+        } else {
+            None
+        };
+        let entry_cache_size = if let Some(size) = opts.query.remove("entry_cache_size") {
+            size.parse().map_err(err_map!(
+                Input,
+                "Error parsing 'entry_cache_size' parameter"
+            ))?
+        } else {
+            0
+        };
+        let audit = if let Some(audit) = opts.query.remove("audit") {
+            audit
+                .parse()
+                .map_err(err_map!(Input, "Error parsing 'audit' parameter"))?
+        } else {
+            false
+        };
         let mut path = opts.host.to_string();
         path.push_str(&*opts.path);
         Ok(Self {
             in_memory: path == ":memory:",
             path,
             max_connections,
+            lock_timeout,
+            timeout,
+            entry_cache_size,
+            audit,
         })
     }
 
     async fn pool(&self, auto_create: bool) -> std::result::Result<SqlitePool, SqlxError> {
-        let conn_opts =
-            SqliteConnectOptions::from_str(self.path.as_ref())?.create_if_missing(auto_create);
+        // writers wait on the database lock held by any transaction which
+        // has fetched records for update, for up to the lock timeout
+        let conn_opts = SqliteConnectOptions::from_str(self.path.as_ref())?
+            .create_if_missing(auto_create)
+            .busy_timeout(self.lock_timeout);
         SqlitePoolOptions::default()
             // maintains at least 1 connection.
             // for an in-memory database this is required to avoid dropping the database,
@@ -88,6 +209,10 @@ impl SqliteStoreOptions {
                     pass_key,
                     profile,
                     self.path.to_string(),
+                    self.lock_timeout,
+                    self.timeout,
+                    self.entry_cache_size,
+                    self.audit,
                 )
                 .await;
             }
@@ -98,12 +223,21 @@ impl SqliteStoreOptions {
             .map(str::to_string)
             .unwrap_or_else(random_profile_name);
         let key_cache = init_db(&conn_pool, &default_profile, method, pass_key).await?;
+        let audit_log = if self.audit {
+            Some(init_audit(&conn_pool, &key_cache.wrap_key).await?)
+        } else {
+            None
+        };
 
         Ok(Store::new(SqliteStore::new(
             conn_pool,
             default_profile,
             key_cache,
             self.path.to_string(),
+            self.lock_timeout,
+            self.timeout,
+            self.entry_cache_size,
+            audit_log,
         )))
     }
 
@@ -129,7 +263,18 @@ impl SqliteStoreOptions {
             }
             Err(err) => Err(err.into()),
         }?;
-        Ok(open_db(conn_pool, method, pass_key, profile, self.path.to_string()).await?)
+        Ok(open_db(
+            conn_pool,
+            method,
+            pass_key,
+            profile,
+            self.path.to_string(),
+            self.lock_timeout,
+            self.timeout,
+            self.entry_cache_size,
+            self.audit,
+        )
+        .await?)
     }
 
     /// Remove the Sqlite store defined by these configuration options
@@ -266,6 +411,23 @@ async fn init_db(
     .execute(&mut conn)
     .await?;
 
+    sqlx::query(CREATE_QUOTAS_TABLE)
+        .persistent(false)
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(CREATE_DELETED_TABLE)
+        .persistent(false)
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(CREATE_QUOTA_TRIGGERS)
+        .persistent(false)
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(CREATE_HISTORY_TABLE)
+        .persistent(false)
+        .execute(&mut conn)
+        .await?;
+
     let mut key_cache = KeyCache::new(wrap_key);
 
     let row = sqlx::query("SELECT id FROM profiles WHERE name = ?1")
@@ -284,6 +446,10 @@ async fn open_db(
     pass_key: PassKey<'_>,
     profile: Option<&str>,
     path: String,
+    lock_timeout: Duration,
+    timeout: Option<Duration>,
+    entry_cache_size: usize,
+    audit: bool,
 ) -> Result<Store<SqliteStore>> {
     let mut conn = conn_pool.acquire().await?;
     let mut ver_ok = false;
@@ -316,6 +482,22 @@ async fn open_db(
     if !ver_ok {
         return Err(err_msg!(Unsupported, "Store version not found"));
     }
+    sqlx::query(CREATE_QUOTAS_TABLE)
+        .persistent(false)
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(CREATE_DELETED_TABLE)
+        .persistent(false)
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(CREATE_QUOTA_TRIGGERS)
+        .persistent(false)
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(CREATE_HISTORY_TABLE)
+        .persistent(false)
+        .execute(&mut conn)
+        .await?;
     let profile = profile
         .map(str::to_string)
         .or(default_profile)
@@ -344,12 +526,53 @@ async fn open_db(
     let profile_id = row.try_get(0)?;
     let store_key = key_cache.load_key(row.try_get(1)?).await?;
     key_cache.add_profile_mut(profile.clone(), profile_id, store_key);
+    drop(conn);
+
+    let audit_log = if audit {
+        Some(init_audit(&conn_pool, &key_cache.wrap_key).await?)
+    } else {
+        None
+    };
 
     Ok(Store::new(SqliteStore::new(
-        conn_pool, profile, key_cache, path,
+        conn_pool,
+        profile,
+        key_cache,
+        path,
+        lock_timeout,
+        timeout,
+        entry_cache_size,
+        audit_log,
     )))
 }
 
+/// Create the audit log table and key if not already present, and load the key
+async fn init_audit(conn_pool: &SqlitePool, wrap_key: &WrapKey) -> Result<AuditLog> {
+    let mut conn = conn_pool.acquire().await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER NOT NULL,
+            data BLOB NOT NULL,
+            mac BLOB NOT NULL,
+            PRIMARY KEY (id)
+        )",
+    )
+    .execute(&mut conn)
+    .await?;
+    let new_key = AuditLog::new().to_wrapped(wrap_key)?;
+    // keep the existing key if another instance has already initialized the log
+    sqlx::query("INSERT OR IGNORE INTO config (name, value) VALUES (?1, ?2)")
+        .bind(AUDIT_KEY_CONFIG)
+        .bind(new_key)
+        .execute(&mut conn)
+        .await?;
+    let enc_key: String = sqlx::query_scalar("SELECT value FROM config WHERE name = ?1")
+        .bind(AUDIT_KEY_CONFIG)
+        .fetch_one(&mut conn)
+        .await?;
+    AuditLog::from_wrapped(&enc_key, wrap_key)
+}
+
 async fn try_remove_file(path: String) -> Result<bool> {
     unblock(|| match remove_file(path) {
         Ok(()) => Ok(true),
@@ -1,21 +1,38 @@
 use std::fmt::{self, Debug, Formatter};
 use std::future::Future;
+use std::ops::{Bound, RangeBounds};
+use std::panic::{resume_unwind, AssertUnwindSafe};
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use futures_lite::stream::{Stream, StreamExt};
+use async_channel::{Receiver, Sender};
+use futures_lite::{
+    future::{self, FutureExt},
+    stream::{Stream, StreamExt},
+};
 use indy_utils::{
-    keys::{EncodedVerKey, KeyType as IndyKeyAlg, PrivateKey},
+    keys::{EncodedVerKey, PrivateKey},
     pack::{pack_message, unpack_message, KeyLookup},
     Validatable,
 };
 use zeroize::Zeroize;
 
-use super::error::Result;
+use super::error::{Error, ErrorKind, Result};
 use super::future::BoxFuture;
-use super::keys::{wrap::WrapKeyMethod, KeyAlg, KeyCategory, KeyEntry, KeyParams, PassKey};
-use super::types::{Entry, EntryKind, EntryOperation, EntryTag, TagFilter};
+use super::keys::{
+    convert_keypair, decrypt_message, encode_key_ident, encrypt_message, generate_keypair,
+    generate_secret_key, import_key, key_agreement, normalize_key_ident, pack_message_v2,
+    secret_key_ident, sign_jws, sign_message, unpack_message_v2, wrap::WrapKeyMethod,
+    JweEncryption, JweKeyLookup, JwsSerialization, KeyAgreementKdf, KeyAlg, KeyCategory, KeyEntry,
+    KeyFormat, KeyParams, PassKey, SignatureFormat,
+};
+use super::retry::RetryPolicy;
+use super::types::{
+    AuditRecord, Entry, EntryCacheStats, EntryChange, EntryHistory, EntryKind, EntryOperation,
+    EntryTag, ProfileQuota, ProfileUsage, SecretBytes, SoftDelete, TagFilter,
+};
 
 /// Represents a generic backend implementation
 pub trait Backend: Send + Sync {
@@ -31,6 +48,20 @@ pub trait Backend: Send + Sync {
     /// Remove an existing profile
     fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool>>;
 
+    /// Replace the storage quota of a profile
+    fn set_profile_quota(
+        &self,
+        _profile: Option<String>,
+        _quota: ProfileQuota,
+    ) -> BoxFuture<'_, Result<()>> {
+        unsupported("Profile quotas are not supported")
+    }
+
+    /// Get the current storage usage and quota of a profile
+    fn profile_usage(&self, _profile: Option<String>) -> BoxFuture<'_, Result<ProfileUsage>> {
+        unsupported("Profile quotas are not supported")
+    }
+
     /// Create a [`Scan`] against the store
     fn scan(
         &self,
@@ -52,6 +83,62 @@ pub trait Backend: Send + Sync {
         key: PassKey<'_>,
     ) -> BoxFuture<'_, Result<()>>;
 
+    /// Write a consistent copy of the store to a new file
+    fn backup_to(&self, _path: String) -> BoxFuture<'_, Result<()>> {
+        unsupported("Backups are not supported")
+    }
+
+    /// Get the usage statistics of the decrypted entry cache, if enabled
+    fn entry_cache_stats(&self) -> Option<EntryCacheStats> {
+        None
+    }
+
+    /// Get the default time limit for session operations and scans, if any
+    fn default_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Subscribe to committed changes to the records of a profile
+    fn subscribe(
+        &self,
+        _profile: Option<String>,
+        _category: Option<String>,
+    ) -> BoxFuture<'_, Result<ChangeStream>> {
+        unsupported("Change notifications are not supported")
+    }
+
+    /// Read and verify the audit log records with identifiers in `[start, end)`
+    fn scan_audit(
+        &self,
+        _start: Option<i64>,
+        _end: Option<i64>,
+    ) -> BoxFuture<'_, Result<Vec<AuditRecord>>> {
+        unsupported("Audit logging is not supported")
+    }
+
+    /// Set the default actor label recorded in the audit log by new sessions
+    fn set_audit_actor(&self, _actor: Option<String>) {}
+
+    /// Set the categories of entries retained when removed by new sessions
+    fn set_soft_delete(&self, _policy: SoftDelete) -> Result<()> {
+        Err(err_msg!(Unsupported, "Soft delete is not supported"))
+    }
+
+    /// Erase the deleted entries of a profile which were removed at least
+    /// `older_than` ago
+    fn purge_deleted(
+        &self,
+        _profile: Option<String>,
+        _older_than: Duration,
+    ) -> BoxFuture<'_, Result<i64>> {
+        unsupported("Soft delete is not supported")
+    }
+
+    /// Set the categories of entries for which new sessions retain previous versions
+    fn set_entry_history(&self, _policy: EntryHistory) -> Result<()> {
+        Err(err_msg!(Unsupported, "Entry history is not supported"))
+    }
+
     /// Close the store instance
     fn close(&self) -> BoxFuture<'_, Result<()>>;
 }
@@ -119,6 +206,26 @@ pub trait QueryBackend: Send {
         tag_filter: Option<TagFilter>,
     ) -> BoxFuture<'q, Result<i64>>;
 
+    /// Fetch the previous versions of a record, most recent first
+    fn fetch_history<'q>(
+        &'q mut self,
+        _kind: EntryKind,
+        _category: &'q str,
+        _name: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<Entry>>> {
+        unsupported("Entry history is not supported")
+    }
+
+    /// Restore a deleted record in the store
+    fn undelete<'q>(
+        &'q mut self,
+        _kind: EntryKind,
+        _category: &'q str,
+        _name: &'q str,
+    ) -> BoxFuture<'q, Result<()>> {
+        unsupported("Soft delete is not supported")
+    }
+
     /// Insert or replace a record in the store
     fn update<'q>(
         &'q mut self,
@@ -131,54 +238,123 @@ pub trait QueryBackend: Send {
         expiry_ms: Option<i64>,
     ) -> BoxFuture<'q, Result<()>>;
 
+    /// Set the actor label recorded in the audit log for this session
+    fn set_audit_actor(&mut self, _actor: Option<String>) {}
+
+    /// Switch the profile used by subsequent operations in this session
+    fn set_profile(&mut self, _profile: String) -> Result<()> {
+        Err(err_msg!(Unsupported, "Switching profiles is not supported"))
+    }
+
     /// Close the current store session
-    fn close(self, commit: bool) -> BoxFuture<'static, Result<()>>;
+    fn close(self, commit: bool) -> BoxFuture<'static, Result<()>>
+    where
+        Self: Sized;
 }
 
 #[derive(Debug)]
 /// An instance of an opened store
-pub struct Store<B: Backend>(B);
+pub struct Store<B: Backend> {
+    backend: B,
+    retry_policy: Option<RetryPolicy>,
+}
 
 impl<B: Backend> Store<B> {
     pub(crate) fn new(inner: B) -> Self {
-        Self(inner)
+        Self {
+            backend: inner,
+            retry_policy: None,
+        }
     }
 
     #[cfg(test)]
     #[allow(unused)]
     pub(crate) fn inner(&self) -> &B {
-        &self.0
+        &self.backend
     }
 
-    pub(crate) fn into_inner(self) -> B {
-        self.0
+    /// Unwrap the backend implementation of the store
+    pub fn into_inner(self) -> B {
+        self.backend
     }
 }
 
 impl<B: Backend> Store<B> {
+    /// Set the policy used to retry operations which fail due to transient
+    /// backend errors
+    ///
+    /// When enabled, idempotent store operations and fetches in sessions
+    /// outside of a transaction are retried automatically. Updates are not
+    /// retried individually, but may be retried along with the enclosing
+    /// transaction using `Store::with_transaction`.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.retry_policy = policy;
+    }
+
+    /// Run an operation under the retry policy of the store
+    ///
+    /// The operation is repeated while it fails with a transient error, up to
+    /// the configured number of attempts.
+    pub async fn retry<F, Fut, T>(&self, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if let Some(policy) = self.retry_policy.as_ref() {
+            policy.run(op).await
+        } else {
+            op().await
+        }
+    }
+
     /// Get the default profile name used when starting a scan or a session
     pub fn get_profile_name(&self) -> &str {
-        self.0.get_profile_name()
+        self.backend.get_profile_name()
     }
 
     /// Replace the wrapping key on a store
     pub async fn rekey(&mut self, method: WrapKeyMethod, pass_key: PassKey<'_>) -> Result<()> {
-        Ok(self.0.rekey_backend(method, pass_key).await?)
+        Ok(self.backend.rekey_backend(method, pass_key).await?)
     }
 
     /// Create a new profile with the given profile name
     pub async fn create_profile(&self, name: Option<String>) -> Result<String> {
-        Ok(self.0.create_profile(name).await?)
+        Ok(self.backend.create_profile(name).await?)
     }
 
     /// Remove an existing profile with the given profile name
     pub async fn remove_profile(&self, name: String) -> Result<bool> {
-        Ok(self.0.remove_profile(name).await?)
+        self.retry(|| self.backend.remove_profile(name.clone()))
+            .await
+    }
+
+    /// Set the limits on the number and total size of the entries in a profile
+    ///
+    /// Inserts and replacements which would exceed either limit fail with
+    /// `ErrorKind::QuotaExceeded`. Existing entries are not affected when a
+    /// limit is lowered below the current usage.
+    pub async fn set_profile_quota(
+        &self,
+        profile: Option<String>,
+        quota: ProfileQuota,
+    ) -> Result<()> {
+        quota.validate()?;
+        self.retry(|| self.backend.set_profile_quota(profile.clone(), quota))
+            .await
+    }
+
+    /// Get the current number and total size of the entries in a profile,
+    /// along with its quota
+    pub async fn profile_usage(&self, profile: Option<String>) -> Result<ProfileUsage> {
+        self.retry(|| self.backend.profile_usage(profile.clone()))
+            .await
     }
 
     /// Create a new scan instance against the store
     ///
-    /// The result will keep an open connection to the backend until it is consumed
+    /// The result will keep an open connection to the backend until it is consumed.
+    /// The default timeout of the store applies to starting the scan and to each
+    /// subsequent fetch.
     pub async fn scan(
         &self,
         profile: Option<String>,
@@ -187,54 +363,366 @@ impl<B: Backend> Store<B> {
         offset: Option<i64>,
         limit: Option<i64>,
     ) -> Result<Scan<'static, Entry>> {
-        Ok(self
-            .0
-            .scan(
-                profile,
-                EntryKind::Item,
-                category,
-                tag_filter,
-                offset,
-                limit,
-            )
-            .await?)
+        let interrupt = Interrupt::new(self.backend.default_timeout());
+        let mut scan = interrupt
+            .run(self.retry(|| {
+                self.backend.scan(
+                    profile.clone(),
+                    EntryKind::Item,
+                    category.clone(),
+                    tag_filter.clone(),
+                    offset,
+                    limit,
+                )
+            }))
+            .await??;
+        scan.interrupt = interrupt;
+        Ok(scan)
     }
 
     /// Create a new session against the store
     pub async fn session(&self, profile: Option<String>) -> Result<Session<B::Session>> {
         // FIXME - add 'immediate' flag
-        Ok(Session::new(self.0.session(profile, false)?))
+        Ok(Session::new(
+            self.backend.session(profile, false)?,
+            self.backend.default_timeout(),
+            self.retry_policy,
+        ))
     }
 
     /// Create a new transaction session against the store
     pub async fn transaction(&self, profile: Option<String>) -> Result<Session<B::Session>> {
-        Ok(Session::new(self.0.session(profile, true)?))
+        // operations within a transaction cannot be retried individually
+        Ok(Session::new(
+            self.backend.session(profile, true)?,
+            self.backend.default_timeout(),
+            None,
+        ))
+    }
+
+    /// Run a closure within a new transaction
+    ///
+    /// The transaction is committed if the closure returns `Ok`, and rolled back
+    /// if it returns an error or panics. When a retry policy is set for the store,
+    /// the whole transaction is repeated if it fails with a transient error such
+    /// as a serialization failure or a busy database, so the closure may be called
+    /// more than once.
+    pub async fn with_transaction<F, T>(&self, profile: Option<String>, mut f: F) -> Result<T>
+    where
+        F: for<'s> FnMut(&'s mut Session<B::Session>) -> BoxFuture<'s, Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match self.run_transaction(profile.clone(), &mut f).await {
+                Err(err) => match self.retry_policy.as_ref() {
+                    Some(policy) if policy.should_retry(attempt, &err) => {
+                        policy.backoff(attempt, &err).await;
+                        attempt += 1;
+                    }
+                    _ => return Err(err),
+                },
+                result => return result,
+            }
+        }
+    }
+
+    async fn run_transaction<F, T>(&self, profile: Option<String>, f: &mut F) -> Result<T>
+    where
+        F: for<'s> FnMut(&'s mut Session<B::Session>) -> BoxFuture<'s, Result<T>>,
+    {
+        let mut txn = self.transaction(profile).await?;
+        match AssertUnwindSafe(f(&mut txn)).catch_unwind().await {
+            Ok(Ok(value)) => {
+                txn.commit().await?;
+                Ok(value)
+            }
+            Ok(Err(err)) => {
+                if let Err(rb_err) = txn.rollback().await {
+                    warn!("Error rolling back transaction: {}", rb_err);
+                }
+                Err(err)
+            }
+            Err(panic) => {
+                if let Err(rb_err) = txn.rollback().await {
+                    warn!("Error rolling back transaction: {}", rb_err);
+                }
+                resume_unwind(panic)
+            }
+        }
+    }
+
+    /// Create a backup of an open store at the given path
+    ///
+    /// The backup is opened with the same wrapping key as the original store
+    pub async fn backup_to(&self, path: &str) -> Result<()> {
+        Ok(self.backend.backup_to(path.to_string()).await?)
+    }
+
+    /// Subscribe to changes to the records of a profile
+    ///
+    /// An event is emitted for each insert, replace or removal once it has been
+    /// committed, optionally limited to a single category. Removals performed
+    /// with `remove_all` are reported without an entry name. Changes are dropped
+    /// while a subscriber is too far behind, which is reported as a `Busy` error.
+    pub async fn subscribe(
+        &self,
+        profile: Option<String>,
+        category: Option<&str>,
+    ) -> Result<ChangeStream> {
+        Ok(self
+            .backend
+            .subscribe(profile, category.map(str::to_string))
+            .await?)
+    }
+
+    /// Read back a range of records from the audit log of the store
+    ///
+    /// Audit logging must be enabled when the store is opened. The chain of
+    /// record MACs is verified as the log is read, and an error is returned
+    /// if any record in the range has been altered, removed or reordered.
+    pub async fn scan_audit(&self, range: impl RangeBounds<i64>) -> Result<Vec<AuditRecord>> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => Some(*start),
+            Bound::Excluded(start) => Some(start.saturating_add(1)),
+            Bound::Unbounded => None,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => Some(end.saturating_add(1)),
+            Bound::Excluded(end) => Some(*end),
+            Bound::Unbounded => None,
+        };
+        self.retry(|| self.backend.scan_audit(start, end)).await
+    }
+
+    /// Set the actor label recorded in the audit log by subsequent sessions
+    pub fn set_audit_actor(&self, actor: Option<&str>) {
+        self.backend.set_audit_actor(actor.map(str::to_string))
+    }
+
+    /// Set the categories of records which are retained when removed
+    ///
+    /// The policy applies to sessions started after it is set. Retained records
+    /// are hidden from all queries, and may be restored with `Session::undelete`
+    /// or erased with `purge_deleted`.
+    pub fn set_soft_delete(&self, policy: SoftDelete) -> Result<()> {
+        self.backend.set_soft_delete(policy)
+    }
+
+    /// Set the categories of records for which previous versions are retained
+    ///
+    /// The policy applies to sessions started after it is set. When a matching
+    /// record is replaced or removed, its previous value and tags are added to
+    /// its history, which may be read with `Session::fetch_history`.
+    pub fn set_entry_history(&self, policy: EntryHistory) -> Result<()> {
+        self.backend.set_entry_history(policy)
+    }
+
+    /// Erase the deleted records of a profile which were removed at least
+    /// `older_than` ago, returning the number of records erased
+    ///
+    /// The default profile is used if none is specified.
+    pub async fn purge_deleted(
+        &self,
+        profile: Option<String>,
+        older_than: Duration,
+    ) -> Result<i64> {
+        self.retry(|| self.backend.purge_deleted(profile.clone(), older_than))
+            .await
+    }
+
+    /// Get the hit and miss counts of the decrypted entry cache, if enabled
+    pub fn entry_cache_stats(&self) -> Option<EntryCacheStats> {
+        self.backend.entry_cache_stats()
     }
 
     /// Close the store instance, waiting for any shutdown procedures to complete.
     pub async fn close(self) -> Result<()> {
-        Ok(self.0.close().await?)
+        Ok(self.backend.close().await?)
     }
 
     pub(crate) async fn arc_close(self: Arc<Self>) -> Result<()> {
-        Ok(self.0.close().await?)
+        Ok(self.backend.close().await?)
+    }
+}
+
+/// A handle used to abort the running operation of a session or scan
+#[derive(Clone, Debug)]
+pub struct CancelHandle(Sender<()>);
+
+impl CancelHandle {
+    /// Abort the running operation, along with any subsequent operations
+    pub fn cancel(&self) {
+        self.0.close();
+    }
+
+    /// Check whether the operation has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+/// Aborts store operations which are cancelled or exceed a time limit
+#[derive(Debug)]
+struct Interrupt {
+    timeout: Option<Duration>,
+    handle: CancelHandle,
+    cancelled: Receiver<()>,
+}
+
+impl Interrupt {
+    fn new(timeout: Option<Duration>) -> Self {
+        let (sender, cancelled) = async_channel::bounded(1);
+        Self {
+            timeout,
+            handle: CancelHandle(sender),
+            cancelled,
+        }
+    }
+
+    /// Drive an operation to completion, or fail with `Timeout` or
+    /// `Cancelled` if it is interrupted
+    async fn run<F: Future>(&self, fut: F) -> Result<F::Output> {
+        if self.handle.is_cancelled() {
+            return Err(err_msg!(Cancelled, "Operation cancelled"));
+        }
+        let cancelled = async {
+            // no value is ever sent, so this only completes once the channel is closed
+            self.cancelled.recv().await.ok();
+            Err(err_msg!(Cancelled, "Operation cancelled"))
+        };
+        let fut = future::or(async { Result::Ok(fut.await) }, cancelled);
+        if let Some(timeout) = self.timeout {
+            async_std::future::timeout(timeout, fut)
+                .await
+                .unwrap_or_else(|_| Err(err_msg!(Timeout, "Operation timed out")))
+        } else {
+            fut.await
+        }
+    }
+}
+
+/// The result of a backend operation which is not implemented
+fn unsupported<'f, T: Send + 'f>(message: &'static str) -> BoxFuture<'f, Result<T>> {
+    Box::pin(async move { Err(err_msg!(Unsupported, "{}", message)) })
+}
+
+fn session_closed() -> Error {
+    err_msg!(Cancelled, "Session was interrupted and has been closed")
+}
+
+/// Symmetric keys are stored under the identifier provided at creation, while
+/// the identifiers of other keys are normalized
+fn key_entry_ident(category: &KeyCategory, ident: &str) -> Result<String> {
+    match category {
+        KeyCategory::SecretKey => Ok(ident.to_string()),
+        _ => normalize_key_ident(ident),
     }
 }
 
 /// An active connection to the store backend
 #[derive(Debug)]
-pub struct Session<Q: QueryBackend>(Q);
+pub struct Session<Q: QueryBackend> {
+    inner: Option<Q>,
+    interrupt: Interrupt,
+    retry_policy: Option<RetryPolicy>,
+}
 
 impl<Q: QueryBackend> Session<Q> {
-    pub(crate) fn new(inner: Q) -> Self {
-        Self(inner)
+    pub(crate) fn new(
+        inner: Q,
+        timeout: Option<Duration>,
+        retry_policy: Option<RetryPolicy>,
+    ) -> Self {
+        Self {
+            inner: Some(inner),
+            interrupt: Interrupt::new(timeout),
+            retry_policy,
+        }
+    }
+
+    fn parts(&mut self) -> Result<(&mut Q, &Interrupt)> {
+        let inner = self.inner.as_mut().ok_or_else(session_closed)?;
+        Ok((inner, &self.interrupt))
+    }
+
+    /// Wait before repeating a fetch which failed with a transient error, if
+    /// permitted by the retry policy
+    async fn backoff<T>(&self, result: &Result<Result<T>>, attempt: &mut usize) -> bool {
+        if let (Some(policy), Ok(Err(err))) = (self.retry_policy.as_ref(), result) {
+            if policy.should_retry(*attempt, err) {
+                policy.backoff(*attempt, err).await;
+                *attempt += 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Close the session if the operation was interrupted
+    async fn complete<T>(&mut self, result: Result<Result<T>>) -> Result<T> {
+        match result {
+            Ok(result) => result,
+            Err(err) => {
+                // the connection may have been left part way through a query,
+                // so it is released and any pending transaction rolled back
+                if let Some(inner) = self.inner.take() {
+                    if let Err(err) = inner.close(false).await {
+                        warn!("Error closing interrupted session: {}", err);
+                    }
+                }
+                Err(err)
+            }
+        }
     }
 }
 
 impl<Q: QueryBackend> Session<Q> {
+    /// Set the time limit for each subsequent operation in this session
+    ///
+    /// This overrides the default timeout of the store. An operation which
+    /// exceeds the limit fails with `ErrorKind::Timeout`, after which the
+    /// session is closed and any pending transaction is rolled back.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.interrupt.timeout = timeout;
+    }
+
+    /// Get a handle which may be used to abort the operations of this session
+    ///
+    /// A cancelled operation fails with `ErrorKind::Cancelled`, after which the
+    /// session is closed and any pending transaction is rolled back.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.interrupt.handle.clone()
+    }
+
+    /// Set the actor label recorded in the audit log for updates in this session
+    pub fn set_audit_actor(&mut self, actor: Option<&str>) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.set_audit_actor(actor.map(str::to_string))
+        }
+    }
+
+    /// Switch the profile used by subsequent operations in this session
+    ///
+    /// Within a transaction, operations on each profile share the same
+    /// connection and are committed or rolled back together. Each operation
+    /// uses the store key of the profile selected at the time.
+    pub fn set_profile(&mut self, profile: &str) -> Result<()> {
+        let inner = self.inner.as_mut().ok_or_else(session_closed)?;
+        inner.set_profile(profile.to_string())
+    }
+
     /// Count the number of entries for a given record category
     pub async fn count(&mut self, category: &str, tag_filter: Option<TagFilter>) -> Result<i64> {
-        Ok(self.0.count(EntryKind::Item, category, tag_filter).await?)
+        let mut attempt = 1;
+        loop {
+            let (inner, interrupt) = self.parts()?;
+            let result = interrupt
+                .run(inner.count(EntryKind::Item, category, tag_filter.clone()))
+                .await;
+            if !self.backoff(&result, &mut attempt).await {
+                return self.complete(result).await;
+            }
+        }
     }
 
     /// Retrieve the current record at `(category, name)`.
@@ -247,10 +735,24 @@ impl<Q: QueryBackend> Session<Q> {
         name: &str,
         for_update: bool,
     ) -> Result<Option<Entry>> {
-        Ok(self
-            .0
-            .fetch(EntryKind::Item, category, name, for_update)
-            .await?)
+        self.fetch_entry(EntryKind::Item, category, name, for_update)
+            .await
+    }
+
+    /// Fetch the previous versions of a record, most recent first
+    ///
+    /// Versions are only recorded for categories with entry history enabled.
+    pub async fn fetch_history(&mut self, category: &str, name: &str) -> Result<Vec<Entry>> {
+        let mut attempt = 1;
+        loop {
+            let (inner, interrupt) = self.parts()?;
+            let result = interrupt
+                .run(inner.fetch_history(EntryKind::Item, category, name))
+                .await;
+            if !self.backoff(&result, &mut attempt).await {
+                return self.complete(result).await;
+            }
+        }
     }
 
     /// Retrieve all records matching the given `category` and `tag_filter`.
@@ -265,10 +767,22 @@ impl<Q: QueryBackend> Session<Q> {
         limit: Option<i64>,
         for_update: bool,
     ) -> Result<Vec<Entry>> {
-        Ok(self
-            .0
-            .fetch_all(EntryKind::Item, category, tag_filter, limit, for_update)
-            .await?)
+        let mut attempt = 1;
+        loop {
+            let (inner, interrupt) = self.parts()?;
+            let result = interrupt
+                .run(inner.fetch_all(
+                    EntryKind::Item,
+                    category,
+                    tag_filter.clone(),
+                    limit,
+                    for_update,
+                ))
+                .await;
+            if !self.backoff(&result, &mut attempt).await {
+                return self.complete(result).await;
+            }
+        }
     }
 
     /// Insert a new record into the store
@@ -280,34 +794,46 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> Result<()> {
-        Ok(self
-            .0
-            .update(
-                EntryKind::Item,
-                EntryOperation::Insert,
-                category,
-                name,
-                Some(value),
-                tags,
-                expiry_ms,
-            )
-            .await?)
+        self.update_entry(
+            EntryKind::Item,
+            EntryOperation::Insert,
+            category,
+            name,
+            Some(value),
+            tags,
+            expiry_ms,
+        )
+        .await
     }
 
     /// Remove a record from the store
+    ///
+    /// If soft delete is enabled for the category, the record is retained and
+    /// may be restored with `undelete` until it is purged.
     pub async fn remove(&mut self, category: &str, name: &str) -> Result<()> {
-        Ok(self
-            .0
-            .update(
-                EntryKind::Item,
-                EntryOperation::Remove,
-                category,
-                name,
-                None,
-                None,
-                None,
-            )
-            .await?)
+        self.update_entry(
+            EntryKind::Item,
+            EntryOperation::Remove,
+            category,
+            name,
+            None,
+            None,
+            None,
+        )
+        .await
+    }
+
+    /// Restore a record which was removed while soft delete was enabled
+    ///
+    /// Returns `ErrorKind::NotFound` if there is no deleted record with the
+    /// given name, including when it has since been purged or replaced by a
+    /// new record.
+    pub async fn undelete(&mut self, category: &str, name: &str) -> Result<()> {
+        let (inner, interrupt) = self.parts()?;
+        let result = interrupt
+            .run(inner.undelete(EntryKind::Item, category, name))
+            .await;
+        self.complete(result).await
     }
 
     /// Replace the value and tags of a record in the store
@@ -319,30 +845,32 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> Result<()> {
-        Ok(self
-            .0
-            .update(
-                EntryKind::Item,
-                EntryOperation::Replace,
-                category,
-                name,
-                Some(value),
-                tags,
-                expiry_ms,
-            )
-            .await?)
+        self.update_entry(
+            EntryKind::Item,
+            EntryOperation::Replace,
+            category,
+            name,
+            Some(value),
+            tags,
+            expiry_ms,
+        )
+        .await
     }
 
     /// Remove all records in the store matching a given `category` and `tag_filter`
+    ///
+    /// If soft delete is enabled for the category, the records are retained and
+    /// may be restored individually with `undelete` until they are purged.
     pub async fn remove_all(
         &mut self,
         category: &str,
         tag_filter: Option<TagFilter>,
     ) -> Result<i64> {
-        Ok(self
-            .0
-            .remove_all(EntryKind::Item, category, tag_filter)
-            .await?)
+        let (inner, interrupt) = self.parts()?;
+        let result = interrupt
+            .run(inner.remove_all(EntryKind::Item, category, tag_filter))
+            .await;
+        self.complete(result).await
     }
 
     /// Perform a record update
@@ -358,18 +886,52 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> Result<()> {
-        Ok(self
-            .0
-            .update(
-                EntryKind::Item,
-                operation,
-                category,
-                name,
-                value,
-                tags,
-                expiry_ms,
-            )
-            .await?)
+        self.update_entry(
+            EntryKind::Item,
+            operation,
+            category,
+            name,
+            value,
+            tags,
+            expiry_ms,
+        )
+        .await
+    }
+
+    async fn update_entry(
+        &mut self,
+        kind: EntryKind,
+        operation: EntryOperation,
+        category: &str,
+        name: &str,
+        value: Option<&[u8]>,
+        tags: Option<&[EntryTag]>,
+        expiry_ms: Option<i64>,
+    ) -> Result<()> {
+        let (inner, interrupt) = self.parts()?;
+        let result = interrupt
+            .run(inner.update(kind, operation, category, name, value, tags, expiry_ms))
+            .await;
+        self.complete(result).await
+    }
+
+    async fn fetch_entry(
+        &mut self,
+        kind: EntryKind,
+        category: &str,
+        name: &str,
+        for_update: bool,
+    ) -> Result<Option<Entry>> {
+        let mut attempt = 1;
+        loop {
+            let (inner, interrupt) = self.parts()?;
+            let result = interrupt
+                .run(inner.fetch(kind, category, name, for_update))
+                .await;
+            if !self.backoff(&result, &mut attempt).await {
+                return self.complete(result).await;
+            }
+        }
     }
 
     /// Create a new keypair in the store
@@ -381,47 +943,69 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         // backend
     ) -> Result<KeyEntry> {
-        match alg {
-            KeyAlg::ED25519 => (),
-            _ => return Err(err_msg!(Unsupported, "Unsupported key algorithm")),
-        }
-
-        let sk = match seed {
-            None => PrivateKey::generate(Some(IndyKeyAlg::ED25519)),
-            Some(s) => PrivateKey::from_seed(s),
-        }
-        .map_err(err_map!(Unexpected, "Error generating keypair"))?;
+        let (pub_key, prv_key) = generate_keypair(&alg, seed)?;
+        let exportable = KeyParams::metadata_exportable(metadata);
+        self.insert_keypair(alg, pub_key, prv_key, metadata, tags, exportable)
+            .await
+    }
 
-        let pk = sk
-            .public_key()
-            .map_err(err_map!(Unexpected, "Error generating public key"))?;
+    /// Create a new keypair in the store by converting an existing keypair
+    /// for use with another algorithm
+    ///
+    /// This currently supports deriving an X25519 key agreement keypair from
+    /// an Ed25519 keypair. The converted keypair is not exportable if the
+    /// source keypair is not.
+    pub async fn convert_keypair(
+        &mut self,
+        ident: &str,
+        alg: KeyAlg,
+        metadata: Option<&str>,
+        tags: Option<&[EntryTag]>,
+    ) -> Result<KeyEntry> {
+        let key = self
+            .fetch_key(KeyCategory::KeyPair, ident, false)
+            .await?
+            .ok_or_else(|| err_msg!(NotFound, "Unknown key"))?;
+        let (pub_key, prv_key) = convert_keypair(&key.params, &alg)?;
+        // the converted private key is derived from the source key, so it
+        // must not be exportable unless the source key is
+        let exportable = key.params.exportable && KeyParams::metadata_exportable(metadata);
+        self.insert_keypair(alg, pub_key, prv_key, metadata, tags, exportable)
+            .await
+    }
 
+    async fn insert_keypair(
+        &mut self,
+        alg: KeyAlg,
+        pub_key: Vec<u8>,
+        prv_key: SecretBytes,
+        metadata: Option<&str>,
+        tags: Option<&[EntryTag]>,
+        exportable: bool,
+    ) -> Result<KeyEntry> {
         let category = KeyCategory::KeyPair;
-        let ident = pk
-            .as_base58()
-            .map_err(err_map!(Unexpected, "Error encoding public key"))?
-            .long_form();
+        let ident = encode_key_ident(&alg, &pub_key)?;
 
         let params = KeyParams {
             alg,
             metadata: metadata.map(str::to_string),
             reference: None,
-            pub_key: Some(pk.key_bytes()),
-            prv_key: Some(sk.key_bytes().into()),
+            pub_key: Some(pub_key),
+            prv_key: Some(prv_key),
+            exportable,
         };
         let mut value = params.to_vec()?;
 
-        self.0
-            .update(
-                EntryKind::Key,
-                EntryOperation::Insert,
-                category.as_str(),
-                &ident,
-                Some(value.as_slice()),
-                tags.clone(),
-                None,
-            )
-            .await?;
+        self.update_entry(
+            EntryKind::Key,
+            EntryOperation::Insert,
+            category.as_str(),
+            &ident,
+            Some(value.as_slice()),
+            tags.clone(),
+            None,
+        )
+        .await?;
         value.zeroize();
 
         Ok(KeyEntry {
@@ -432,9 +1016,92 @@ impl<Q: QueryBackend> Session<Q> {
         })
     }
 
-    // pub async fn import_key(&self, key: KeyEntry) -> Result<()> {
-    //     Ok(())
-    // }
+    /// Create a new symmetric key in the store
+    ///
+    /// A random identifier is assigned to the key unless one is provided.
+    pub async fn create_key(
+        &mut self,
+        alg: KeyAlg,
+        ident: Option<&str>,
+        metadata: Option<&str>,
+        tags: Option<&[EntryTag]>,
+    ) -> Result<KeyEntry> {
+        let key = generate_secret_key(&alg)?;
+        self.insert_secret_key(alg, ident, key, metadata, tags)
+            .await
+    }
+
+    async fn insert_secret_key(
+        &mut self,
+        alg: KeyAlg,
+        ident: Option<&str>,
+        key: SecretBytes,
+        metadata: Option<&str>,
+        tags: Option<&[EntryTag]>,
+    ) -> Result<KeyEntry> {
+        let category = KeyCategory::SecretKey;
+        let ident = ident
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        let params = KeyParams {
+            prv_key: Some(key),
+            alg,
+            metadata: metadata.map(str::to_string),
+            reference: None,
+            pub_key: None,
+            exportable: KeyParams::metadata_exportable(metadata),
+        };
+        let mut value = params.to_vec()?;
+
+        self.update_entry(
+            EntryKind::Key,
+            EntryOperation::Insert,
+            category.as_str(),
+            &ident,
+            Some(value.as_slice()),
+            tags.clone(),
+            None,
+        )
+        .await?;
+        value.zeroize();
+
+        Ok(KeyEntry {
+            category,
+            ident,
+            params,
+            tags: tags.map(|t| t.to_vec()),
+        })
+    }
+
+    /// Import an existing private or secret key into the store
+    ///
+    /// The key may be provided as raw bytes, a JWK, or a DER-encoded PKCS#8
+    /// document. The public key and identifier are derived from the private
+    /// key, and the identifier of a symmetric key is derived from the key
+    /// itself. Importing a key which is already present in the store fails
+    /// with a `Duplicate` error.
+    pub async fn import_key(
+        &mut self,
+        alg: KeyAlg,
+        secret: &[u8],
+        format: KeyFormat,
+        metadata: Option<&str>,
+        tags: Option<&[EntryTag]>,
+    ) -> Result<KeyEntry> {
+        match import_key(&alg, secret, format)? {
+            (Some(pub_key), prv_key) => {
+                let exportable = KeyParams::metadata_exportable(metadata);
+                self.insert_keypair(alg, pub_key, prv_key, metadata, tags, exportable)
+                    .await
+            }
+            (None, key) => {
+                let ident = secret_key_ident(&alg, &key)?;
+                self.insert_secret_key(alg, Some(&ident), key, metadata, tags)
+                    .await
+            }
+        }
+    }
 
     /// Fetch an existing key from the store
     ///
@@ -446,16 +1113,11 @@ impl<Q: QueryBackend> Session<Q> {
         ident: &str,
         for_update: bool,
     ) -> Result<Option<KeyEntry>> {
-        // normalize ident
-        let ident = EncodedVerKey::from_str(&ident)
-            .and_then(|k| k.as_base58())
-            .map_err(err_map!("Invalid key"))?
-            .long_form();
+        let ident = key_entry_ident(&category, ident)?;
 
         Ok(
             if let Some(row) = self
-                .0
-                .fetch(EntryKind::Key, category.as_str(), &ident, for_update)
+                .fetch_entry(EntryKind::Key, category.as_str(), &ident, for_update)
                 .await?
             {
                 let params = KeyParams::from_slice(&row.value)?;
@@ -471,25 +1133,58 @@ impl<Q: QueryBackend> Session<Q> {
         )
     }
 
+    /// Export an existing key from the store in the requested format
+    ///
+    /// Only JWK and raw formats are supported. The private key is returned
+    /// only when `include_private` is set, and only for keys which have not
+    /// been created as non-exportable. Otherwise a `Forbidden` error is
+    /// returned.
+    pub async fn export_key(
+        &mut self,
+        category: KeyCategory,
+        ident: &str,
+        format: KeyFormat,
+        include_private: bool,
+    ) -> Result<SecretBytes> {
+        let key = self
+            .fetch_key(category, ident, false)
+            .await?
+            .ok_or_else(|| err_msg!(NotFound, "Unknown key"))?;
+        match format {
+            KeyFormat::Jwk => Ok(key.to_jwk(include_private)?.into()),
+            KeyFormat::Raw if include_private => {
+                if !key.is_exportable() {
+                    return Err(err_msg!(Forbidden, "Key is not exportable"));
+                }
+                key.params
+                    .prv_key
+                    .clone()
+                    .ok_or_else(|| err_msg!(Input, "Undefined private key"))
+            }
+            KeyFormat::Raw => key
+                .params
+                .pub_key
+                .as_ref()
+                .map(|pk| SecretBytes::from(pk.as_slice()))
+                .ok_or_else(|| err_msg!(Input, "Undefined public key")),
+            _ => Err(err_msg!(Unsupported, "Unsupported key format")),
+        }
+    }
+
     /// Remove an existing key from the store
     pub async fn remove_key(&mut self, category: KeyCategory, ident: &str) -> Result<()> {
-        // normalize ident
-        let ident = EncodedVerKey::from_str(&ident)
-            .and_then(|k| k.as_base58())
-            .map_err(err_map!("Invalid key"))?
-            .long_form();
-
-        self.0
-            .update(
-                EntryKind::Key,
-                EntryOperation::Remove,
-                category.as_str(),
-                &ident,
-                None,
-                None,
-                None,
-            )
-            .await
+        let ident = key_entry_ident(&category, ident)?;
+
+        self.update_entry(
+            EntryKind::Key,
+            EntryOperation::Remove,
+            category.as_str(),
+            &ident,
+            None,
+            None,
+            None,
+        )
+        .await
     }
 
     // pub async fn scan_keys(
@@ -505,6 +1200,9 @@ impl<Q: QueryBackend> Session<Q> {
     // }
 
     /// Replace the metadata and tags on an existing key in the store
+    ///
+    /// The export policy of the key is determined when it is created and is
+    /// not changed by updating the metadata.
     pub async fn update_key(
         &mut self,
         category: KeyCategory,
@@ -512,15 +1210,10 @@ impl<Q: QueryBackend> Session<Q> {
         metadata: Option<&str>,
         tags: Option<&[EntryTag]>,
     ) -> Result<()> {
-        // normalize ident
-        let ident = EncodedVerKey::from_str(&ident)
-            .and_then(|k| k.as_base58())
-            .map_err(err_map!("Invalid key"))?
-            .long_form();
+        let ident = key_entry_ident(&category, ident)?;
 
         let row = self
-            .0
-            .fetch(EntryKind::Key, category.as_str(), &ident, true)
+            .fetch_entry(EntryKind::Key, category.as_str(), &ident, true)
             .await?
             .ok_or_else(|| err_msg!(NotFound, "Key entry not found"))?;
 
@@ -528,17 +1221,16 @@ impl<Q: QueryBackend> Session<Q> {
         params.metadata = metadata.map(str::to_string);
         let mut value = params.to_vec()?;
 
-        self.0
-            .update(
-                EntryKind::Key,
-                EntryOperation::Replace,
-                category.as_str(),
-                &ident,
-                Some(&value),
-                tags,
-                None,
-            )
-            .await?;
+        self.update_entry(
+            EntryKind::Key,
+            EntryOperation::Replace,
+            category.as_str(),
+            &ident,
+            Some(&value),
+            tags,
+            None,
+        )
+        .await?;
         value.zeroize();
 
         Ok(())
@@ -546,13 +1238,119 @@ impl<Q: QueryBackend> Session<Q> {
 
     /// Sign a message using an existing keypair in the store identified by `key_ident`
     pub async fn sign_message(&mut self, key_ident: &str, data: &[u8]) -> Result<Vec<u8>> {
+        self.sign_message_format(key_ident, data, SignatureFormat::Raw)
+            .await
+    }
+
+    /// Sign a message using an existing keypair in the store identified by `key_ident`,
+    /// producing a signature in the requested format
+    ///
+    /// ECDSA keys support both raw (`r || s`) and DER-encoded signatures, while
+    /// Ed25519 keys only support raw signatures.
+    pub async fn sign_message_format(
+        &mut self,
+        key_ident: &str,
+        data: &[u8],
+        format: SignatureFormat,
+    ) -> Result<Vec<u8>> {
+        if let Some(key) = self
+            .fetch_key(KeyCategory::KeyPair, key_ident, false)
+            .await?
+        {
+            sign_message(&key.params, data, format)
+        } else {
+            return Err(err_msg!(NotFound, "Unknown key"));
+        }
+    }
+
+    /// Produce a JWS over `payload` using an existing keypair in the store
+    /// identified by `key_ident`
+    ///
+    /// The `alg` and `kid` headers are set according to the key, and any
+    /// additional `headers` are included in the protected header. The payload
+    /// is omitted from the result when `detached` is set.
+    pub async fn sign_jws(
+        &mut self,
+        key_ident: &str,
+        payload: &[u8],
+        headers: Option<&serde_json::Map<String, serde_json::Value>>,
+        serialization: JwsSerialization,
+        detached: bool,
+    ) -> Result<String> {
         if let Some(key) = self
             .fetch_key(KeyCategory::KeyPair, key_ident, false)
             .await?
         {
-            let sk = key.private_key()?;
-            sk.sign(&data)
-                .map_err(|e| err_msg!(Unexpected, "Signature error: {}", e))
+            sign_jws(
+                &key.ident,
+                &key.params,
+                payload,
+                headers,
+                serialization,
+                detached,
+            )
+        } else {
+            return Err(err_msg!(NotFound, "Unknown key"));
+        }
+    }
+
+    /// Derive a shared key using ECDH between an existing keypair in the store
+    /// identified by `local_ident` and a peer public key identifier
+    ///
+    /// The raw shared secret is never returned: the derived key material is
+    /// produced by the provided key derivation function. X25519 (including
+    /// converted Ed25519) and P-256 keypairs are supported; other NIST curves
+    /// such as P-384 and P-521 are not.
+    pub async fn key_agreement(
+        &mut self,
+        local_ident: &str,
+        peer_public_key: &str,
+        kdf: &KeyAgreementKdf,
+    ) -> Result<SecretBytes> {
+        if let Some(key) = self
+            .fetch_key(KeyCategory::KeyPair, local_ident, false)
+            .await?
+        {
+            key_agreement(&key.params, peer_public_key, kdf)
+        } else {
+            return Err(err_msg!(NotFound, "Unknown key"));
+        }
+    }
+
+    /// Encrypt a message using an existing symmetric key in the store identified
+    /// by `key_ident`
+    ///
+    /// The result contains the random nonce, followed by the ciphertext and
+    /// authentication tag.
+    pub async fn encrypt_with_key(
+        &mut self,
+        key_ident: &str,
+        data: &[u8],
+        aad: Option<&[u8]>,
+    ) -> Result<Vec<u8>> {
+        if let Some(key) = self
+            .fetch_key(KeyCategory::SecretKey, key_ident, false)
+            .await?
+        {
+            encrypt_message(&key.params, data, aad.unwrap_or_default())
+        } else {
+            return Err(err_msg!(NotFound, "Unknown key"));
+        }
+    }
+
+    /// Decrypt a message produced by `encrypt_with_key` using an existing
+    /// symmetric key in the store identified by `key_ident`
+    pub async fn decrypt_with_key(
+        &mut self,
+        key_ident: &str,
+        data: &[u8],
+        aad: Option<&[u8]>,
+    ) -> Result<SecretBytes> {
+        if let Some(key) = self
+            .fetch_key(KeyCategory::SecretKey, key_ident, false)
+            .await?
+        {
+            decrypt_message(&key.params, data, aad.unwrap_or_default())
         } else {
             return Err(err_msg!(NotFound, "Unknown key"));
         }
@@ -599,14 +1397,64 @@ impl<Q: QueryBackend> Session<Q> {
         }
     }
 
+    /// Pack a DIDComm v2 message for the given recipient keys
+    ///
+    /// When `from_key_ident` identifies an existing keypair in the store, the
+    /// message is encrypted so that the recipients can authenticate the sender
+    /// (authcrypt), and otherwise it is anonymously encrypted (anoncrypt).
+    /// Recipient keys must be X25519 or P-256 key identifiers.
+    pub async fn pack_message_v2(
+        &mut self,
+        recipient_keys: impl IntoIterator<Item = &str>,
+        from_key_ident: Option<&str>,
+        data: &[u8],
+        enc: JweEncryption,
+    ) -> Result<Vec<u8>> {
+        let sender = if let Some(ident) = from_key_ident {
+            Some(
+                self.fetch_key(KeyCategory::KeyPair, ident, false)
+                    .await?
+                    .ok_or_else(|| err_msg!(NotFound, "Unknown sender key"))?,
+            )
+        } else {
+            None
+        };
+        let recipient_keys = recipient_keys.into_iter().collect::<Vec<_>>();
+        pack_message_v2(
+            &recipient_keys,
+            sender.as_ref().map(|key| (key.ident.as_str(), &key.params)),
+            data,
+            enc,
+        )
+    }
+
+    /// Unpack a DIDComm v2 message, automatically looking up the recipient keypair
+    ///
+    /// Returns the decrypted message, the identifier of the recipient key, and
+    /// the identifier of the sender key for authenticated messages.
+    pub async fn unpack_message_v2(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(SecretBytes, String, Option<String>)> {
+        unpack_message_v2(data, self).await
+    }
+
     /// Commit the pending transaction
-    pub async fn commit(self) -> Result<()> {
-        Ok(self.0.close(true).await?)
+    pub async fn commit(mut self) -> Result<()> {
+        let inner = self.inner.take().ok_or_else(session_closed)?;
+        self.interrupt.run(inner.close(true)).await?
     }
 
     /// Roll back the pending transaction
-    pub async fn rollback(self) -> Result<()> {
-        Ok(self.0.close(false).await?)
+    ///
+    /// This has no effect if the session was already closed following an
+    /// interrupted operation.
+    pub async fn rollback(mut self) -> Result<()> {
+        if let Some(inner) = self.inner.take() {
+            inner.close(false).await
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -631,10 +1479,31 @@ impl<'a, Q: QueryBackend> KeyLookup<'a> for &'a mut Session<Q> {
     }
 }
 
+impl<'a, Q: QueryBackend> JweKeyLookup<'a> for &'a mut Session<Q> {
+    fn find<'f>(self, kids: &'f [String]) -> BoxFuture<'f, Option<(usize, KeyEntry)>>
+    where
+        'a: 'f,
+    {
+        Box::pin(async move {
+            for (idx, kid) in kids.iter().enumerate() {
+                if let Ok(Some(key)) = self.fetch_key(KeyCategory::KeyPair, kid, false).await {
+                    return Some((idx, key));
+                }
+            }
+            None
+        })
+    }
+}
+
+/// A stream of committed changes to the records of a store
+pub type ChangeStream = Pin<Box<dyn Stream<Item = Result<EntryChange>> + Send>>;
+
 /// An active record scan of a store backend
 pub struct Scan<'s, T> {
     stream: Option<Pin<Box<dyn Stream<Item = Result<Vec<T>>> + Send + 's>>>,
     page_size: usize,
+    interrupt: Interrupt,
+    interrupted: Option<ErrorKind>,
 }
 
 impl<'s, T> Scan<'s, T> {
@@ -645,13 +1514,40 @@ impl<'s, T> Scan<'s, T> {
         Self {
             stream: Some(stream.boxed()),
             page_size,
+            interrupt: Interrupt::new(None),
+            interrupted: None,
         }
     }
 
+    /// Set the time limit for each subsequent fetch, overriding the default
+    /// timeout of the store
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.interrupt.timeout = timeout;
+    }
+
+    /// Get a handle which may be used to abort the scan
+    ///
+    /// An interrupted fetch fails with `ErrorKind::Cancelled` or
+    /// `ErrorKind::Timeout`, and the scan is closed. Any later fetch fails
+    /// with the same kind of error.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.interrupt.handle.clone()
+    }
+
     /// Fetch the next set of result rows
     pub async fn fetch_next(&mut self) -> Result<Option<Vec<T>>> {
+        if let Some(kind) = self.interrupted {
+            return Err(Error::from_msg(
+                kind,
+                "Scan was interrupted and has been closed",
+            ));
+        }
         if let Some(mut s) = self.stream.take() {
-            match s.try_next().await? {
+            let result = self.interrupt.run(s.try_next()).await.map_err(|err| {
+                self.interrupted.replace(err.kind());
+                err
+            })?;
+            match result? {
                 Some(val) => {
                     if val.len() == self.page_size {
                         self.stream.replace(s);
@@ -660,6 +1556,9 @@ impl<'s, T> Scan<'s, T> {
                 }
                 None => Ok(None),
             }
+        } else if self.interrupt.handle.is_cancelled() {
+            self.interrupted.replace(ErrorKind::Cancelled);
+            Err(err_msg!(Cancelled, "Operation cancelled"))
         } else {
             Ok(None)
         }
@@ -673,3 +1572,50 @@ impl<S> Debug for Scan<'_, S> {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::future::block_on;
+
+    #[test]
+    fn interrupt_timeout() {
+        let interrupt = Interrupt::new(Some(Duration::from_millis(10)));
+        let result = block_on(interrupt.run(future::pending::<()>()));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Timeout);
+        assert_eq!(block_on(interrupt.run(future::ready(1))).unwrap(), 1);
+    }
+
+    #[test]
+    fn interrupt_cancel() {
+        let interrupt = Interrupt::new(None);
+        let handle = interrupt.handle.clone();
+        let result = block_on(future::zip(
+            interrupt.run(future::pending::<()>()),
+            async move { handle.cancel() },
+        ));
+        assert_eq!(result.0.unwrap_err().kind(), ErrorKind::Cancelled);
+        assert!(interrupt.handle.is_cancelled());
+        // subsequent operations are not started
+        let result = block_on(interrupt.run(future::ready(1)));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Cancelled);
+    }
+
+    #[test]
+    fn scan_interrupted() {
+        let mut scan = Scan::<()>::new(futures_lite::stream::pending(), 1);
+        scan.set_timeout(Some(Duration::from_millis(10)));
+        for _ in 0..2 {
+            let result = block_on(scan.fetch_next());
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::Timeout);
+        }
+
+        let mut scan = Scan::<()>::new(futures_lite::stream::empty(), 1);
+        scan.cancel_handle().cancel();
+        for _ in 0..2 {
+            let result = block_on(scan.fetch_next());
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::Cancelled);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::fmt::{self, Debug, Formatter};
 use std::mem;
 use std::ops::Deref;
@@ -75,6 +76,133 @@ impl PartialEq for Entry {
     }
 }
 
+/// A committed change to an entry record, reported by `Store::subscribe`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryChange {
+    /// The type of update performed
+    pub operation: EntryOperation,
+
+    /// The category of the entry record
+    pub category: String,
+
+    /// The name of the entry record, or `None` for a removal of all matching
+    /// records in the category
+    pub name: Option<String>,
+}
+
+/// Usage statistics for the decrypted entry cache of a store
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EntryCacheStats {
+    /// The maximum number of entries held by the cache
+    pub capacity: usize,
+    /// The number of entries currently held by the cache
+    pub entries: usize,
+    /// The number of fetches answered from the cache
+    pub hits: u64,
+    /// The number of fetches which were not found in the cache
+    pub misses: u64,
+}
+
+/// Storage limits applied to the entries of a profile
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProfileQuota {
+    /// The maximum number of entries in the profile, if limited
+    pub max_items: Option<i64>,
+    /// The maximum total size of the encrypted entry values in the profile, if limited
+    pub max_bytes: Option<i64>,
+}
+
+impl ProfileQuota {
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if self.max_items.map(|max| max < 0).unwrap_or(false)
+            || self.max_bytes.map(|max| max < 0).unwrap_or(false)
+        {
+            Err(err_msg!(Input, "Profile quota limits must not be negative"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check the usage of the profile following an update against the quota
+    pub(crate) fn check(&self, items: i64, bytes: i64) -> Result<(), Error> {
+        if self.max_items.map(|max| items > max).unwrap_or(false) {
+            Err(err_msg!(
+                QuotaExceeded,
+                "Profile entry count limit exceeded"
+            ))
+        } else if self.max_bytes.map(|max| bytes > max).unwrap_or(false) {
+            Err(err_msg!(
+                QuotaExceeded,
+                "Profile storage size limit exceeded"
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The current storage usage of a profile
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProfileUsage {
+    /// The number of active (unexpired and not deleted) entries in the profile
+    pub items: i64,
+    /// The total size of the encrypted entry values in the profile
+    pub bytes: i64,
+    /// The limits applied to the profile
+    pub quota: ProfileQuota,
+}
+
+/// The categories of entries which are retained when removed
+///
+/// Removed entries in a matching category are marked as deleted rather than
+/// being erased. Deleted entries are hidden from all queries until they are
+/// restored, or are erased once they fall outside of the retention window.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SoftDelete {
+    /// Removed entries are erased immediately
+    Disabled,
+    /// Removed entries are retained in all categories
+    All,
+    /// Removed entries are retained in the listed categories
+    Categories(BTreeSet<String>),
+}
+
+impl SoftDelete {
+    /// Check whether removed entries are retained for a category
+    pub(crate) fn applies(&self, category: &str) -> bool {
+        match self {
+            Self::Disabled => false,
+            Self::All => true,
+            Self::Categories(categories) => categories.contains(category),
+        }
+    }
+}
+
+impl Default for SoftDelete {
+    fn default() -> Self {
+        Self::Disabled
+    }
+}
+
+/// The categories of entries for which previous versions are retained
+///
+/// When an entry in a matching category is replaced or removed, its previous
+/// value and tags are recorded in the history of the entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntryHistory {
+    /// The categories for which previous versions are retained
+    pub categories: BTreeSet<String>,
+    /// The maximum number of previous versions retained for each entry, if limited
+    pub max_versions: Option<usize>,
+}
+
+impl EntryHistory {
+    /// Check whether previous versions are retained for a category
+    pub(crate) fn applies(&self, category: &str) -> bool {
+        self.categories.contains(category)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum EntryKind {
     Key = 1,
@@ -92,6 +220,54 @@ pub enum EntryOperation {
     Remove,
 }
 
+/// Store operations recorded in the audit log
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AuditOperation {
+    /// A new entry was inserted
+    Insert,
+    /// An existing entry was replaced
+    Replace,
+    /// An existing entry was removed
+    Remove,
+    /// All matching entries in a category were removed
+    RemoveAll,
+    /// A profile was created
+    CreateProfile,
+    /// A profile was removed
+    RemoveProfile,
+    /// The store wrapping key was changed
+    Rekey,
+    /// A deleted entry was restored
+    Undelete,
+    /// Deleted entries of a profile were erased
+    PurgeDeleted,
+}
+
+/// A verified record read from the audit log of a store
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// The sequence number of the record in the log
+    pub id: i64,
+
+    /// The operation performed
+    pub operation: AuditOperation,
+
+    /// The name of the affected profile, if any
+    pub profile: Option<String>,
+
+    /// The category of the affected entries, if any
+    pub category: Option<String>,
+
+    /// The hex-encoded SHA-256 hash of the affected entry name, if any
+    pub name_hash: Option<String>,
+
+    /// The time at which the operation was recorded
+    pub timestamp: Expiry,
+
+    /// The actor label supplied by the caller, if any
+    pub actor: Option<String>,
+}
+
 /// A tag on an entry record in the store
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Zeroize)]
 pub enum EntryTag {
@@ -137,6 +137,86 @@ macro_rules! backend_tests {
             })
         }
 
+        #[test]
+        fn keypair_pack_unpack_v2() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_keypair_pack_unpack_v2(&db).await;
+            })
+        }
+
+        #[test]
+        fn keypair_key_agreement() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_keypair_key_agreement(&db).await;
+            })
+        }
+
+        #[test]
+        fn keypair_x25519() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_keypair_x25519(&db).await;
+            })
+        }
+
+        #[test]
+        fn keypair_secp256k1() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_keypair_secp256k1(&db).await;
+            })
+        }
+
+        #[test]
+        fn keypair_p256() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_keypair_p256(&db).await;
+            })
+        }
+
+        #[test]
+        fn keypair_bls() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_keypair_bls(&db).await;
+            })
+        }
+
+        #[test]
+        fn secret_key_encrypt_decrypt() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_secret_key_encrypt_decrypt(&db).await;
+            })
+        }
+
+        #[test]
+        fn import_key() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_import_key(&db).await;
+            })
+        }
+
+        #[test]
+        fn export_key() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_export_key(&db).await;
+            })
+        }
+
+        #[test]
+        fn keypair_jws() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_keypair_jws(&db).await;
+            })
+        }
+
         #[test]
         fn txn_rollback() {
             block_on(async {
@@ -176,13 +256,71 @@ macro_rules! backend_tests {
                 super::utils::db_txn_fetch_for_update(&db).await;
             })
         }
+
+        #[test]
+        fn subscribe() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_subscribe(&db).await;
+            })
+        }
+
+        #[test]
+        fn profile_quota() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_profile_quota(&db).await;
+            })
+        }
+
+        #[test]
+        fn txn_cancel() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_txn_cancel(&db).await;
+            })
+        }
+
+        #[test]
+        fn txn_closure() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_txn_closure(&db).await;
+            })
+        }
+
+        #[test]
+        fn txn_multi_profile() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_txn_multi_profile(&db).await;
+            })
+        }
+
+        #[test]
+        fn soft_delete() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_soft_delete(&db).await;
+            })
+        }
+
+        #[test]
+        fn entry_history() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_entry_history(&db).await;
+            })
+        }
     };
 }
 
 #[cfg(feature = "sqlite")]
 mod sqlite {
     use aries_askar::sqlite::{SqliteStore, SqliteStoreOptions};
-    use aries_askar::{generate_raw_wrap_key, ManageBackend, Store, WrapKeyMethod};
+    use aries_askar::{
+        generate_raw_wrap_key, AuditOperation, ErrorKind, ManageBackend, Store, WrapKeyMethod,
+    };
     use std::path::Path;
 
     #[test]
@@ -235,6 +373,27 @@ mod sqlite {
         })
     }
 
+    #[test]
+    fn open_invalid_db() {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4().to_string());
+        std::fs::write(&fname, vec![0x55u8; 4096]).expect("Error writing test file");
+        let key = generate_raw_wrap_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let err = SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .open_backend(Some(WrapKeyMethod::RawKey), key.as_ref(), None)
+                .await
+                .expect_err("Expected error opening invalid database");
+            assert_eq!(err.kind(), ErrorKind::Corrupt);
+            // SQLITE_NOTADB
+            assert_eq!(err.code(), Some("26"));
+
+            std::fs::remove_file(&fname).expect("Error removing test file");
+        })
+    }
+
     #[test]
     fn rekey_db() {
         env_logger::builder().is_test(true).try_init().unwrap_or(());
@@ -274,6 +433,181 @@ mod sqlite {
         })
     }
 
+    #[test]
+    fn backup_db() {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4().to_string());
+        let key = generate_raw_wrap_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let store = SqliteStoreOptions::in_memory()
+                .provision(WrapKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning sqlite store");
+
+            let mut conn = store.session(None).await.expect("Error starting session");
+            conn.insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            drop(conn);
+
+            store
+                .backup_to(fname.as_str())
+                .await
+                .expect("Error creating backup");
+            assert_eq!(Path::new(&fname).exists(), true);
+            assert!(store.backup_to(fname.as_str()).await.is_err());
+
+            let backup = SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .open_backend(Some(WrapKeyMethod::RawKey), key.as_ref(), None)
+                .await
+                .expect("Error opening sqlite store backup");
+            let mut conn = backup.session(None).await.expect("Error starting session");
+            let row = conn
+                .fetch("category", "name", false)
+                .await
+                .expect("Error fetching test row")
+                .expect("Expected row in backup");
+            assert_eq!(row.value.as_ref(), b"value");
+            drop(conn);
+            backup.close().await.expect("Error closing store backup");
+
+            store.close().await.expect("Error closing sqlite store");
+            SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .remove_backend()
+                .await
+                .expect("Error removing sqlite store backup");
+        })
+    }
+
+    #[test]
+    fn entry_cache() {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let key = generate_raw_wrap_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let store = SqliteStoreOptions::new("sqlite://:memory:?entry_cache_size=10")
+                .expect("Error initializing sqlite store options")
+                .provision(WrapKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning sqlite store");
+
+            let mut conn = store.session(None).await.expect("Error starting session");
+            conn.insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            for _ in 0..2 {
+                let row = conn
+                    .fetch("category", "name", false)
+                    .await
+                    .expect("Error fetching test row")
+                    .expect("Expected row");
+                assert_eq!(row.value.as_ref(), b"value");
+            }
+            let stats = store.entry_cache_stats().expect("Expected entry cache");
+            assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 1));
+
+            conn.replace("category", "name", b"updated", None, None)
+                .await
+                .expect("Error replacing test row");
+            let row = conn
+                .fetch("category", "name", false)
+                .await
+                .expect("Error fetching test row")
+                .expect("Expected row");
+            assert_eq!(row.value.as_ref(), b"updated");
+
+            conn.remove_all("category", None)
+                .await
+                .expect("Error removing test rows");
+            assert!(conn
+                .fetch("category", "name", false)
+                .await
+                .expect("Error fetching test row")
+                .is_none());
+            drop(conn);
+            store.close().await.expect("Error closing sqlite store");
+        })
+    }
+
+    #[test]
+    fn audit_log() {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let key = generate_raw_wrap_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let mut store = SqliteStoreOptions::new("sqlite://:memory:?audit=true")
+                .expect("Error initializing sqlite store options")
+                .provision(WrapKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning sqlite store");
+
+            store.set_audit_actor(Some("admin"));
+            let profile = store
+                .create_profile(None)
+                .await
+                .expect("Error creating profile");
+
+            let mut conn = store.session(None).await.expect("Error starting session");
+            conn.set_audit_actor(Some("user"));
+            conn.insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            conn.replace("category", "name", b"updated", None, None)
+                .await
+                .expect("Error replacing test row");
+            conn.remove("category", "name")
+                .await
+                .expect("Error removing test row");
+            conn.remove_all("category", None)
+                .await
+                .expect("Error removing test rows");
+            drop(conn);
+
+            let new_key = generate_raw_wrap_key(None).expect("Error creating raw key");
+            store
+                .rekey(WrapKeyMethod::RawKey, new_key)
+                .await
+                .expect("Error rekeying store");
+
+            let records = store
+                .scan_audit(..)
+                .await
+                .expect("Error scanning audit log");
+            assert_eq!(
+                records
+                    .iter()
+                    .map(|rec| (rec.id, rec.operation))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (1, AuditOperation::CreateProfile),
+                    (2, AuditOperation::Insert),
+                    (3, AuditOperation::Replace),
+                    (4, AuditOperation::Remove),
+                    (5, AuditOperation::Rekey),
+                ]
+            );
+            assert_eq!(records[0].profile.as_deref(), Some(profile.as_str()));
+            assert_eq!(records[0].actor.as_deref(), Some("admin"));
+            assert_eq!(records[1].category.as_deref(), Some("category"));
+            assert!(records[1].name_hash.is_some());
+            assert_eq!(records[1].actor.as_deref(), Some("user"));
+            assert_eq!(records[4].profile, None);
+
+            let records = store
+                .scan_audit(2..4)
+                .await
+                .expect("Error scanning audit log");
+            assert_eq!(
+                records.iter().map(|rec| rec.id).collect::<Vec<_>>(),
+                vec![2, 3]
+            );
+            store.close().await.expect("Error closing sqlite store");
+        })
+    }
+
     async fn init_db() -> Store<SqliteStore> {
         env_logger::builder().is_test(true).try_init().unwrap_or(());
         let key = generate_raw_wrap_key(None).expect("Error creating raw key");
@@ -307,6 +641,232 @@ mod sqlite {
     }
 }
 
+#[cfg(feature = "memory")]
+mod memory {
+    use aries_askar::memory::{MemoryStore, MemoryStoreOptions};
+    use aries_askar::{generate_raw_wrap_key, ErrorKind, ManageBackend, Store, WrapKeyMethod};
+
+    #[test]
+    fn create_open_remove_named() {
+        let key = generate_raw_wrap_key(None).expect("Error creating raw key");
+        let db_url = format!("memory://test-{}", uuid::Uuid::new_v4().to_string());
+
+        block_on(async move {
+            let store = db_url
+                .as_str()
+                .provision_backend(WrapKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning memory store");
+
+            let store2 = db_url
+                .as_str()
+                .open_backend(Some(WrapKeyMethod::RawKey), key.as_ref(), None)
+                .await
+                .expect("Error opening memory store");
+            assert_eq!(store.get_profile_name(), store2.get_profile_name());
+            store2.close().await.expect("Error closing memory store");
+            store.close().await.expect("Error closing memory store");
+
+            assert_eq!(
+                db_url
+                    .as_str()
+                    .remove_backend()
+                    .await
+                    .expect("Error removing memory store"),
+                true
+            );
+            db_url
+                .as_str()
+                .open_backend(Some(WrapKeyMethod::RawKey), key.as_ref(), None)
+                .await
+                .expect_err("Expected open failure");
+        })
+    }
+
+    #[test]
+    fn txn_isolation() {
+        block_on(async {
+            let db = init_db().await;
+            let mut txn = db
+                .transaction(None)
+                .await
+                .expect("Error starting transaction");
+            txn.insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+
+            // other sessions may proceed while the transaction is open, and
+            // do not observe its changes until it is committed
+            let mut conn = db.session(None).await.expect("Error starting session");
+            assert_eq!(
+                conn.fetch("category", "name", false)
+                    .await
+                    .expect("Error fetching test row"),
+                None
+            );
+            txn.commit().await.expect("Error committing transaction");
+
+            let row = conn
+                .fetch("category", "name", false)
+                .await
+                .expect("Error fetching test row")
+                .expect("Expected row");
+            assert_eq!(row.tags, None);
+        })
+    }
+
+    #[test]
+    fn txn_conflict() {
+        block_on(async {
+            let db = init_db().await;
+
+            // overlapping transactions inserting the same entry
+            let mut txn1 = db
+                .transaction(None)
+                .await
+                .expect("Error starting transaction");
+            let mut txn2 = db
+                .transaction(None)
+                .await
+                .expect("Error starting transaction");
+            txn1.insert("category", "name", b"value1", None, None)
+                .await
+                .expect("Error inserting test row");
+            txn2.insert("category", "name", b"value2", None, None)
+                .await
+                .expect("Error inserting test row");
+            txn1.commit().await.expect("Error committing transaction");
+            let err = txn2.commit().await.expect_err("Expected duplicate entry");
+            assert_eq!(err.kind(), ErrorKind::Duplicate);
+
+            let mut conn = db.session(None).await.expect("Error starting session");
+            let row = conn
+                .fetch("category", "name", false)
+                .await
+                .expect("Error fetching test row")
+                .expect("Expected row");
+            assert_eq!(row.value, &b"value1"[..]);
+
+            // an entry fetched for update is replaced by another session
+            let mut txn = db
+                .transaction(None)
+                .await
+                .expect("Error starting transaction");
+            txn.fetch("category", "name", true)
+                .await
+                .expect("Error fetching test row")
+                .expect("Expected row");
+            conn.replace("category", "name", b"value3", None, None)
+                .await
+                .expect("Error replacing test row");
+            txn.replace("category", "name", b"value4", None, None)
+                .await
+                .expect("Error replacing test row");
+            let err = txn.commit().await.expect_err("Expected conflict");
+            assert_eq!(err.kind(), ErrorKind::Busy);
+            let row = conn
+                .fetch("category", "name", false)
+                .await
+                .expect("Error fetching test row")
+                .expect("Expected row");
+            assert_eq!(row.value, &b"value3"[..]);
+        })
+    }
+
+    async fn init_db() -> Store<MemoryStore> {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let key = generate_raw_wrap_key(None).expect("Error creating raw key");
+        MemoryStoreOptions::in_memory()
+            .provision(WrapKeyMethod::RawKey, key, None, false)
+            .await
+            .expect("Error provisioning memory store")
+    }
+
+    backend_tests!(init_db());
+}
+
+#[cfg(all(feature = "any", feature = "memory"))]
+mod custom {
+    use aries_askar::any::{register_backend, AnyBackend, AnyStore, BackendFactory};
+    use aries_askar::future::BoxFuture;
+    use aries_askar::memory::MemoryStoreOptions;
+    use aries_askar::{
+        generate_raw_wrap_key, Error, ErrorKind, ManageBackend, PassKey, WrapKeyMethod,
+    };
+
+    const SCHEME: &str = "custom-memory";
+
+    struct MemoryFactory;
+
+    impl BackendFactory for MemoryFactory {
+        fn open_backend<'a>(
+            &'a self,
+            uri: &'a str,
+            method: Option<WrapKeyMethod>,
+            pass_key: PassKey<'a>,
+            profile: Option<&'a str>,
+        ) -> BoxFuture<'a, Result<AnyBackend, Error>> {
+            Box::pin(async move {
+                let store = MemoryStoreOptions::new(uri)?
+                    .open(method, pass_key, profile)
+                    .await?;
+                Ok(AnyBackend::custom(store.into_inner()))
+            })
+        }
+
+        fn provision_backend<'a>(
+            &'a self,
+            uri: &'a str,
+            method: WrapKeyMethod,
+            pass_key: PassKey<'a>,
+            profile: Option<&'a str>,
+            recreate: bool,
+        ) -> BoxFuture<'a, Result<AnyBackend, Error>> {
+            Box::pin(async move {
+                let store = MemoryStoreOptions::new(uri)?
+                    .provision(method, pass_key, profile, recreate)
+                    .await?;
+                Ok(AnyBackend::custom(store.into_inner()))
+            })
+        }
+
+        fn remove_backend<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<bool, Error>> {
+            Box::pin(async move { MemoryStoreOptions::new(uri)?.remove().await })
+        }
+    }
+
+    fn register() {
+        // registration is shared between test threads
+        register_backend(SCHEME, MemoryFactory).unwrap_or(());
+    }
+
+    #[test]
+    fn register_duplicate() {
+        register();
+        register_backend(SCHEME, MemoryFactory).expect_err("Expected duplicate scheme");
+    }
+
+    #[test]
+    fn register_builtin() {
+        let err = register_backend("memory", MemoryFactory).expect_err("Expected reserved scheme");
+        assert_eq!(err.kind(), ErrorKind::Duplicate);
+    }
+
+    async fn init_db() -> AnyStore {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        register();
+        let key = generate_raw_wrap_key(None).expect("Error creating raw key");
+        let db_url = format!("{}://", SCHEME);
+        db_url
+            .as_str()
+            .provision_backend(WrapKeyMethod::RawKey, key.as_ref(), None, false)
+            .await
+            .expect("Error provisioning custom store")
+    }
+
+    backend_tests!(init_db());
+}
+
 #[cfg(feature = "pg_test")]
 mod postgres {
     use aries_askar::postgres::test_db::TestDB;
@@ -1,6 +1,11 @@
 use aries_askar::{
-    verify_signature, Backend, Entry, EntryTag, ErrorKind, KeyAlg, Store, TagFilter,
+    derive_verkey, verify_jws, verify_signature, Backend, Entry, EntryChange, EntryHistory,
+    EntryOperation, EntryTag, ErrorKind, JweEncryption, JwsSerialization, KeyAgreementKdf, KeyAlg,
+    KeyCategory, KeyFormat, ProfileQuota, SignatureFormat, SoftDelete, Store, TagFilter,
+    MAX_DERIVED_KEY_LENGTH,
 };
+use futures_lite::stream::StreamExt;
+use std::time::Duration;
 
 const ERR_PROFILE: &'static str = "Error creating profile";
 const ERR_SESSION: &'static str = "Error starting session";
@@ -13,14 +18,29 @@ const ERR_REQ_ERR: &'static str = "Expected error";
 const ERR_INSERT: &'static str = "Error inserting test row";
 const ERR_REPLACE: &'static str = "Error replacing test row";
 const ERR_REMOVE_ALL: &'static str = "Error removing test rows";
+const ERR_UNDELETE: &'static str = "Error restoring test row";
+const ERR_PURGE: &'static str = "Error purging deleted rows";
+const ERR_HISTORY: &'static str = "Error fetching entry history";
 const ERR_SCAN: &'static str = "Error starting scan";
+const ERR_SUBSCRIBE: &'static str = "Error subscribing to changes";
+const ERR_CHANGE: &'static str = "Error receiving change";
+const ERR_QUOTA: &'static str = "Error setting profile quota";
+const ERR_USAGE: &'static str = "Error fetching profile usage";
 const ERR_SCAN_NEXT: &'static str = "Error fetching scan rows";
 const ERR_CREATE_KEYPAIR: &'static str = "Error creating keypair";
 const ERR_FETCH_KEY: &'static str = "Error fetching key";
 const ERR_SIGN: &'static str = "Error signing message";
+const ERR_CONVERT_KEYPAIR: &'static str = "Error converting keypair";
 const ERR_VERIFY: &'static str = "Error verifying signature";
 const ERR_PACK: &'static str = "Error packing message";
 const ERR_UNPACK: &'static str = "Error unpacking message";
+const ERR_CREATE_KEY: &'static str = "Error creating key";
+const ERR_ENCRYPT: &'static str = "Error encrypting message";
+const ERR_DECRYPT: &'static str = "Error decrypting message";
+const ERR_IMPORT_KEY: &'static str = "Error importing key";
+const ERR_EXPORT_KEY: &'static str = "Error exporting key";
+const ERR_UPDATE_KEY: &'static str = "Error updating key";
+const ERR_KEY_AGREEMENT: &'static str = "Error performing key agreement";
 
 pub async fn db_create_remove_profile<DB: Backend>(db: &Store<DB>) {
     let profile = db.create_profile(None).await.expect(ERR_PROFILE);
@@ -564,6 +584,814 @@ pub async fn db_keypair_pack_unpack_auth<DB: Backend>(db: &Store<DB>) {
     assert_eq!(p_send, Some(sender_key.encoded_verkey().unwrap()));
 }
 
+pub async fn db_keypair_pack_unpack_v2<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let msg = b"message".to_vec();
+    for alg in &[KeyAlg::X25519, KeyAlg::P256] {
+        let sender_key = conn
+            .create_keypair(alg.clone(), None, None, None)
+            .await
+            .expect(ERR_CREATE_KEYPAIR);
+        let recip_key = conn
+            .create_keypair(alg.clone(), None, None, None)
+            .await
+            .expect(ERR_CREATE_KEYPAIR);
+
+        // anoncrypt
+        for enc in &[
+            JweEncryption::XChaCha20Poly1305,
+            JweEncryption::Aes256CbcHmacSha512,
+        ] {
+            let packed = conn
+                .pack_message_v2(vec![recip_key.ident.as_str()], None, &msg, *enc)
+                .await
+                .expect(ERR_PACK);
+            let (unpacked, p_recip, p_send) =
+                conn.unpack_message_v2(&packed).await.expect(ERR_UNPACK);
+            assert_eq!(unpacked, msg);
+            assert_eq!(p_recip, recip_key.ident);
+            assert_eq!(p_send, None);
+        }
+
+        // authcrypt
+        let packed = conn
+            .pack_message_v2(
+                vec![recip_key.ident.as_str()],
+                Some(&sender_key.ident),
+                &msg,
+                JweEncryption::Aes256CbcHmacSha512,
+            )
+            .await
+            .expect(ERR_PACK);
+        let (unpacked, p_recip, p_send) = conn.unpack_message_v2(&packed).await.expect(ERR_UNPACK);
+        assert_eq!(unpacked, msg);
+        assert_eq!(p_recip, recip_key.ident);
+        assert_eq!(p_send, Some(sender_key.ident.clone()));
+
+        let err = conn
+            .pack_message_v2(
+                vec![recip_key.ident.as_str()],
+                Some(&sender_key.ident),
+                &msg,
+                JweEncryption::XChaCha20Poly1305,
+            )
+            .await
+            .expect_err(ERR_REQ_ERR);
+        assert_eq!(err.kind(), ErrorKind::Input);
+    }
+
+    // the recipient keypair must be present in the store
+    let other_key = derive_verkey(KeyAlg::X25519, b"testseed000000000000000000000002")
+        .expect("Error deriving verkey");
+    let packed = conn
+        .pack_message_v2(
+            vec![other_key.as_str()],
+            None,
+            &msg,
+            JweEncryption::default(),
+        )
+        .await
+        .expect(ERR_PACK);
+    let err = conn
+        .unpack_message_v2(&packed)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    // Ed25519 verkeys are not supported as recipients
+    let ed_key = conn
+        .create_keypair(KeyAlg::ED25519, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let err = conn
+        .pack_message_v2(
+            vec![ed_key.ident.as_str()],
+            None,
+            &msg,
+            JweEncryption::default(),
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+}
+
+pub async fn db_keypair_key_agreement<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let concat = KeyAgreementKdf::ConcatKdf {
+        alg_id: "A256GCM".to_string(),
+        apu: b"Alice".to_vec(),
+        apv: b"Bob".to_vec(),
+        key_len: 32,
+    };
+    let hkdf = KeyAgreementKdf::Hkdf {
+        salt: b"salt".to_vec(),
+        info: b"info".to_vec(),
+        key_len: 32,
+    };
+
+    for alg in &[KeyAlg::X25519, KeyAlg::P256] {
+        let alice = conn
+            .create_keypair(alg.clone(), None, None, None)
+            .await
+            .expect(ERR_CREATE_KEYPAIR);
+        let bob = conn
+            .create_keypair(alg.clone(), None, None, None)
+            .await
+            .expect(ERR_CREATE_KEYPAIR);
+
+        let key_concat = conn
+            .key_agreement(&alice.ident, &bob.ident, &concat)
+            .await
+            .expect(ERR_KEY_AGREEMENT);
+        assert_eq!(key_concat.len(), 32);
+        assert_eq!(
+            conn.key_agreement(&bob.ident, &alice.ident, &concat)
+                .await
+                .expect(ERR_KEY_AGREEMENT),
+            key_concat
+        );
+
+        let key_hkdf = conn
+            .key_agreement(&alice.ident, &bob.ident, &hkdf)
+            .await
+            .expect(ERR_KEY_AGREEMENT);
+        assert_eq!(
+            conn.key_agreement(&bob.ident, &alice.ident, &hkdf)
+                .await
+                .expect(ERR_KEY_AGREEMENT),
+            key_hkdf
+        );
+        assert_ne!(key_concat, key_hkdf);
+    }
+
+    // Ed25519 keypairs are converted to X25519
+    let ed_key = conn
+        .create_keypair(KeyAlg::ED25519, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let x_key = conn
+        .create_keypair(KeyAlg::X25519, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    assert_eq!(
+        conn.key_agreement(&ed_key.ident, &x_key.ident, &hkdf)
+            .await
+            .expect(ERR_KEY_AGREEMENT),
+        conn.key_agreement(&x_key.ident, &ed_key.ident, &hkdf)
+            .await
+            .expect(ERR_KEY_AGREEMENT)
+    );
+
+    // the peer key must use a compatible algorithm
+    let p256_key = conn
+        .create_keypair(KeyAlg::P256, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let err = conn
+        .key_agreement(&x_key.ident, &p256_key.ident, &hkdf)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+
+    let err = conn
+        .key_agreement(
+            &x_key.ident,
+            &ed_key.ident,
+            &KeyAgreementKdf::Hkdf {
+                salt: vec![],
+                info: vec![],
+                key_len: 0,
+            },
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+
+    // the derived key length is limited for both key derivation functions
+    for kdf in &[
+        KeyAgreementKdf::ConcatKdf {
+            alg_id: "A256GCM".to_string(),
+            apu: vec![],
+            apv: vec![],
+            key_len: MAX_DERIVED_KEY_LENGTH + 1,
+        },
+        KeyAgreementKdf::Hkdf {
+            salt: vec![],
+            info: vec![],
+            key_len: usize::MAX,
+        },
+    ] {
+        let err = conn
+            .key_agreement(&x_key.ident, &ed_key.ident, kdf)
+            .await
+            .expect_err(ERR_REQ_ERR);
+        assert_eq!(err.kind(), ErrorKind::Input);
+    }
+    let key = conn
+        .key_agreement(
+            &x_key.ident,
+            &ed_key.ident,
+            &KeyAgreementKdf::ConcatKdf {
+                alg_id: "A256GCM".to_string(),
+                apu: vec![],
+                apv: vec![],
+                key_len: MAX_DERIVED_KEY_LENGTH,
+            },
+        )
+        .await
+        .expect(ERR_KEY_AGREEMENT);
+    assert_eq!(key.len(), MAX_DERIVED_KEY_LENGTH);
+
+    let err = conn
+        .key_agreement("unknown", &x_key.ident, &hkdf)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+pub async fn db_keypair_x25519<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let seed = b"testseed000000000000000000000001";
+    let key_info = conn
+        .create_keypair(KeyAlg::X25519, None, Some(seed), None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    assert_eq!(
+        key_info.ident,
+        derive_verkey(KeyAlg::X25519, seed).expect("Error deriving verkey")
+    );
+    assert!(key_info.ident.ends_with(":x25519"));
+
+    let found = conn
+        .fetch_key(key_info.category.clone(), &key_info.ident, false)
+        .await
+        .expect(ERR_FETCH_KEY);
+    assert_eq!(Some(key_info.clone()), found);
+
+    // key agreement keys cannot be used for signing
+    let err = conn
+        .sign_message(&key_info.ident, b"message")
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+    let sign_key = conn
+        .create_keypair(KeyAlg::ED25519, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let converted = conn
+        .convert_keypair(&sign_key.ident, KeyAlg::X25519, None, None)
+        .await
+        .expect(ERR_CONVERT_KEYPAIR);
+    assert_eq!(converted.params.alg, KeyAlg::X25519);
+    let found = conn
+        .fetch_key(converted.category.clone(), &converted.ident, false)
+        .await
+        .expect(ERR_FETCH_KEY);
+    assert_eq!(Some(converted.clone()), found);
+
+    let err = conn
+        .convert_keypair(&converted.ident, KeyAlg::X25519, None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+}
+
+pub async fn db_keypair_secp256k1<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let key_info = conn
+        .create_keypair(KeyAlg::Secp256k1, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    assert!(key_info.ident.ends_with(":secp256k1"));
+    assert_eq!(key_info.params.pub_key.as_ref().map(Vec::len), Some(33));
+
+    let found = conn
+        .fetch_key(key_info.category.clone(), &key_info.ident, false)
+        .await
+        .expect(ERR_FETCH_KEY);
+    assert_eq!(Some(key_info.clone()), found);
+
+    let message = b"message".to_vec();
+    let sig = conn
+        .sign_message(&key_info.ident, &message)
+        .await
+        .expect(ERR_SIGN);
+    assert_eq!(sig.len(), 64);
+
+    assert_eq!(
+        verify_signature(&key_info.ident, &message, &sig).expect(ERR_VERIFY),
+        true
+    );
+    assert_eq!(
+        verify_signature(&key_info.ident, b"bad input", &sig).expect(ERR_VERIFY),
+        false
+    );
+    assert_eq!(
+        verify_signature(&key_info.ident, &message, b"bad sig").expect(ERR_VERIFY),
+        false
+    );
+
+    let err = verify_signature("not a key:secp256k1", &message, &sig).expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+
+    let seed = b"testseed000000000000000000000001";
+    let seeded = conn
+        .create_keypair(KeyAlg::Secp256k1, None, Some(seed), None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    assert_eq!(
+        seeded.ident,
+        derive_verkey(KeyAlg::Secp256k1, seed).expect("Error deriving verkey")
+    );
+}
+
+pub async fn db_keypair_p256<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let key_info = conn
+        .create_keypair(KeyAlg::P256, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    assert!(key_info.ident.ends_with(":p256"));
+
+    let found = conn
+        .fetch_key(key_info.category.clone(), &key_info.ident, false)
+        .await
+        .expect(ERR_FETCH_KEY);
+    assert_eq!(Some(key_info.clone()), found);
+
+    let message = b"message".to_vec();
+    for format in &[SignatureFormat::Raw, SignatureFormat::Der] {
+        let sig = conn
+            .sign_message_format(&key_info.ident, &message, *format)
+            .await
+            .expect(ERR_SIGN);
+        assert_eq!(
+            verify_signature(&key_info.ident, &message, &sig).expect(ERR_VERIFY),
+            true
+        );
+        assert_eq!(
+            verify_signature(&key_info.ident, b"bad input", &sig).expect(ERR_VERIFY),
+            false
+        );
+    }
+
+    // DER signatures are not supported for Ed25519 keys
+    let ed_key = conn
+        .create_keypair(KeyAlg::ED25519, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let err = conn
+        .sign_message_format(&ed_key.ident, &message, SignatureFormat::Der)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+}
+
+pub async fn db_keypair_bls<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let seed = b"testseed000000000000000000000001";
+    for (alg, pk_len) in &[(KeyAlg::Bls12381G1, 48), (KeyAlg::Bls12381G2, 96)] {
+        let key_info = conn
+            .create_keypair(alg.clone(), None, Some(seed), None)
+            .await
+            .expect(ERR_CREATE_KEYPAIR);
+        assert!(key_info.ident.ends_with(&format!(":{}", alg)));
+        assert_eq!(key_info.params.alg, *alg);
+        assert_eq!(
+            key_info.params.pub_key.as_ref().map(|pk| pk.len()),
+            Some(*pk_len)
+        );
+        assert_eq!(
+            key_info.ident,
+            derive_verkey(alg.clone(), seed).expect("Error deriving verkey")
+        );
+
+        let found = conn
+            .fetch_key(key_info.category.clone(), &key_info.ident, false)
+            .await
+            .expect(ERR_FETCH_KEY);
+        assert_eq!(Some(key_info.clone()), found);
+
+        let err = conn
+            .sign_message(&key_info.ident, b"message")
+            .await
+            .expect_err(ERR_REQ_ERR);
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    let err = conn
+        .create_keypair(KeyAlg::Bls12381G1, None, Some(b"short seed"), None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+}
+
+pub async fn db_secret_key_encrypt_decrypt<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let message = b"message".to_vec();
+    let aad = b"associated data".to_vec();
+    for alg in &[
+        KeyAlg::ChaCha20Poly1305,
+        KeyAlg::XChaCha20Poly1305,
+        KeyAlg::Aes256Gcm,
+    ] {
+        let key_info = conn
+            .create_key(alg.clone(), None, Some("metadata"), None)
+            .await
+            .expect(ERR_CREATE_KEY);
+        assert_eq!(key_info.category, KeyCategory::SecretKey);
+        assert_eq!(key_info.params.pub_key, None);
+
+        let found = conn
+            .fetch_key(KeyCategory::SecretKey, &key_info.ident, false)
+            .await
+            .expect(ERR_FETCH_KEY);
+        assert_eq!(Some(key_info.clone()), found);
+
+        let enc = conn
+            .encrypt_with_key(&key_info.ident, &message, Some(&aad))
+            .await
+            .expect(ERR_ENCRYPT);
+        assert_ne!(&enc[..], &message[..]);
+        let dec = conn
+            .decrypt_with_key(&key_info.ident, &enc, Some(&aad))
+            .await
+            .expect(ERR_DECRYPT);
+        assert_eq!(dec, message);
+
+        let err = conn
+            .decrypt_with_key(&key_info.ident, &enc, None)
+            .await
+            .expect_err(ERR_REQ_ERR);
+        assert_eq!(err.kind(), ErrorKind::Encryption);
+    }
+
+    let key_info = conn
+        .create_key(KeyAlg::ChaCha20Poly1305, Some("named key"), None, None)
+        .await
+        .expect(ERR_CREATE_KEY);
+    assert_eq!(key_info.ident, "named key");
+    let err = conn
+        .create_key(KeyAlg::ChaCha20Poly1305, Some("named key"), None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+
+    // keypairs cannot be used for symmetric encryption
+    let keypair = conn
+        .create_keypair(KeyAlg::ED25519, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let err = conn
+        .encrypt_with_key(&keypair.ident, &message, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let err = conn
+        .create_key(KeyAlg::ED25519, None, None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+    conn.remove_key(KeyCategory::SecretKey, &key_info.ident)
+        .await
+        .expect("Error removing key");
+    let err = conn
+        .encrypt_with_key(&key_info.ident, &message, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+pub async fn db_import_key<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    // RFC 8037 appendix A.1
+    let jwk = br#"{"kty":"OKP","crv":"Ed25519",
+        "d":"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A",
+        "x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#;
+    let seed =
+        hex::decode("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60").unwrap();
+    let key_info = conn
+        .import_key(KeyAlg::ED25519, jwk, KeyFormat::Jwk, Some("metadata"), None)
+        .await
+        .expect(ERR_IMPORT_KEY);
+    assert_eq!(
+        key_info.ident,
+        derive_verkey(KeyAlg::ED25519, &seed).expect("Error deriving verkey")
+    );
+    let found = conn
+        .fetch_key(KeyCategory::KeyPair, &key_info.ident, false)
+        .await
+        .expect(ERR_FETCH_KEY);
+    assert_eq!(Some(key_info.clone()), found);
+
+    // the same key cannot be imported twice
+    let err = conn
+        .import_key(KeyAlg::ED25519, &seed, KeyFormat::Raw, None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+
+    // the expanded secret key must contain the matching public key
+    let mut expanded = seed.clone();
+    expanded.extend_from_slice(&[0u8; 32]);
+    let err = conn
+        .import_key(KeyAlg::ED25519, &expanded, KeyFormat::Raw, None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+
+    let pkcs8 = hex::decode(
+        "308187020100301306072a8648ce3d020106082a8648ce3d030107046d306b02\
+         010104208e9b109e719098bf980487df1f5d77e9cb29606ebed2263b5f57c213\
+         df84f4b2a144034200047fcdce2770f6c45d4183cbee6fdb4b7b580733357be9\
+         ef13bacf6e3c7bd15445c7f144cd1bbd9b7e872cdfedb9eeb9f4b3695d6ea90b\
+         24ad8a4623288588e5ad",
+    )
+    .unwrap();
+    let key_info = conn
+        .import_key(KeyAlg::P256, &pkcs8, KeyFormat::Pkcs8, None, None)
+        .await
+        .expect(ERR_IMPORT_KEY);
+    assert!(key_info.ident.ends_with(":p256"));
+    let message = b"message".to_vec();
+    let sig = conn
+        .sign_message(&key_info.ident, &message)
+        .await
+        .expect(ERR_SIGN);
+    assert_eq!(
+        verify_signature(&key_info.ident, &message, &sig).expect(ERR_VERIFY),
+        true
+    );
+
+    let secret = [1u8; 32];
+    let key_info = conn
+        .import_key(
+            KeyAlg::XChaCha20Poly1305,
+            &secret,
+            KeyFormat::Raw,
+            None,
+            None,
+        )
+        .await
+        .expect(ERR_IMPORT_KEY);
+    assert_eq!(key_info.category, KeyCategory::SecretKey);
+    let enc = conn
+        .encrypt_with_key(&key_info.ident, &message, None)
+        .await
+        .expect(ERR_ENCRYPT);
+    let dec = conn
+        .decrypt_with_key(&key_info.ident, &enc, None)
+        .await
+        .expect(ERR_DECRYPT);
+    assert_eq!(dec, message);
+    let err = conn
+        .import_key(
+            KeyAlg::XChaCha20Poly1305,
+            &secret,
+            KeyFormat::Raw,
+            None,
+            None,
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+
+    let err = conn
+        .import_key(
+            KeyAlg::XChaCha20Poly1305,
+            &pkcs8,
+            KeyFormat::Pkcs8,
+            None,
+            None,
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    let err = conn
+        .import_key(KeyAlg::Secp256k1, &pkcs8, KeyFormat::Pkcs8, None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+}
+
+pub async fn db_export_key<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    // RFC 8037 appendix A.1
+    let seed =
+        hex::decode("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60").unwrap();
+    let key_info = conn
+        .import_key(KeyAlg::ED25519, &seed, KeyFormat::Raw, None, None)
+        .await
+        .expect(ERR_IMPORT_KEY);
+
+    let public_jwk =
+        r#"{"kty":"OKP","crv":"Ed25519","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#;
+    assert_eq!(key_info.to_jwk(false).unwrap(), public_jwk);
+    let jwk = conn
+        .export_key(KeyCategory::KeyPair, &key_info.ident, KeyFormat::Jwk, false)
+        .await
+        .expect(ERR_EXPORT_KEY);
+    assert_eq!(jwk, public_jwk.as_bytes().to_vec());
+
+    let private_jwk = conn
+        .export_key(KeyCategory::KeyPair, &key_info.ident, KeyFormat::Jwk, true)
+        .await
+        .expect(ERR_EXPORT_KEY);
+    assert!(private_jwk
+        .as_opt_str()
+        .unwrap()
+        .contains(r#""d":"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A""#));
+    let raw_pk = conn
+        .export_key(KeyCategory::KeyPair, &key_info.ident, KeyFormat::Raw, false)
+        .await
+        .expect(ERR_EXPORT_KEY);
+    assert_eq!(Some(raw_pk.to_vec()), key_info.params.pub_key);
+
+    let restricted = conn
+        .create_keypair(KeyAlg::P256, Some(r#"{"exportable": false}"#), None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    assert!(!restricted.is_exportable());
+    assert!(restricted.to_jwk(false).is_ok());
+    for format in &[KeyFormat::Jwk, KeyFormat::Raw] {
+        let err = conn
+            .export_key(KeyCategory::KeyPair, &restricted.ident, *format, true)
+            .await
+            .expect_err(ERR_REQ_ERR);
+        assert_eq!(err.kind(), ErrorKind::Forbidden);
+    }
+
+    // replacing the metadata does not affect the export policy
+    conn.update_key(KeyCategory::KeyPair, &restricted.ident, None, None)
+        .await
+        .expect(ERR_UPDATE_KEY);
+    let err = conn
+        .export_key(
+            KeyCategory::KeyPair,
+            &restricted.ident,
+            KeyFormat::Jwk,
+            true,
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Forbidden);
+
+    // a keypair converted from a non-exportable key is not exportable
+    let restricted_sign = conn
+        .create_keypair(
+            KeyAlg::ED25519,
+            Some(r#"{"exportable": false}"#),
+            None,
+            None,
+        )
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let converted = conn
+        .convert_keypair(&restricted_sign.ident, KeyAlg::X25519, None, None)
+        .await
+        .expect(ERR_CONVERT_KEYPAIR);
+    assert!(!converted.is_exportable());
+    for format in &[KeyFormat::Jwk, KeyFormat::Raw] {
+        let err = conn
+            .export_key(KeyCategory::KeyPair, &converted.ident, *format, true)
+            .await
+            .expect_err(ERR_REQ_ERR);
+        assert_eq!(err.kind(), ErrorKind::Forbidden);
+    }
+
+    let err = conn
+        .export_key(
+            KeyCategory::KeyPair,
+            &key_info.ident,
+            KeyFormat::Pkcs8,
+            true,
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    drop(conn);
+
+    // the exported private key can be imported into another profile
+    let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    let mut conn = db.session(Some(profile)).await.expect(ERR_SESSION);
+    let imported = conn
+        .import_key(KeyAlg::ED25519, &private_jwk, KeyFormat::Jwk, None, None)
+        .await
+        .expect(ERR_IMPORT_KEY);
+    assert_eq!(imported.ident, key_info.ident);
+}
+
+pub async fn db_keypair_jws<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let payload = b"{\"iss\":\"joe\"}";
+    for alg in &[KeyAlg::ED25519, KeyAlg::Secp256k1, KeyAlg::P256] {
+        let key_info = conn
+            .create_keypair(alg.clone(), None, None, None)
+            .await
+            .expect(ERR_CREATE_KEYPAIR);
+
+        for serialization in &[JwsSerialization::Compact, JwsSerialization::Json] {
+            let jws = conn
+                .sign_jws(&key_info.ident, payload, None, *serialization, false)
+                .await
+                .expect(ERR_SIGN);
+            assert_eq!(
+                verify_jws(&jws, None, &key_info.ident).expect(ERR_VERIFY),
+                true
+            );
+
+            let detached = conn
+                .sign_jws(&key_info.ident, payload, None, *serialization, true)
+                .await
+                .expect(ERR_SIGN);
+            assert_eq!(
+                verify_jws(&detached, Some(payload), &key_info.ident).expect(ERR_VERIFY),
+                true
+            );
+            assert_eq!(
+                verify_jws(&detached, Some(b"other"), &key_info.ident).expect(ERR_VERIFY),
+                false
+            );
+        }
+    }
+
+    let key_info = conn
+        .create_keypair(KeyAlg::ED25519, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let mut headers = serde_json::Map::new();
+    headers.insert("b64".to_string(), false.into());
+    headers.insert("typ".to_string(), "JWT".into());
+    let jws = conn
+        .sign_jws(
+            &key_info.ident,
+            payload,
+            Some(&headers),
+            JwsSerialization::Json,
+            false,
+        )
+        .await
+        .expect(ERR_SIGN);
+    assert!(jws.contains(r#""payload":"{\"iss\":\"joe\"}""#));
+    assert_eq!(
+        verify_jws(&jws, None, &key_info.ident).expect(ERR_VERIFY),
+        true
+    );
+
+    // the signature must be produced by the identified key
+    let other_key = conn
+        .create_keypair(KeyAlg::ED25519, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    assert_eq!(
+        verify_jws(&jws, None, &other_key.ident).expect(ERR_VERIFY),
+        false
+    );
+
+    // the JWS algorithm must match the signing key
+    headers.insert("alg".to_string(), "ES256".into());
+    let err = conn
+        .sign_jws(
+            &key_info.ident,
+            payload,
+            Some(&headers),
+            JwsSerialization::Compact,
+            true,
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+
+    let bls_key = conn
+        .create_keypair(KeyAlg::Bls12381G2, None, None, None)
+        .await
+        .expect(ERR_CREATE_KEYPAIR);
+    let err = conn
+        .sign_jws(
+            &bls_key.ident,
+            payload,
+            None,
+            JwsSerialization::Compact,
+            false,
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+}
+
 pub async fn db_txn_rollback<DB: Backend>(db: &Store<DB>) {
     let test_row = Entry::new("category", "name", "value", None);
 
@@ -707,3 +1535,481 @@ pub async fn db_txn_fetch_for_update<DB: Backend>(db: &Store<DB>) {
 
     conn.commit().await.expect("Error committing transaction");
 }
+
+pub async fn db_subscribe<DB: Backend>(db: &Store<DB>) {
+    let mut changes = db
+        .subscribe(None, Some("category"))
+        .await
+        .expect(ERR_SUBSCRIBE);
+    let change = |operation, name: Option<&str>| EntryChange {
+        operation,
+        category: "category".to_string(),
+        name: name.map(str::to_string),
+    };
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    conn.insert("other", "name", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "name", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.replace("category", "name", b"updated", None, None)
+        .await
+        .expect(ERR_REPLACE);
+    drop(conn);
+
+    let mut txn = db.transaction(None).await.expect(ERR_TRANSACTION);
+    txn.insert("category", "skip", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    txn.rollback()
+        .await
+        .expect("Error rolling back transaction");
+
+    let mut txn = db.transaction(None).await.expect(ERR_TRANSACTION);
+    txn.remove("category", "name")
+        .await
+        .expect("Error removing test row");
+    txn.insert("category", "name2", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    txn.remove_all("category", None)
+        .await
+        .expect(ERR_REMOVE_ALL);
+    txn.commit().await.expect("Error committing transaction");
+
+    let expected = vec![
+        change(EntryOperation::Insert, Some("name")),
+        change(EntryOperation::Replace, Some("name")),
+        change(EntryOperation::Remove, Some("name")),
+        change(EntryOperation::Insert, Some("name2")),
+        change(EntryOperation::Remove, None),
+    ];
+    for exp in expected {
+        let found = changes
+            .next()
+            .await
+            .expect("Expected change")
+            .expect(ERR_CHANGE);
+        assert_eq!(found, exp);
+    }
+}
+
+pub async fn db_profile_quota<DB: Backend>(db: &Store<DB>) {
+    let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    db.set_profile_quota(
+        Some(profile.clone()),
+        ProfileQuota {
+            max_items: Some(2),
+            max_bytes: None,
+        },
+    )
+    .await
+    .expect(ERR_QUOTA);
+
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    conn.insert("category", "one", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "two", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    let err = conn
+        .insert("category", "three", b"value", None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::QuotaExceeded);
+    conn.replace("category", "two", b"other", None, None)
+        .await
+        .expect(ERR_REPLACE);
+
+    drop(conn);
+
+    let usage = db
+        .profile_usage(Some(profile.clone()))
+        .await
+        .expect(ERR_USAGE);
+    assert_eq!(usage.items, 2);
+    assert!(usage.bytes > 0);
+
+    // limit the total size to the current usage
+    let quota = ProfileQuota {
+        max_items: None,
+        max_bytes: Some(usage.bytes),
+    };
+    db.set_profile_quota(Some(profile.clone()), quota)
+        .await
+        .expect(ERR_QUOTA);
+
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    conn.replace("category", "two", b"equal", None, None)
+        .await
+        .expect(ERR_REPLACE);
+    let err = conn
+        .replace("category", "two", b"larger value", None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::QuotaExceeded);
+    conn.insert("category", "three", b"value", None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    drop(conn);
+
+    let usage = db
+        .profile_usage(Some(profile.clone()))
+        .await
+        .expect(ERR_USAGE);
+    assert_eq!(usage.items, 2);
+    assert_eq!(usage.quota, quota);
+
+    assert_eq!(
+        db.set_profile_quota(Some("not a profile".to_string()), quota)
+            .await
+            .expect_err(ERR_REQ_ERR)
+            .kind(),
+        ErrorKind::NotFound
+    );
+
+    // expired entries do not count toward the quota
+    let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    let quota = ProfileQuota {
+        max_items: Some(1),
+        max_bytes: None,
+    };
+    db.set_profile_quota(Some(profile.clone()), quota)
+        .await
+        .expect(ERR_QUOTA);
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    conn.insert("category", "expired", b"value", None, Some(-1000))
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "active", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    drop(conn);
+
+    let usage = db.profile_usage(Some(profile)).await.expect(ERR_USAGE);
+    assert_eq!(usage.items, 1);
+}
+
+pub async fn db_txn_cancel<DB: Backend>(db: &Store<DB>) {
+    let test_row = Entry::new("category", "name", "value", None);
+
+    let mut conn = db.transaction(None).await.expect(ERR_TRANSACTION);
+    conn.insert(
+        &test_row.category,
+        &test_row.name,
+        &test_row.value,
+        test_row.tags.as_ref().map(|t| t.as_slice()),
+        None,
+    )
+    .await
+    .expect(ERR_INSERT);
+
+    let cancel = conn.cancel_handle();
+    cancel.cancel();
+    assert!(cancel.is_cancelled());
+    let err = conn
+        .fetch(&test_row.category, &test_row.name, false)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+
+    // the session is closed once interrupted
+    let err = conn
+        .count(&test_row.category, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+    conn.rollback()
+        .await
+        .expect("Error rolling back transaction");
+
+    // the transaction was rolled back
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    let row = conn
+        .fetch(&test_row.category, &test_row.name, false)
+        .await
+        .expect(ERR_FETCH);
+    assert_eq!(row, None);
+}
+
+pub async fn db_txn_closure<DB: Backend>(db: &Store<DB>) {
+    let test_row = Entry::new("category", "name", "value", None);
+
+    db.with_transaction(None, |txn| {
+        let row = test_row.clone();
+        Box::pin(async move {
+            txn.insert(&row.category, &row.name, &row.value, None, None)
+                .await
+        })
+    })
+    .await
+    .expect("Error committing transaction");
+
+    // a failed closure is rolled back
+    let err = db
+        .with_transaction(None, |txn| {
+            let row = test_row.clone();
+            Box::pin(async move {
+                txn.insert(&row.category, "other", &row.value, None, None)
+                    .await?;
+                txn.insert(&row.category, &row.name, &row.value, None, None)
+                    .await
+            })
+        })
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    let count = conn.count(&test_row.category, None).await.expect(ERR_COUNT);
+    assert_eq!(count, 1);
+}
+
+pub async fn db_txn_multi_profile<DB: Backend>(db: &Store<DB>) {
+    let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    let default_profile = db.get_profile_name().to_string();
+
+    let mut conn = db.transaction(None).await.expect(ERR_TRANSACTION);
+    conn.insert("category", "default", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.set_profile(&profile).expect("Error switching profile");
+    conn.insert("category", "other", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 1);
+    conn.set_profile(&default_profile)
+        .expect("Error switching profile");
+    assert!(conn
+        .fetch("category", "default", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+    conn.commit().await.expect("Error committing transaction");
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    assert!(conn
+        .fetch("category", "default", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+    assert!(conn
+        .fetch("category", "other", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_none());
+    drop(conn);
+
+    let mut conn = db.session(Some(profile)).await.expect(ERR_SESSION);
+    assert!(conn
+        .fetch("category", "other", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+    assert!(conn
+        .fetch("category", "default", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_none());
+}
+
+pub async fn db_soft_delete<DB: Backend>(db: &Store<DB>) {
+    db.set_soft_delete(SoftDelete::Categories(
+        vec!["category".to_string()].into_iter().collect(),
+    ))
+    .expect("Error setting soft delete policy");
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    for name in &["one", "two"] {
+        conn.insert("category", name, b"value", None, None)
+            .await
+            .expect(ERR_INSERT);
+    }
+    conn.insert("other", "one", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+
+    conn.remove("category", "one")
+        .await
+        .expect("Error removing test row");
+    assert!(conn
+        .fetch("category", "one", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_none());
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 1);
+    conn.undelete("category", "one").await.expect(ERR_UNDELETE);
+    assert!(conn
+        .fetch("category", "one", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+
+    // restoring a live entry fails
+    let err = conn
+        .undelete("category", "one")
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    // entries in other categories are removed immediately
+    conn.remove("other", "one")
+        .await
+        .expect("Error removing test row");
+    let err = conn.undelete("other", "one").await.expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    assert_eq!(
+        conn.remove_all("category", None)
+            .await
+            .expect(ERR_REMOVE_ALL),
+        2
+    );
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 0);
+    conn.undelete("category", "two").await.expect(ERR_UNDELETE);
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 1);
+
+    // an insert replaces a deleted entry with the same name
+    conn.insert("category", "one", b"updated", None, None)
+        .await
+        .expect(ERR_INSERT);
+    let row = conn
+        .fetch("category", "one", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.value.as_ref(), b"updated");
+
+    conn.remove("category", "two")
+        .await
+        .expect("Error removing test row");
+    drop(conn);
+
+    assert_eq!(
+        db.purge_deleted(None, Duration::from_secs(3600))
+            .await
+            .expect(ERR_PURGE),
+        0
+    );
+    assert_eq!(
+        db.purge_deleted(None, Duration::from_secs(0))
+            .await
+            .expect(ERR_PURGE),
+        1
+    );
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    let err = conn
+        .undelete("category", "two")
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    drop(conn);
+
+    // a restored entry counts toward the profile quota
+    let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    conn.insert("category", "one", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.remove("category", "one")
+        .await
+        .expect("Error removing test row");
+    conn.insert("category", "two", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    db.set_profile_quota(
+        Some(profile.clone()),
+        ProfileQuota {
+            max_items: Some(1),
+            max_bytes: None,
+        },
+    )
+    .await
+    .expect(ERR_QUOTA);
+    let err = conn
+        .undelete("category", "one")
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::QuotaExceeded);
+    drop(conn);
+
+    // purging is limited to the selected profile
+    assert_eq!(
+        db.purge_deleted(None, Duration::from_secs(0))
+            .await
+            .expect(ERR_PURGE),
+        0
+    );
+    assert_eq!(
+        db.purge_deleted(Some(profile), Duration::from_secs(0))
+            .await
+            .expect(ERR_PURGE),
+        1
+    );
+}
+
+pub async fn db_entry_history<DB: Backend>(db: &Store<DB>) {
+    db.set_entry_history(EntryHistory {
+        categories: vec!["category".to_string()].into_iter().collect(),
+        max_versions: Some(2),
+    })
+    .expect("Error setting entry history policy");
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    let tags = vec![EntryTag::Encrypted("t1".to_string(), "v1".to_string())];
+    conn.insert("category", "name", b"one", Some(tags.as_slice()), None)
+        .await
+        .expect(ERR_INSERT);
+    assert!(conn
+        .fetch_history("category", "name")
+        .await
+        .expect(ERR_HISTORY)
+        .is_empty());
+
+    conn.replace("category", "name", b"two", None, None)
+        .await
+        .expect(ERR_REPLACE);
+    conn.replace("category", "name", b"three", None, None)
+        .await
+        .expect(ERR_REPLACE);
+    let versions = conn
+        .fetch_history("category", "name")
+        .await
+        .expect(ERR_HISTORY);
+    assert_eq!(
+        versions,
+        vec![
+            Entry::new("category", "name", "two", None),
+            Entry::new("category", "name", "one", Some(tags.clone())),
+        ]
+    );
+
+    // only the most recent versions are retained
+    conn.remove("category", "name")
+        .await
+        .expect("Error removing test row");
+    let versions = conn
+        .fetch_history("category", "name")
+        .await
+        .expect(ERR_HISTORY);
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].value.as_ref(), b"three");
+    assert_eq!(versions[1].value.as_ref(), b"two");
+
+    // entries in other categories are not tracked
+    conn.insert("other", "name", b"one", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.replace("other", "name", b"two", None, None)
+        .await
+        .expect(ERR_REPLACE);
+    assert!(conn
+        .fetch_history("other", "name")
+        .await
+        .expect(ERR_HISTORY)
+        .is_empty());
+}
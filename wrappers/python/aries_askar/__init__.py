@@ -1,17 +1,25 @@
 """aries-askar Python wrapper library"""
 
-from .bindings import derive_verkey, generate_raw_key, verify_signature, version
+from .bindings import (
+    derive_verkey,
+    generate_raw_key,
+    verify_jws,
+    verify_signature,
+    version,
+)
 from .error import StoreError, StoreErrorCode
 from .store import Session, Store
-from .types import Entry, KeyAlg
+from .types import Entry, KeyAlg, KeyFormat
 
 __all__ = (
     "derive_verkey",
     "generate_raw_key",
+    "verify_jws",
     "verify_signature",
     "version",
     "Entry",
     "KeyAlg",
+    "KeyFormat",
     "Session",
     "Store",
     "StoreError",
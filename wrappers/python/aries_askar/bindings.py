@@ -435,6 +435,27 @@ async def verify_signature(
     )
 
 
+async def verify_jws(
+    jws: str,
+    signer_vk: str,
+    detached_payload: Union[str, bytes] = None,
+) -> bool:
+    """Verify a JWS in the compact or flattened JSON serialization.
+
+    The signature is checked against `signer_vk`, not the `kid` header.
+    """
+    return (
+        await do_call_async(
+            "askar_verify_jws",
+            encode_str(jws),
+            encode_bytes(detached_payload),
+            encode_str(signer_vk),
+            return_type=c_int8,
+        )
+        != 0
+    )
+
+
 def version() -> str:
     """Get the version of the installed aries-askar library."""
     lib = get_library()
@@ -668,6 +689,48 @@ async def session_create_keypair(
     )
 
 
+async def session_convert_keypair(
+    handle: SessionHandle,
+    ident: str,
+    alg: str,
+    metadata: str = None,
+    tags: dict = None,
+) -> str:
+    return str(
+        await do_call_async(
+            "askar_session_convert_keypair",
+            handle,
+            encode_str(ident),
+            encode_str(alg),
+            encode_str(metadata),
+            encode_str(None if tags is None else json.dumps(tags)),
+            return_type=StrBuffer,
+        )
+    )
+
+
+async def session_import_key(
+    handle: SessionHandle,
+    alg: str,
+    secret: Union[str, bytes],
+    format: str = None,
+    metadata: str = None,
+    tags: dict = None,
+) -> str:
+    return str(
+        await do_call_async(
+            "askar_session_import_key",
+            handle,
+            encode_str(alg),
+            encode_str(format),
+            encode_bytes(secret),
+            encode_str(metadata),
+            encode_str(None if tags is None else json.dumps(tags)),
+            return_type=StrBuffer,
+        )
+    )
+
+
 async def session_fetch_keypair(
     handle: SessionHandle, ident: str, for_update: bool = False
 ) -> Optional[EntrySetHandle]:
@@ -682,6 +745,24 @@ async def session_fetch_keypair(
         return EntrySetHandle(ptr)
 
 
+async def session_export_key(
+    handle: SessionHandle,
+    category: str,
+    ident: str,
+    format: str = None,
+    include_private: bool = False,
+) -> ByteBuffer:
+    return await do_call_async(
+        "askar_session_export_key",
+        handle,
+        encode_str(category),
+        encode_str(ident),
+        encode_str(format),
+        c_int8(include_private),
+        return_type=ByteBuffer,
+    )
+
+
 async def session_update_keypair(
     handle: SessionHandle, ident: str, metadata: str = None, tags: dict = None
 ):
@@ -708,6 +789,122 @@ async def session_sign_message(
     )
 
 
+async def session_sign_jws(
+    handle: SessionHandle,
+    key_ident: str,
+    payload: Union[str, bytes],
+    headers: dict = None,
+    serialization: str = None,
+    detached: bool = False,
+) -> str:
+    return str(
+        await do_call_async(
+            "askar_session_sign_jws",
+            handle,
+            encode_str(key_ident),
+            encode_bytes(payload),
+            encode_str(None if headers is None else json.dumps(headers)),
+            encode_str(serialization),
+            c_int8(detached),
+            return_type=StrBuffer,
+        )
+    )
+
+
+async def session_create_key(
+    handle: SessionHandle,
+    alg: str,
+    ident: str = None,
+    metadata: str = None,
+    tags: dict = None,
+) -> str:
+    return str(
+        await do_call_async(
+            "askar_session_create_key",
+            handle,
+            encode_str(alg),
+            encode_str(ident),
+            encode_str(metadata),
+            encode_str(None if tags is None else json.dumps(tags)),
+            return_type=StrBuffer,
+        )
+    )
+
+
+async def session_encrypt_with_key(
+    handle: SessionHandle,
+    key_ident: str,
+    message: Union[str, bytes],
+    aad: Union[str, bytes] = None,
+) -> ByteBuffer:
+    return await do_call_async(
+        "askar_session_encrypt_with_key",
+        handle,
+        encode_str(key_ident),
+        encode_bytes(message),
+        encode_bytes(aad),
+        return_type=ByteBuffer,
+    )
+
+
+async def session_decrypt_with_key(
+    handle: SessionHandle,
+    key_ident: str,
+    ciphertext: Union[str, bytes],
+    aad: Union[str, bytes] = None,
+) -> ByteBuffer:
+    return await do_call_async(
+        "askar_session_decrypt_with_key",
+        handle,
+        encode_str(key_ident),
+        encode_bytes(ciphertext),
+        encode_bytes(aad),
+        return_type=ByteBuffer,
+    )
+
+
+async def session_key_agreement_concat(
+    handle: SessionHandle,
+    local_ident: str,
+    peer_public_key: str,
+    alg_id: str,
+    apu: Union[str, bytes] = None,
+    apv: Union[str, bytes] = None,
+    key_len: int = 32,
+) -> ByteBuffer:
+    return await do_call_async(
+        "askar_session_key_agreement_concat",
+        handle,
+        encode_str(local_ident),
+        encode_str(peer_public_key),
+        encode_str(alg_id),
+        encode_bytes(apu),
+        encode_bytes(apv),
+        c_int64(key_len),
+        return_type=ByteBuffer,
+    )
+
+
+async def session_key_agreement_hkdf(
+    handle: SessionHandle,
+    local_ident: str,
+    peer_public_key: str,
+    salt: Union[str, bytes] = None,
+    info: Union[str, bytes] = None,
+    key_len: int = 32,
+) -> ByteBuffer:
+    return await do_call_async(
+        "askar_session_key_agreement_hkdf",
+        handle,
+        encode_str(local_ident),
+        encode_str(peer_public_key),
+        encode_bytes(salt),
+        encode_bytes(info),
+        c_int64(key_len),
+        return_type=ByteBuffer,
+    )
+
+
 async def session_pack_message(
     handle: SessionHandle,
     recipient_vks: Sequence[str],
@@ -738,6 +935,37 @@ async def session_unpack_message(
     return (result.unpacked, str(result.recipient), result.sender.opt_str())
 
 
+async def session_pack_message_v2(
+    handle: SessionHandle,
+    recipient_keys: Sequence[str],
+    from_key_ident: Optional[str],
+    message: Union[str, bytes],
+    enc: str = None,
+) -> ByteBuffer:
+    return await do_call_async(
+        "askar_session_pack_message_v2",
+        handle,
+        encode_str(",".join(recipient_keys)),
+        encode_str(from_key_ident),
+        encode_bytes(message),
+        encode_str(enc),
+        return_type=ByteBuffer,
+    )
+
+
+async def session_unpack_message_v2(
+    handle: SessionHandle,
+    message: Union[str, bytes],
+) -> (ByteBuffer, str, Optional[str]):
+    result = await do_call_async(
+        "askar_session_unpack_message_v2",
+        handle,
+        encode_bytes(message),
+        return_type=lib_unpack_result,
+    )
+    return (result.unpacked, str(result.recipient), result.sender.opt_str())
+
+
 async def scan_start(
     handle: StoreHandle,
     profile: Optional[str],
@@ -13,6 +13,13 @@ class StoreErrorCode(IntEnum):
     NOT_FOUND = 6
     UNEXPECTED = 7
     UNSUPPORTED = 8
+    QUOTA_EXCEEDED = 9
+    TIMEOUT = 10
+    CANCELLED = 11
+    CONNECTION = 12
+    CORRUPT = 13
+    STORAGE_FULL = 14
+    FORBIDDEN = 15
     WRAPPER = 99
 
 
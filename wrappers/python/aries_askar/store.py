@@ -7,7 +7,7 @@ from typing import Optional, Sequence, Union
 from . import bindings
 
 from .error import StoreError, StoreErrorCode
-from .types import Entry, EntryOperation, KeyAlg, KeyEntry
+from .types import Entry, EntryOperation, KeyAlg, KeyEntry, KeyFormat
 
 
 class EntrySet:
@@ -324,6 +324,43 @@ class Session:
             )
         )
 
+    async def convert_keypair(
+        self,
+        ident: str,
+        key_alg: KeyAlg,
+        *,
+        metadata: str = None,
+        tags: dict = None,
+    ) -> str:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot convert keypair with closed session"
+            )
+        return str(
+            await bindings.session_convert_keypair(
+                self._handle, ident, key_alg.value, metadata, tags
+            )
+        )
+
+    async def import_key(
+        self,
+        key_alg: KeyAlg,
+        secret: Union[str, bytes],
+        *,
+        format: KeyFormat = KeyFormat.RAW,
+        metadata: str = None,
+        tags: dict = None,
+    ) -> str:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot import key with closed session"
+            )
+        return str(
+            await bindings.session_import_key(
+                self._handle, key_alg.value, secret, format.value, metadata, tags
+            )
+        )
+
     async def fetch_keypair(
         self, ident: str, *, for_update: bool = False
     ) -> Optional[KeyEntry]:
@@ -337,6 +374,23 @@ class Session:
             result = KeyEntry(entry.category, entry.name, entry.value_json, entry.tags)
             return result
 
+    async def export_key(
+        self,
+        ident: str,
+        *,
+        category: str = "keypair",
+        format: KeyFormat = KeyFormat.JWK,
+        include_private: bool = False,
+    ) -> bytes:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot export key with closed session"
+            )
+        buf = await bindings.session_export_key(
+            self._handle, category, ident, format.value, include_private
+        )
+        return bytes(buf)
+
     async def update_keypair(
         self,
         ident: str,
@@ -358,6 +412,104 @@ class Session:
         buf = await bindings.session_sign_message(self._handle, key_ident, message)
         return bytes(buf)
 
+    async def sign_jws(
+        self,
+        key_ident: str,
+        payload: Union[str, bytes],
+        *,
+        headers: dict = None,
+        serialization: str = "compact",
+        detached: bool = False,
+    ) -> str:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot sign JWS with closed session"
+            )
+        return str(
+            await bindings.session_sign_jws(
+                self._handle, key_ident, payload, headers, serialization, detached
+            )
+        )
+
+    async def create_key(
+        self,
+        key_alg: KeyAlg,
+        *,
+        ident: str = None,
+        metadata: str = None,
+        tags: dict = None,
+    ) -> str:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot create key with closed session"
+            )
+        return str(
+            await bindings.session_create_key(
+                self._handle, key_alg.value, ident, metadata, tags
+            )
+        )
+
+    async def encrypt_with_key(
+        self, key_ident: str, message: Union[str, bytes], aad: Union[str, bytes] = None
+    ) -> bytes:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot encrypt message with closed session"
+            )
+        buf = await bindings.session_encrypt_with_key(
+            self._handle, key_ident, message, aad
+        )
+        return bytes(buf)
+
+    async def decrypt_with_key(
+        self,
+        key_ident: str,
+        ciphertext: Union[str, bytes],
+        aad: Union[str, bytes] = None,
+    ) -> bytes:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot decrypt message with closed session"
+            )
+        buf = await bindings.session_decrypt_with_key(
+            self._handle, key_ident, ciphertext, aad
+        )
+        return bytes(buf)
+
+    async def key_agreement(
+        self,
+        local_ident: str,
+        peer_public_key: str,
+        kdf: str = "concat",
+        key_len: int = 32,
+        *,
+        alg_id: str = None,
+        apu: Union[str, bytes] = None,
+        apv: Union[str, bytes] = None,
+        salt: Union[str, bytes] = None,
+        info: Union[str, bytes] = None,
+    ) -> bytes:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER,
+                "Cannot perform key agreement with closed session",
+            )
+        if kdf == "concat":
+            if not alg_id:
+                raise StoreError(
+                    StoreErrorCode.INPUT, "Algorithm identifier required for Concat KDF"
+                )
+            buf = await bindings.session_key_agreement_concat(
+                self._handle, local_ident, peer_public_key, alg_id, apu, apv, key_len
+            )
+        elif kdf == "hkdf":
+            buf = await bindings.session_key_agreement_hkdf(
+                self._handle, local_ident, peer_public_key, salt, info, key_len
+            )
+        else:
+            raise StoreError(StoreErrorCode.INPUT, f"Unsupported KDF: {kdf}")
+        return bytes(buf)
+
     async def pack_message(
         self,
         recipient_vks: Sequence[str],
@@ -387,6 +539,36 @@ class Session:
         )
         return (bytes(unpacked), recip, sender)
 
+    async def pack_message_v2(
+        self,
+        recipient_keys: Sequence[str],
+        from_key_ident: Optional[str],
+        message: Union[str, bytes],
+        enc: str = None,
+    ) -> bytes:
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot pack message with closed session"
+            )
+        return bytes(
+            await bindings.session_pack_message_v2(
+                self._handle, recipient_keys, from_key_ident, message, enc
+            )
+        )
+
+    async def unpack_message_v2(
+        self,
+        message: Union[str, bytes],
+    ) -> (bytes, str, Optional[str]):
+        if not self._handle:
+            raise StoreError(
+                StoreErrorCode.WRAPPER, "Cannot unpack message with closed session"
+            )
+        (unpacked, recip, sender) = await bindings.session_unpack_message_v2(
+            self._handle, message
+        )
+        return (bytes(unpacked), recip, sender)
+
     async def commit(self):
         if not self._is_txn:
             raise StoreError(StoreErrorCode.WRAPPER, "Session is not a transaction")
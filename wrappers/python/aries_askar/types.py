@@ -78,6 +78,20 @@ class KeyEntry:
 
 class KeyAlg(Enum):
     ED25519 = "ed25519"
+    X25519 = "x25519"
+    SECP256K1 = "secp256k1"
+    P256 = "p256"
+    BLS12381G1 = "bls12381g1"
+    BLS12381G2 = "bls12381g2"
+    C20P = "c20p"
+    XC20P = "xc20p"
+    A256GCM = "a256gcm"
+
+
+class KeyFormat(Enum):
+    RAW = "raw"
+    JWK = "jwk"
+    PKCS8 = "pkcs8"
 
 
 class EntryOperation(Enum):